@@ -1,12 +1,140 @@
-use rust_forge_boilerplate::common::infrastructure;
-use sqlx::migrate::MigrateDatabase;
-use std::env;
+use rust_forge_boilerplate::common::infrastructure::{
+    self,
+    migrations::{MigrationPlan, Migrations, RunOptions, destructive_statements},
+};
+use sqlx::{Postgres, migrate::MigrateDatabase, pool::PoolConnection};
+use std::{env, process};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+const USAGE: &str = "\
+Usage: migrator [COMMAND] [OPTIONS]
+
+Commands:
+  up [--to VERSION]   Apply pending migrations, up to VERSION if given (default)
+  down [--steps N]    Revert the latest N applied migrations (default 1)
+  redo                Revert the latest applied migration and apply it again
+  status              List applied and pending migrations with their checksums
+  create <NAME>       Create an empty <timestamp>_<NAME>.up.sql/.down.sql pair
+
+Options:
+  --dry-run           Print the SQL up, down and redo would run, without running it
+  --allow-destructive Run DROP TABLE/COLUMN, ALTER TYPE and the like when APP_ENV
+                      is production, where they are refused otherwise
+  -h, --help          Print this help";
+
+#[derive(Debug)]
+enum Command {
+    Run(MigrationPlan),
+    Status,
+    Create(String),
+}
+
+#[derive(Debug)]
+struct Cli {
+    command: Command,
+    dry_run: bool,
+    allow_destructive: bool,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Cli, String> {
+    let mut command = None;
+    let mut name = None;
+    let mut to = None;
+    let mut steps = None;
+    let mut dry_run = false;
+    let mut allow_destructive = false;
+
+    while let Some(arg) = args.next() {
+        // Options take their value as `--to VERSION` or `--to=VERSION`.
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag, Some(value)),
+            _ => (arg.as_str(), None),
+        };
+
+        match flag {
+            "--dry-run" if inline.is_none() => dry_run = true,
+            "--allow-destructive" if inline.is_none() => allow_destructive = true,
+            "--to" => {
+                let value = option_value(flag, inline, &mut args)?;
+                to = Some(
+                    value
+                        .parse::<i64>()
+                        .map_err(|_| format!("Invalid VERSION: {}", value))?,
+                );
+            }
+            "--steps" => {
+                let value = option_value(flag, inline, &mut args)?;
+                steps = Some(
+                    value
+                        .parse::<usize>()
+                        .map_err(|_| format!("Invalid --steps: {}", value))?,
+                );
+            }
+            _ if arg.starts_with('-') => return Err(format!("Unknown option: {}", arg)),
+            _ if command.is_none() => command = Some(arg),
+            _ if command.as_deref() == Some("create") && name.is_none() => name = Some(arg),
+            _ => return Err(format!("Unexpected argument: {}", arg)),
+        }
+    }
+
+    let command = match command.as_deref().unwrap_or("up") {
+        "up" => Command::Run(MigrationPlan::Up { to }),
+        "down" => Command::Run(MigrationPlan::Down {
+            steps: steps.unwrap_or(1),
+        }),
+        "redo" => Command::Run(MigrationPlan::Redo),
+        "status" => Command::Status,
+        "create" => Command::Create(name.ok_or("create needs a NAME")?),
+        other => return Err(format!("Unknown command: {}", other)),
+    };
+    if to.is_some() && !matches!(command, Command::Run(MigrationPlan::Up { .. })) {
+        return Err("--to only applies to up".to_string());
+    }
+    if steps.is_some() && !matches!(command, Command::Run(MigrationPlan::Down { .. })) {
+        return Err("--steps only applies to down".to_string());
+    }
+    if dry_run && !matches!(command, Command::Run(_)) {
+        return Err("--dry-run only applies to up, down and redo".to_string());
+    }
+    if allow_destructive && !matches!(command, Command::Run(_)) {
+        return Err("--allow-destructive only applies to up, down and redo".to_string());
+    }
+
+    Ok(Cli {
+        command,
+        dry_run,
+        allow_destructive,
+    })
+}
+
+fn option_value(
+    flag: &str,
+    inline: Option<&str>,
+    args: &mut impl Iterator<Item = String>,
+) -> Result<String, String> {
+    inline
+        .map(str::to_string)
+        .or_else(|| args.next())
+        .ok_or_else(|| format!("{} needs a value", flag))
+}
+
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+async fn main() {
     dotenv::dotenv().ok();
 
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return;
+    }
+    let cli = match parse_args(args.into_iter()) {
+        Ok(cli) => cli,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+
     tracing_subscriber::registry()
         .with(tracing_subscriber::EnvFilter::new(
             env::var("RUST_LOG").unwrap_or_else(|_| "info".into()),
@@ -14,27 +142,168 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
+    if let Err(e) = run(cli).await {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
+    let migrations = Migrations::from_env().await?;
+
+    match cli.command {
+        Command::Create(name) => {
+            for path in migrations.create(&name, chrono::Utc::now())? {
+                println!("Created {}", path.display());
+            }
+        }
+        Command::Status => {
+            let mut conn = connect(false).await?;
+            let applied = migrations.applied(&mut conn).await?;
+            println!(
+                "{:<16} {:<8} {:<10} {:<16} {:<24} DESCRIPTION",
+                "VERSION", "STATE", "REVERSIBLE", "CHECKSUM", "INSTALLED ON"
+            );
+            for status in migrations.status(&applied) {
+                println!(
+                    "{:<16} {:<8} {:<10} {:<16} {:<24} {}",
+                    status.version,
+                    status.state.as_str(),
+                    if status.reversible { "yes" } else { "no" },
+                    &hex::encode(&status.checksum)[..16],
+                    status
+                        .installed_on
+                        .map(|at| at.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+                        .unwrap_or_default(),
+                    status.description
+                );
+            }
+        }
+        Command::Run(plan) => {
+            let creates_database = matches!(plan, MigrationPlan::Up { .. }) && !cli.dry_run;
+            let mut conn = connect(creates_database).await?;
+
+            tracing::info!("Running migrations ({:?})...", plan);
+            // Destructive statements only need the flag in production;
+            // elsewhere they run with a warning.
+            let production = env::var("APP_ENV").is_ok_and(|app_env| app_env == "production");
+            let options = RunOptions {
+                dry_run: cli.dry_run,
+                allow_destructive: cli.allow_destructive || !production,
+            };
+            let steps = migrations.run(&mut conn, plan, options).await?;
+
+            if cli.dry_run {
+                for migration in &steps {
+                    println!(
+                        "-- {} {} {}",
+                        migration.migration_type.label(),
+                        migration.version,
+                        migration.description
+                    );
+                    for statement in destructive_statements(migration) {
+                        println!("-- DESTRUCTIVE: {}", statement.statement);
+                    }
+                    println!("{}\n", migration.sql.trim_end());
+                }
+                tracing::info!("Dry run: {} migrations would run", steps.len());
+            } else if steps.is_empty() {
+                tracing::info!("Nothing to migrate");
+            } else {
+                tracing::info!("Migrations completed successfully");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// One connection to `DATABASE_URL`, which the advisory lock is held on.
+async fn connect(
+    create_database: bool,
+) -> Result<PoolConnection<Postgres>, Box<dyn std::error::Error>> {
     let database_url =
         env::var("DATABASE_URL").expect("DATABASE_URL environment variable is required");
 
-    tracing::info!("Running migrations...");
-
-    if !sqlx::Postgres::database_exists(&database_url).await? {
+    if create_database && !Postgres::database_exists(&database_url).await? {
         tracing::info!("Database does not exist, creating...");
-        sqlx::Postgres::create_database(&database_url).await?;
+        Postgres::create_database(&database_url).await?;
     }
 
-    let database_max_connections: u32 = env::var("DATABASE_MAX_CONNECTIONS")
-        .unwrap_or_else(|_| "10".to_string())
-        .parse()
-        .expect("Invalid DATABASE_MAX_CONNECTIONS");
+    let pool = infrastructure::database::create_pool(&database_url, 1).await?;
+    Ok(pool.acquire().await?)
+}
 
-    let pool =
-        infrastructure::database::create_pool(&database_url, database_max_connections).await?;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    sqlx::migrate!("./migrations").run(&pool).await?;
+    fn parse(args: &[&str]) -> Result<Cli, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
 
-    tracing::info!("Migrations completed successfully");
+    #[test]
+    fn defaults_to_up() {
+        let cli = parse(&[]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Command::Run(MigrationPlan::Up { to: None })
+        ));
+        assert!(!cli.dry_run && !cli.allow_destructive);
+    }
 
-    Ok(())
+    #[test]
+    fn parses_to_with_or_without_equals() {
+        for args in [
+            &["up", "--to", "20261018100000"][..],
+            &["up", "--to=20261018100000"],
+        ] {
+            assert!(matches!(
+                parse(args).unwrap().command,
+                Command::Run(MigrationPlan::Up {
+                    to: Some(20261018100000)
+                })
+            ));
+        }
+    }
+
+    #[test]
+    fn parses_steps_and_flags() {
+        let cli = parse(&["down", "--steps=3", "--dry-run"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Command::Run(MigrationPlan::Down { steps: 3 })
+        ));
+        assert!(cli.dry_run);
+
+        let cli = parse(&["down", "--allow-destructive"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Command::Run(MigrationPlan::Down { steps: 1 })
+        ));
+        assert!(cli.allow_destructive);
+    }
+
+    #[test]
+    fn parses_create() {
+        assert!(matches!(
+            parse(&["create", "add_users"]).unwrap().command,
+            Command::Create(name) if name == "add_users"
+        ));
+        assert!(parse(&["create"]).is_err());
+    }
+
+    #[test]
+    fn rejects_bad_values_and_misplaced_options() {
+        assert!(parse(&["up", "--to"]).is_err());
+        assert!(parse(&["up", "--to=latest"]).is_err());
+        assert!(parse(&["down", "--steps", "-1"]).is_err());
+        assert!(parse(&["down", "--to", "1"]).is_err());
+        assert!(parse(&["up", "--steps=2"]).is_err());
+        assert!(parse(&["status", "--dry-run"]).is_err());
+        assert!(parse(&["create", "x", "--allow-destructive"]).is_err());
+        assert!(parse(&["up", "--dry-run=yes"]).is_err());
+        assert!(parse(&["sideways"]).is_err());
+        assert!(parse(&["up", "extra"]).is_err());
+    }
 }
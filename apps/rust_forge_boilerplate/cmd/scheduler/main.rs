@@ -1,6 +1,19 @@
-use rust_forge_boilerplate::common::infrastructure;
-use std::env;
-use tokio_cron_scheduler::{Job, JobScheduler};
+use rust_forge_boilerplate::{
+    common::infrastructure::{
+        self,
+        lock::LockBackend,
+        queue::backend::JobQueue,
+        redis::{CacheConfig, RedisCache, RedisClient, RedisConfig, RedisConnection},
+        scheduler::{
+            config::SchedulerConfig,
+            leader::{LeaderElection, LeaderElectionConfig, SchedulerLeaderStore},
+            runner::Scheduler,
+            task::{TaskRegistry, TaskState},
+        },
+    },
+    jobs_modules::tasks::PurgeJobRecords,
+};
+use std::{env, sync::Arc};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 #[tokio::main]
@@ -21,24 +34,47 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .parse()
         .expect("Invalid DATABASE_MAX_CONNECTIONS");
 
-    let _db_pool =
+    let db_pool =
         infrastructure::database::create_pool(&database_url, database_max_connections).await?;
 
-    tracing::info!("Scheduler started");
+    // Without the Redis lock or queue backend, Redis is only there for tasks
+    // that use it, so the scheduler does not wait for it to start.
+    let redis_config = RedisConfig::from_env();
+    let redis_conn = if LockBackend::uses_redis() || JobQueue::uses_redis() {
+        infrastructure::redis::RedisClientImpl::create_connection(&redis_config).await?
+    } else {
+        RedisConnection::connect_in_background(&redis_config)?
+    };
+    let cache_config = CacheConfig::from_env();
+    let election = LeaderElection::new(
+        LockBackend::from_env(redis_conn.clone(), db_pool.clone(), &cache_config.namespace),
+        SchedulerLeaderStore::new(db_pool.clone()),
+        LeaderElectionConfig::from_env(),
+    );
+    let jobs = Arc::new(JobQueue::from_env(
+        Some(redis_conn.clone()),
+        db_pool.clone(),
+        &cache_config.namespace,
+    ));
+    let cache = Arc::new(RedisCache::new(redis_conn.clone(), cache_config));
 
-    let scheduler = JobScheduler::new().await?;
+    // Register scheduled tasks here; their schedules live in the file at
+    // SCHEDULER_CONFIG_FILE_PATH. Use `.register_job(|ctx| MyJob { .. })` to
+    // have the worker run a job instead of running the work here.
+    let task_registry = TaskRegistry::new().register(PurgeJobRecords::from_env());
+    let scheduler = Scheduler::new(
+        task_registry,
+        SchedulerConfig::from_env(),
+        TaskState {
+            db: db_pool,
+            redis: redis_conn,
+            cache,
+            jobs,
+        },
+        election,
+    );
 
-    scheduler
-        .add(Job::new_async("0 * * * * *", |_uuid, _l| {
-            Box::pin(async move {
-                tracing::info!("Scheduled job executed");
-            })
-        })?)
-        .await?;
+    scheduler.run().await;
 
-    scheduler.start().await?;
-
-    loop {
-        tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
-    }
+    Ok(())
 }
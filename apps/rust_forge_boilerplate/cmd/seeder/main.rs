@@ -13,13 +13,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
-    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL environment variable is required");
+    let database_url =
+        env::var("DATABASE_URL").expect("DATABASE_URL environment variable is required");
     let database_max_connections: u32 = env::var("DATABASE_MAX_CONNECTIONS")
         .unwrap_or_else(|_| "10".to_string())
         .parse()
         .expect("Invalid DATABASE_MAX_CONNECTIONS");
 
-    let _db_pool = infrastructure::database::create_pool(&database_url, database_max_connections).await?;
+    let _db_pool =
+        infrastructure::database::create_pool(&database_url, database_max_connections).await?;
 
     tracing::info!("Running seeders...");
 
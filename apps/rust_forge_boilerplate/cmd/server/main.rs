@@ -1,9 +1,34 @@
-use actix_web::{App, HttpServer, middleware::Logger};
+use actix_web::{
+    App, HttpServer,
+    middleware::{Condition, Logger},
+};
 use rust_forge_boilerplate::{
-    common::infrastructure::{self, redis::RedisClient},
+    common::infrastructure::{
+        self,
+        database::{DatabaseCluster, DatabaseClusterConfig},
+        local_cache::LocalCacheConfig,
+        lock::LockBackend,
+        queue::{
+            backend::JobQueue, dead_letter::DeadLetterStore, status::JobStatusFeed,
+            workflow::BatchStore,
+        },
+        redis::{CacheConfig, RedisCache, RedisClient, RedisConfig, RedisConnection},
+        scheduler::{control::RegisteredTaskStore, history::TaskRunStore},
+    },
+    common::middleware::{
+        admin::AdminAuthConfig,
+        idempotency::{Idempotency, IdempotencyBackend, IdempotencyConfig},
+        rate_limit::{RateLimitBackend, RateLimitPolicy, RateLimiter},
+        session::{RedisSessionStore, SessionConfig, SessionMiddleware},
+    },
     healthcheck_modules::{self, repo::HealthCheckRepo},
+    jobs_modules::{self, service::JobsService},
+    scheduler_modules::{self, service::SchedulerService},
+};
+use std::{
+    env,
+    sync::{Arc, Mutex},
 };
-use std::{env, sync::Arc};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 #[actix_web::main]
@@ -11,6 +36,7 @@ async fn main() -> std::io::Result<()> {
     let health_check_repo = Arc::new(HealthCheckRepo {});
     let health_check_service = Arc::new(healthcheck_modules::service::HealthCheckService {
         repo: health_check_repo.clone(),
+        reported_scheduler_leader: Mutex::new(None),
     });
     dotenv::dotenv().ok();
 
@@ -21,21 +47,81 @@ async fn main() -> std::io::Result<()> {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
-    let database_url =
-        env::var("DATABASE_URL").expect("DATABASE_URL environment variable is required");
-    let database_max_connections: u32 = env::var("DATABASE_MAX_CONNECTIONS")
-        .unwrap_or_else(|_| "10".to_string())
+    let metrics_handle = infrastructure::metrics::install_recorder();
+
+    let degraded_start: bool = env::var("SERVER_DEGRADED_START")
+        .unwrap_or_else(|_| "false".to_string())
         .parse()
-        .expect("Invalid DATABASE_MAX_CONNECTIONS");
-    print!("{}", database_url);
-    let db_pool = infrastructure::database::create_pool(&database_url, database_max_connections)
-        .await
-        .expect("Failed to create database pool");
+        .expect("Invalid SERVER_DEGRADED_START");
+
+    let database_config = DatabaseClusterConfig::from_env();
+    let redis_config = RedisConfig::from_env();
 
-    let redis_url = env::var("REDIS_URL").expect("REDIS_URL environment variable is required");
-    let redis_conn = infrastructure::redis::RedisClientImpl::create_connection(&redis_url)
-        .await
-        .expect("Failed to create Redis connection");
+    let (db_cluster, redis_conn) = if degraded_start {
+        tracing::info!("Starting in degraded mode, dependencies connect in the background");
+        let db_cluster = DatabaseCluster::connect_lazy(&database_config)
+            .expect("Invalid database configuration");
+        let redis_conn = RedisConnection::connect_in_background(&redis_config)
+            .expect("Invalid Redis configuration");
+        (db_cluster, redis_conn)
+    } else {
+        let db_cluster = DatabaseCluster::connect(&database_config)
+            .await
+            .expect("Failed to create database pool");
+        let redis_conn = infrastructure::redis::RedisClientImpl::create_connection(&redis_config)
+            .await
+            .expect("Failed to create Redis connection");
+        (db_cluster, redis_conn)
+    };
+    db_cluster.spawn_health_checks(
+        database_config.health_check_interval,
+        database_config.health_check_timeout,
+    );
+
+    let cache_config = CacheConfig::from_env();
+    let lock = Arc::new(LockBackend::from_env(
+        redis_conn.clone(),
+        db_cluster.writer().clone(),
+        &cache_config.namespace,
+    ));
+    let rate_limit_policy = RateLimitPolicy::from_env();
+    let rate_limit_store = Arc::new(RateLimitBackend::from_env(
+        redis_conn.clone(),
+        &cache_config.namespace,
+    ));
+    let idempotency_config = IdempotencyConfig::from_env();
+    let idempotency_store = Arc::new(IdempotencyBackend::from_env(
+        redis_conn.clone(),
+        db_cluster.writer().clone(),
+        &cache_config.namespace,
+    ));
+    let job_queue = Arc::new(JobQueue::from_env(
+        Some(redis_conn.clone()),
+        db_cluster.writer().clone(),
+        &cache_config.namespace,
+    ));
+    let jobs_service = Arc::new(JobsService {
+        dead_letters: DeadLetterStore::new(db_cluster.writer().clone()),
+        batches: BatchStore::new(db_cluster.writer().clone()),
+        status_feed: JobStatusFeed::start(job_queue.statuses().clone()),
+        queue: job_queue.clone(),
+    });
+    let scheduler_service = Arc::new(SchedulerService {
+        tasks: RegisteredTaskStore::new(db_cluster.writer().clone()),
+        runs: TaskRunStore::new(db_cluster.writer().clone()),
+    });
+    let admin_auth_config = AdminAuthConfig::from_env();
+    let session_config = SessionConfig::from_env();
+    let session_store = Arc::new(RedisSessionStore::new(
+        redis_conn.clone(),
+        &cache_config.namespace,
+    ));
+    let mut cache = RedisCache::new(redis_conn.clone(), cache_config);
+    if let Some(local_cache_config) = LocalCacheConfig::from_env() {
+        cache = cache.with_local_tier(&local_cache_config);
+        cache.spawn_invalidation_listener();
+    }
+    let cache = Arc::new(cache);
 
     let server_host = env::var("SERVER_HOST").unwrap_or_else(|_| "127.0.0.1".to_string());
     let server_port: u16 = env::var("SERVER_PORT")
@@ -48,10 +134,43 @@ async fn main() -> std::io::Result<()> {
 
     HttpServer::new(move || {
         App::new()
+            .wrap(Condition::new(
+                session_config.is_some(),
+                SessionMiddleware::new(
+                    session_store.clone(),
+                    session_config.clone().unwrap_or_default(),
+                ),
+            ))
+            .wrap(Condition::new(
+                idempotency_config.is_some(),
+                Idempotency::new(
+                    idempotency_store.clone(),
+                    idempotency_config.clone().unwrap_or_default(),
+                ),
+            ))
+            .wrap(Condition::new(
+                rate_limit_policy.is_some(),
+                RateLimiter::new(
+                    rate_limit_store.clone(),
+                    rate_limit_policy.clone().unwrap_or_default(),
+                ),
+            ))
             .wrap(Logger::default())
             .app_data(actix_web::web::Data::new(health_check_service.clone()))
-            .app_data(actix_web::web::Data::new(db_pool.clone()))
+            .app_data(actix_web::web::Data::new(metrics_handle.clone()))
+            .app_data(actix_web::web::Data::new(db_cluster.clone()))
             .app_data(actix_web::web::Data::new(redis_conn.clone()))
+            .app_data(actix_web::web::Data::new(cache.clone()))
+            .app_data(actix_web::web::Data::new(lock.clone()))
+            .app_data(actix_web::web::Data::new(session_store.clone()))
+            .app_data(actix_web::web::Data::new(job_queue.clone()))
+            .app_data(actix_web::web::Data::new(jobs_service.clone()))
+            .app_data(actix_web::web::Data::new(scheduler_service.clone()))
+            .app_data(actix_web::web::Data::new(admin_auth_config.clone()))
+            // Ahead of the health check's `/api` scope, which would otherwise
+            // match these paths first.
+            .configure(jobs_modules::configure_routes)
+            .configure(scheduler_modules::configure_routes)
             .configure(healthcheck_modules::configure_routes)
     })
     .bind(&bind_address)?
@@ -1,5 +1,16 @@
-use rust_forge_boilerplate::common::infrastructure::{self, redis::RedisClient};
-use std::env;
+use rust_forge_boilerplate::common::infrastructure::{
+    self,
+    outbox::{OutboxRelay, OutboxRelayConfig},
+    queue::{
+        backend::JobQueue,
+        dead_letter::DeadLetterStore,
+        registry::JobRegistry,
+        worker::{JobWorker, WorkerConfig},
+        workflow::BatchStore,
+    },
+    redis::{CacheConfig, RedisClient, RedisConfig},
+};
+use std::{env, sync::Arc};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 #[tokio::main]
@@ -20,16 +31,50 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .parse()
         .expect("Invalid DATABASE_MAX_CONNECTIONS");
 
-    let _db_pool =
+    let db_pool =
         infrastructure::database::create_pool(&database_url, database_max_connections).await?;
 
-    let redis_url = env::var("REDIS_URL").expect("REDIS_URL environment variable is required");
-    let _redis_conn = infrastructure::redis::RedisClientImpl::create_connection(&redis_url).await?;
+    // Redis is only needed by the Redis queue backend and the outbox relay.
+    let outbox_config = OutboxRelayConfig::from_env();
+    let redis_conn = if JobQueue::uses_redis() || outbox_config.enabled {
+        let redis_config = RedisConfig::from_env();
+        Some(infrastructure::redis::RedisClientImpl::create_connection(&redis_config).await?)
+    } else {
+        None
+    };
 
     tracing::info!("Worker started");
 
-    loop {
-        tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-        tracing::info!("Worker tick...");
+    let job_queue = Arc::new(JobQueue::from_env(
+        redis_conn.clone(),
+        db_pool.clone(),
+        &CacheConfig::from_env().namespace,
+    ));
+    // Register job types here, e.g. `.register::<SendWelcomeEmail>()`.
+    let job_registry = JobRegistry::new().with_data(db_pool.clone());
+    let job_worker = JobWorker::new(
+        job_queue,
+        job_registry,
+        DeadLetterStore::new(db_pool.clone()),
+        BatchStore::new(db_pool.clone()),
+        WorkerConfig::from_env(),
+    );
+
+    let outbox_relay = redis_conn
+        .filter(|_| outbox_config.enabled)
+        .map(|redis_conn| OutboxRelay::new(db_pool, redis_conn, outbox_config));
+
+    // The relay runs forever; the job worker returns after a graceful
+    // shutdown on Ctrl-C.
+    tokio::select! {
+        _ = async {
+            match outbox_relay {
+                Some(relay) => relay.run().await,
+                None => std::future::pending().await,
+            }
+        } => {}
+        _ = job_worker.run() => {}
     }
+
+    Ok(())
 }
@@ -1,4 +1,42 @@
-use sqlx::{PgPool, Pool, Postgres, postgres::PgPoolOptions};
+use actix_web::{FromRequest, HttpMessage, HttpRequest, dev::Payload, web};
+use sqlx::{
+    Execute, FromRow, PgConnection, PgPool, Pool, Postgres, Transaction,
+    pool::PoolConnection,
+    postgres::{PgArguments, PgPoolOptions, PgQueryResult, PgRow},
+    query::{Query, QueryAs},
+};
+use std::{
+    env,
+    future::{Future, Ready, ready},
+    iter::Peekable,
+    ops::{Deref, DerefMut},
+    panic::Location,
+    str::{Chars, FromStr},
+    sync::{
+        Arc, Mutex, OnceLock,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use crate::common::utils::{
+    backoff::{BackoffPolicy, retry_with_backoff},
+    error::AppError,
+};
+use tracing::Instrument;
+
+static SLOW_QUERY_THRESHOLD: OnceLock<Duration> = OnceLock::new();
+
+fn slow_query_threshold() -> Duration {
+    *SLOW_QUERY_THRESHOLD.get_or_init(|| {
+        let threshold_ms: u64 = env::var("DATABASE_SLOW_QUERY_THRESHOLD_MS")
+            .unwrap_or_else(|_| "500".to_string())
+            .parse()
+            .expect("Invalid DATABASE_SLOW_QUERY_THRESHOLD_MS");
+        Duration::from_millis(threshold_ms)
+    })
+}
+
 #[async_trait::async_trait]
 pub trait DbPoolTrait<T>
 where
@@ -7,11 +45,23 @@ where
     async fn create_pool(database_url: &str, max_connections: u32) -> Result<Pool<T>, sqlx::Error>;
 }
 
+/// Connects to Postgres, retrying with the startup `BackoffPolicy` so a
+/// container that starts before the database does not crash-loop.
 pub async fn create_pool(database_url: &str, max_connections: u32) -> Result<PgPool, sqlx::Error> {
+    let policy = BackoffPolicy::from_env();
+    retry_with_backoff("Postgres", &policy, || {
+        PgPoolOptions::new()
+            .max_connections(max_connections)
+            .connect(database_url)
+    })
+    .await
+}
+
+/// Builds a pool without connecting; connections are opened on first use.
+pub fn create_lazy_pool(database_url: &str, max_connections: u32) -> Result<PgPool, sqlx::Error> {
     PgPoolOptions::new()
         .max_connections(max_connections)
-        .connect(database_url)
-        .await
+        .connect_lazy(database_url)
 }
 
 pub struct PostgresDatabase;
@@ -22,9 +72,665 @@ impl DbPoolTrait<Postgres> for PostgresDatabase {
         database_url: &str,
         max_connections: u32,
     ) -> Result<Pool<Postgres>, sqlx::Error> {
-        PgPoolOptions::new()
-            .max_connections(max_connections)
-            .connect(database_url)
-            .await
+        create_pool(database_url, max_connections).await
+    }
+}
+
+/// How `DatabaseCluster::reader` picks a replica among the healthy ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplicaSelection {
+    RoundRobin,
+    LeastConnections,
+}
+
+impl FromStr for ReplicaSelection {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "round_robin" => Ok(Self::RoundRobin),
+            "least_connections" => Ok(Self::LeastConnections),
+            other => Err(format!("unknown replica selection strategy: {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DatabaseClusterConfig {
+    pub primary_url: String,
+    pub replica_urls: Vec<String>,
+    pub max_connections: u32,
+    pub selection: ReplicaSelection,
+    pub health_check_interval: Duration,
+    /// How long a replica may take to answer a health check ping.
+    pub health_check_timeout: Duration,
+    pub read_your_writes_window: Duration,
+}
+
+impl DatabaseClusterConfig {
+    pub fn from_env() -> Self {
+        let primary_url =
+            env::var("DATABASE_URL").expect("DATABASE_URL environment variable is required");
+        let replica_urls = env::var("DATABASE_REPLICA_URLS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|url| !url.is_empty())
+            .map(String::from)
+            .collect();
+        let max_connections: u32 = env::var("DATABASE_MAX_CONNECTIONS")
+            .unwrap_or_else(|_| "10".to_string())
+            .parse()
+            .expect("Invalid DATABASE_MAX_CONNECTIONS");
+        let selection: ReplicaSelection = env::var("DATABASE_REPLICA_SELECTION")
+            .unwrap_or_else(|_| "round_robin".to_string())
+            .parse()
+            .expect("Invalid DATABASE_REPLICA_SELECTION");
+        let health_check_interval_secs: u64 =
+            env::var("DATABASE_REPLICA_HEALTH_CHECK_INTERVAL_SECS")
+                .unwrap_or_else(|_| "10".to_string())
+                .parse()
+                .expect("Invalid DATABASE_REPLICA_HEALTH_CHECK_INTERVAL_SECS");
+        let health_check_timeout_ms: u64 = env::var("DATABASE_REPLICA_HEALTH_CHECK_TIMEOUT_MS")
+            .unwrap_or_else(|_| "2000".to_string())
+            .parse()
+            .ok()
+            .filter(|timeout_ms| *timeout_ms > 0)
+            .expect("Invalid DATABASE_REPLICA_HEALTH_CHECK_TIMEOUT_MS");
+        let read_your_writes_window_ms: u64 = env::var("DATABASE_READ_YOUR_WRITES_WINDOW_MS")
+            .unwrap_or_else(|_| "2000".to_string())
+            .parse()
+            .expect("Invalid DATABASE_READ_YOUR_WRITES_WINDOW_MS");
+
+        Self {
+            primary_url,
+            replica_urls,
+            max_connections,
+            selection,
+            health_check_interval: Duration::from_secs(health_check_interval_secs),
+            health_check_timeout: Duration::from_millis(health_check_timeout_ms),
+            read_your_writes_window: Duration::from_millis(read_your_writes_window_ms),
+        }
+    }
+}
+
+struct Replica {
+    pool: PgPool,
+    healthy: AtomicBool,
+}
+
+struct ClusterInner {
+    primary: PgPool,
+    replicas: Vec<Replica>,
+    selection: ReplicaSelection,
+    next_replica: AtomicUsize,
+    read_your_writes_window: Duration,
+}
+
+/// A primary pool plus any number of read replicas.
+///
+/// Writes always go to the primary. Reads are spread over the replicas that
+/// passed their last health check and fall back to the primary when none did.
+#[derive(Clone)]
+pub struct DatabaseCluster {
+    inner: Arc<ClusterInner>,
+}
+
+impl DatabaseCluster {
+    pub fn new(
+        primary: PgPool,
+        replicas: Vec<PgPool>,
+        selection: ReplicaSelection,
+        read_your_writes_window: Duration,
+    ) -> Self {
+        let replicas = replicas
+            .into_iter()
+            .map(|pool| Replica {
+                pool,
+                healthy: AtomicBool::new(true),
+            })
+            .collect();
+
+        Self {
+            inner: Arc::new(ClusterInner {
+                primary,
+                replicas,
+                selection,
+                next_replica: AtomicUsize::new(0),
+                read_your_writes_window,
+            }),
+        }
+    }
+
+    /// Waits for the primary to accept connections. Replicas never block
+    /// startup: they are checked once and join the rotation when healthy.
+    pub async fn connect(config: &DatabaseClusterConfig) -> Result<Self, sqlx::Error> {
+        let primary = create_pool(&config.primary_url, config.max_connections).await?;
+        let cluster = Self::with_lazy_replicas(primary, config)?;
+        cluster.check_replicas(config.health_check_timeout).await;
+
+        Ok(cluster)
+    }
+
+    /// Builds the cluster without touching the network, for starting in
+    /// degraded mode while the database is still coming up.
+    pub fn connect_lazy(config: &DatabaseClusterConfig) -> Result<Self, sqlx::Error> {
+        let primary = create_lazy_pool(&config.primary_url, config.max_connections)?;
+        Self::with_lazy_replicas(primary, config)
+    }
+
+    fn with_lazy_replicas(
+        primary: PgPool,
+        config: &DatabaseClusterConfig,
+    ) -> Result<Self, sqlx::Error> {
+        let replicas = config
+            .replica_urls
+            .iter()
+            .map(|replica_url| create_lazy_pool(replica_url, config.max_connections))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self::new(
+            primary,
+            replicas,
+            config.selection,
+            config.read_your_writes_window,
+        ))
+    }
+
+    pub fn writer(&self) -> &PgPool {
+        &self.inner.primary
+    }
+
+    pub fn reader(&self) -> &PgPool {
+        let healthy: Vec<&Replica> = self
+            .inner
+            .replicas
+            .iter()
+            .filter(|replica| replica.healthy.load(Ordering::Relaxed))
+            .collect();
+
+        if healthy.is_empty() {
+            return &self.inner.primary;
+        }
+
+        let replica = match self.inner.selection {
+            ReplicaSelection::RoundRobin => {
+                let index = self.inner.next_replica.fetch_add(1, Ordering::Relaxed);
+                healthy[index % healthy.len()]
+            }
+            ReplicaSelection::LeastConnections => healthy
+                .iter()
+                .min_by_key(|replica| {
+                    replica
+                        .pool
+                        .size()
+                        .saturating_sub(replica.pool.num_idle() as u32)
+                })
+                .copied()
+                .unwrap_or(healthy[0]),
+        };
+
+        &replica.pool
+    }
+
+    pub fn replica_count(&self) -> usize {
+        self.inner.replicas.len()
+    }
+
+    pub fn healthy_replica_count(&self) -> usize {
+        self.inner
+            .replicas
+            .iter()
+            .filter(|replica| replica.healthy.load(Ordering::Relaxed))
+            .count()
+    }
+
+    /// Starts a per-request view of the cluster that keeps reads on the
+    /// primary for the configured window after a write.
+    pub fn session(&self) -> DbSession {
+        DbSession {
+            cluster: self.clone(),
+            last_write: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Pings every replica once, taking failing ones out of rotation and
+    /// putting recovered ones back.
+    pub async fn check_replicas(&self, timeout: Duration) {
+        for (index, replica) in self.inner.replicas.iter().enumerate() {
+            let ping = sqlx::query("SELECT 1").execute(&replica.pool);
+            let healthy = matches!(tokio::time::timeout(timeout, ping).await, Ok(Ok(_)));
+            let was_healthy = replica.healthy.swap(healthy, Ordering::Relaxed);
+
+            if was_healthy && !healthy {
+                tracing::warn!(
+                    "Database replica #{} is unhealthy, removing from rotation",
+                    index
+                );
+            } else if !was_healthy && healthy {
+                tracing::info!(
+                    "Database replica #{} recovered, adding back to rotation",
+                    index
+                );
+            }
+        }
+    }
+
+    pub fn spawn_health_checks(
+        &self,
+        interval: Duration,
+        timeout: Duration,
+    ) -> tokio::task::JoinHandle<()> {
+        let cluster = self.clone();
+        tokio::spawn(async move {
+            if cluster.replica_count() == 0 {
+                return;
+            }
+
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                cluster.check_replicas(timeout).await;
+            }
+        })
+    }
+}
+
+/// Request-scoped handle on a `DatabaseCluster` giving read-your-writes
+/// consistency: once `writer` has been used, `reader` returns the primary
+/// until the cluster's stickiness window has passed.
+#[derive(Clone)]
+pub struct DbSession {
+    cluster: DatabaseCluster,
+    last_write: Arc<Mutex<Option<Instant>>>,
+}
+
+impl DbSession {
+    pub fn writer(&self) -> &PgPool {
+        self.mark_write();
+        self.cluster.writer()
+    }
+
+    pub fn reader(&self) -> &PgPool {
+        let window = self.cluster.inner.read_your_writes_window;
+        let last_write = *self.last_write.lock().unwrap_or_else(|e| e.into_inner());
+
+        match last_write {
+            Some(written_at) if written_at.elapsed() < window => self.cluster.writer(),
+            _ => self.cluster.reader(),
+        }
+    }
+
+    pub fn mark_write(&self) {
+        *self.last_write.lock().unwrap_or_else(|e| e.into_inner()) = Some(Instant::now());
+    }
+
+    pub fn cluster(&self) -> &DatabaseCluster {
+        &self.cluster
+    }
+}
+
+impl FromRequest for DbSession {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        if let Some(session) = req.extensions().get::<DbSession>() {
+            return ready(Ok(session.clone()));
+        }
+
+        let Some(cluster) = req.app_data::<web::Data<DatabaseCluster>>() else {
+            tracing::error!("DatabaseCluster is not registered as app data");
+            let error = AppError::new(1100, None);
+            return ready(Err(actix_web::error::InternalError::from_response(
+                error.to_string(),
+                error.http_response_builder(),
+            )
+            .into()));
+        };
+
+        let session = cluster.session();
+        req.extensions_mut().insert(session.clone());
+        ready(Ok(session))
+    }
+}
+
+/// What repositories run their queries on: either a pool, in which case
+/// every call checks out its own connection, or a connection the caller
+/// already holds, typically an open `Transaction`.
+pub enum DbExecutor<'a> {
+    Pool(&'a PgPool),
+    Connection(&'a mut PgConnection),
+}
+
+impl<'a> DbExecutor<'a> {
+    pub async fn acquire(&mut self) -> Result<DbConnection<'_>, sqlx::Error> {
+        match self {
+            DbExecutor::Pool(pool) => Ok(DbConnection::Pooled(Box::new(pool.acquire().await?))),
+            DbExecutor::Connection(conn) => Ok(DbConnection::Borrowed(conn)),
+        }
+    }
+
+    #[track_caller]
+    pub fn execute<'q>(
+        &mut self,
+        query: Query<'q, Postgres, PgArguments>,
+    ) -> impl Future<Output = Result<PgQueryResult, sqlx::Error>> + Send {
+        let caller = Location::caller();
+        async move {
+            let statement = redact_statement(query.sql());
+            let mut conn = self.acquire().await?;
+            instrument_query(&statement, caller, query.execute(&mut *conn)).await
+        }
+    }
+
+    #[track_caller]
+    pub fn fetch_all<'q, O>(
+        &mut self,
+        query: QueryAs<'q, Postgres, O, PgArguments>,
+    ) -> impl Future<Output = Result<Vec<O>, sqlx::Error>> + Send
+    where
+        O: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+    {
+        let caller = Location::caller();
+        async move {
+            let statement = redact_statement(query.sql());
+            let mut conn = self.acquire().await?;
+            instrument_query(&statement, caller, query.fetch_all(&mut *conn)).await
+        }
+    }
+
+    #[track_caller]
+    pub fn fetch_one<'q, O>(
+        &mut self,
+        query: QueryAs<'q, Postgres, O, PgArguments>,
+    ) -> impl Future<Output = Result<O, sqlx::Error>> + Send
+    where
+        O: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+    {
+        let caller = Location::caller();
+        async move {
+            let statement = redact_statement(query.sql());
+            let mut conn = self.acquire().await?;
+            instrument_query(&statement, caller, query.fetch_one(&mut *conn)).await
+        }
+    }
+
+    #[track_caller]
+    pub fn fetch_optional<'q, O>(
+        &mut self,
+        query: QueryAs<'q, Postgres, O, PgArguments>,
+    ) -> impl Future<Output = Result<Option<O>, sqlx::Error>> + Send
+    where
+        O: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+    {
+        let caller = Location::caller();
+        async move {
+            let statement = redact_statement(query.sql());
+            let mut conn = self.acquire().await?;
+            instrument_query(&statement, caller, query.fetch_optional(&mut *conn)).await
+        }
+    }
+}
+
+/// Wraps a query in a `db.query` span, records its duration in the
+/// `db_query_duration_seconds` histogram and logs it at WARN when it exceeds
+/// `DATABASE_SLOW_QUERY_THRESHOLD_MS`.
+async fn instrument_query<T, F>(
+    statement: &str,
+    caller: &'static Location<'static>,
+    query: F,
+) -> Result<T, sqlx::Error>
+where
+    F: Future<Output = Result<T, sqlx::Error>>,
+{
+    let span = tracing::info_span!(
+        "db.query",
+        db.system = "postgresql",
+        db.statement = %statement,
+        code.filepath = caller.file(),
+        code.lineno = caller.line(),
+    );
+
+    let started_at = Instant::now();
+    let result = query.instrument(span).await;
+    let elapsed = started_at.elapsed();
+
+    // Labelled by call site rather than statement: statements built at
+    // runtime, e.g. with varying `IN` lists, would add series without bound.
+    let outcome = if result.is_ok() { "ok" } else { "error" };
+    metrics::histogram!(
+        "db_query_duration_seconds",
+        "operation" => statement_operation(statement),
+        "caller" => caller.to_string(),
+        "outcome" => outcome,
+    )
+    .record(elapsed.as_secs_f64());
+
+    if elapsed >= slow_query_threshold() {
+        tracing::warn!(
+            caller = %caller,
+            duration_ms = elapsed.as_millis() as u64,
+            statement = %statement,
+            "Slow query"
+        );
+    }
+
+    result
+}
+
+/// Normalizes whitespace and replaces string, dollar-quoted and numeric
+/// literals with `?`, so statements can be logged and traced without leaking
+/// values. Bind parameters (`$1`, `$2`, ...) are kept as they are.
+pub fn redact_statement(sql: &str) -> String {
+    let mut redacted = String::with_capacity(sql.len());
+    let mut chars = sql.chars().peekable();
+    let mut previous = ' ';
+
+    while let Some(c) = chars.next() {
+        if c == '$'
+            && let Some(tag) = dollar_quote_tag(previous, &mut chars)
+        {
+            let mut literal = String::new();
+            for next in chars.by_ref() {
+                literal.push(next);
+                if literal.ends_with(&tag) {
+                    break;
+                }
+            }
+            redacted.push('?');
+            previous = '?';
+            continue;
+        }
+
+        match c {
+            '\'' => {
+                // In `E'...'` escape strings a backslash escapes the next
+                // character, quotes included.
+                let escapes = redacted.ends_with(['E', 'e'])
+                    && !redacted[..redacted.len() - 1]
+                        .ends_with(|c: char| c.is_alphanumeric() || c == '_');
+                if escapes {
+                    redacted.pop();
+                }
+                while let Some(next) = chars.next() {
+                    if escapes && next == '\\' {
+                        chars.next();
+                    } else if next == '\'' {
+                        if chars.peek() == Some(&'\'') {
+                            chars.next();
+                        } else {
+                            break;
+                        }
+                    }
+                }
+                redacted.push('?');
+                previous = '?';
+            }
+            c if c.is_ascii_digit()
+                && !(previous.is_alphanumeric() || previous == '_' || previous == '$') =>
+            {
+                while chars
+                    .peek()
+                    .is_some_and(|next| next.is_ascii_digit() || *next == '.')
+                {
+                    chars.next();
+                }
+                redacted.push('?');
+                previous = '?';
+            }
+            c if c.is_whitespace() => {
+                if previous != ' ' {
+                    redacted.push(' ');
+                }
+                previous = ' ';
+            }
+            c => {
+                redacted.push(c);
+                previous = c;
+            }
+        }
+    }
+
+    redacted.trim_end().to_string()
+}
+
+/// First keyword of a redacted statement, for the `operation` label.
+fn statement_operation(statement: &str) -> &'static str {
+    let keyword = statement.split([' ', '(']).next().unwrap_or_default();
+    ["SELECT", "INSERT", "UPDATE", "DELETE", "WITH"]
+        .into_iter()
+        .find(|operation| keyword.eq_ignore_ascii_case(operation))
+        .unwrap_or("OTHER")
+}
+
+/// After a `$`, consumes the rest of a dollar quote's opening tag, `$` or
+/// `tag$`, and returns the whole tag. Leaves `chars` alone for bind
+/// parameters and identifiers containing `$`.
+fn dollar_quote_tag(previous: char, chars: &mut Peekable<Chars<'_>>) -> Option<String> {
+    if previous.is_alphanumeric() || previous == '_' {
+        return None;
+    }
+
+    let mut lookahead = chars.clone();
+    let mut tag = String::from("$");
+    while let Some(next) = lookahead.next_if(|next| next.is_alphanumeric() || *next == '_') {
+        tag.push(next);
+    }
+    if tag[1..].starts_with(|c: char| c.is_ascii_digit()) || lookahead.next() != Some('$') {
+        return None;
+    }
+
+    tag.push('$');
+    *chars = lookahead;
+    Some(tag)
+}
+
+impl<'a> From<&'a PgPool> for DbExecutor<'a> {
+    fn from(pool: &'a PgPool) -> Self {
+        DbExecutor::Pool(pool)
+    }
+}
+
+impl<'a> From<&'a mut PgConnection> for DbExecutor<'a> {
+    fn from(conn: &'a mut PgConnection) -> Self {
+        DbExecutor::Connection(conn)
+    }
+}
+
+impl<'a, 't> From<&'a mut Transaction<'t, Postgres>> for DbExecutor<'a> {
+    fn from(tx: &'a mut Transaction<'t, Postgres>) -> Self {
+        DbExecutor::Connection(tx)
+    }
+}
+
+pub enum DbConnection<'a> {
+    Pooled(Box<PoolConnection<Postgres>>),
+    Borrowed(&'a mut PgConnection),
+}
+
+impl Deref for DbConnection<'_> {
+    type Target = PgConnection;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            DbConnection::Pooled(conn) => conn,
+            DbConnection::Borrowed(conn) => conn,
+        }
+    }
+}
+
+impl DerefMut for DbConnection<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        match self {
+            DbConnection::Pooled(conn) => conn,
+            DbConnection::Borrowed(conn) => conn,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_string_and_numeric_literals() {
+        assert_eq!(
+            redact_statement("SELECT * FROM users WHERE name = 'O''Brien' AND age > 42.5"),
+            "SELECT * FROM users WHERE name = ? AND age > ?"
+        );
+    }
+
+    #[test]
+    fn keeps_bind_parameters_and_identifiers() {
+        assert_eq!(
+            redact_statement("SELECT col1 FROM t2 WHERE id = $1 LIMIT $12"),
+            "SELECT col1 FROM t2 WHERE id = $1 LIMIT $12"
+        );
+        assert_eq!(redact_statement("SELECT a$1 FROM t"), "SELECT a$1 FROM t");
+    }
+
+    #[test]
+    fn redacts_dollar_quoted_literals() {
+        assert_eq!(
+            redact_statement("SELECT $$it's secret$$, $tag$a $$ b$tag$ FROM t"),
+            "SELECT ?, ? FROM t"
+        );
+    }
+
+    #[test]
+    fn redacts_escape_string_literals() {
+        assert_eq!(
+            redact_statement(r"SELECT E'it\'s secret', e'a\\' FROM t WHERE name = 'x'"),
+            "SELECT ?, ? FROM t WHERE name = ?"
+        );
+        assert_eq!(
+            redact_statement(r"SELECT type'a\' FROM t"),
+            "SELECT type? FROM t"
+        );
+    }
+
+    #[test]
+    fn names_the_statement_operation() {
+        assert_eq!(statement_operation("select id FROM t"), "SELECT");
+        assert_eq!(
+            statement_operation("WITH x AS (SELECT 1) SELECT * FROM x"),
+            "WITH"
+        );
+        assert_eq!(statement_operation("LISTEN jobs"), "OTHER");
+    }
+
+    #[test]
+    fn redacts_unterminated_literals() {
+        assert_eq!(redact_statement("SELECT 'secret"), "SELECT ?");
+        assert_eq!(redact_statement("SELECT $$secret"), "SELECT ?");
+    }
+
+    #[test]
+    fn normalizes_whitespace() {
+        assert_eq!(
+            redact_statement("  SELECT\n\t id\n   FROM t  \n"),
+            "SELECT id FROM t"
+        );
     }
 }
@@ -0,0 +1,189 @@
+use futures::StreamExt;
+use moka::{Expiry, sync::Cache as MokaCache};
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use std::{
+    env,
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+use crate::common::{
+    infrastructure::redis::RedisConnection,
+    utils::backoff::{BackoffPolicy, retry_with_backoff},
+};
+
+#[derive(Debug, Clone)]
+pub struct LocalCacheConfig {
+    pub max_entries: u64,
+    /// Upper bound on how long an entry stays in process, even when the
+    /// Redis entry lives longer; limits staleness if an invalidation is lost.
+    pub max_ttl: Duration,
+}
+
+impl LocalCacheConfig {
+    /// Returns `None` unless `CACHE_LOCAL_ENABLED=true`.
+    pub fn from_env() -> Option<Self> {
+        let enabled: bool = env::var("CACHE_LOCAL_ENABLED")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .expect("Invalid CACHE_LOCAL_ENABLED");
+        if !enabled {
+            return None;
+        }
+
+        let max_entries: u64 = env::var("CACHE_LOCAL_MAX_ENTRIES")
+            .unwrap_or_else(|_| "10000".to_string())
+            .parse()
+            .expect("Invalid CACHE_LOCAL_MAX_ENTRIES");
+        let max_ttl_ms: u64 = env::var("CACHE_LOCAL_MAX_TTL_MS")
+            .unwrap_or_else(|_| "30000".to_string())
+            .parse()
+            .expect("Invalid CACHE_LOCAL_MAX_TTL_MS");
+
+        Some(Self {
+            max_entries,
+            max_ttl: Duration::from_millis(max_ttl_ms),
+        })
+    }
+}
+
+#[derive(Clone)]
+struct LocalEntry {
+    bytes: Vec<u8>,
+    ttl: Duration,
+}
+
+struct LocalEntryExpiry;
+
+impl Expiry<String, LocalEntry> for LocalEntryExpiry {
+    fn expire_after_create(
+        &self,
+        _key: &String,
+        entry: &LocalEntry,
+        _created_at: Instant,
+    ) -> Option<Duration> {
+        Some(entry.ttl)
+    }
+
+    fn expire_after_update(
+        &self,
+        _key: &String,
+        entry: &LocalEntry,
+        _updated_at: Instant,
+        _duration_until_expiry: Option<Duration>,
+    ) -> Option<Duration> {
+        Some(entry.ttl)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct InvalidationMessage {
+    origin: Uuid,
+    keys: Vec<String>,
+}
+
+/// In-process tier in front of `RedisCache`, bounded by entry count with
+/// TinyLFU admission and per-entry TTL.
+///
+/// Every write, delete and tag invalidation is broadcast on a Redis pub/sub
+/// channel so the local tiers of the other replicas evict the same keys.
+pub struct LocalCacheTier {
+    entries: MokaCache<String, LocalEntry>,
+    max_ttl: Duration,
+    instance_id: Uuid,
+    channel: String,
+}
+
+impl LocalCacheTier {
+    pub fn new(config: &LocalCacheConfig, namespace: &str) -> Self {
+        let entries = MokaCache::builder()
+            .max_capacity(config.max_entries)
+            .expire_after(LocalEntryExpiry)
+            .build();
+
+        Self {
+            entries,
+            max_ttl: config.max_ttl,
+            instance_id: Uuid::new_v4(),
+            channel: format!("{}:cache-invalidation", namespace),
+        }
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let bytes = self.entries.get(key).map(|entry| entry.bytes);
+        let result = if bytes.is_some() { "hit" } else { "miss" };
+        metrics::counter!("cache_requests_total", "tier" => "local", "result" => result)
+            .increment(1);
+        bytes
+    }
+
+    pub(crate) fn insert(&self, key: &str, bytes: Vec<u8>, ttl: Option<Duration>) {
+        let ttl = ttl.map_or(self.max_ttl, |ttl| ttl.min(self.max_ttl));
+        self.entries
+            .insert(key.to_string(), LocalEntry { bytes, ttl });
+    }
+
+    /// Evicts `keys` here and tells the other replicas to do the same.
+    pub(crate) async fn invalidate(&self, conn: &RedisConnection, keys: Vec<String>) {
+        for key in &keys {
+            self.entries.invalidate(key);
+        }
+
+        let message = InvalidationMessage {
+            origin: self.instance_id,
+            keys,
+        };
+        let Ok(payload) = serde_json::to_string(&message) else {
+            return;
+        };
+
+        let mut conn = conn.clone();
+        if let Err(e) = conn.publish::<_, _, ()>(&self.channel, payload).await {
+            tracing::warn!("Failed to broadcast cache invalidation: {}", e);
+        }
+    }
+
+    /// Applies invalidations from other replicas until the process exits,
+    /// resubscribing with backoff when the pub/sub connection drops.
+    ///
+    /// Messages published while unsubscribed are lost, so the whole local
+    /// tier is cleared on every (re)subscribe.
+    pub async fn run_invalidation_listener(&self, conn: RedisConnection) {
+        let policy = BackoffPolicy::from_env().unbounded();
+
+        loop {
+            let subscribed = retry_with_backoff("Redis cache invalidation", &policy, || async {
+                let mut pubsub = conn.pubsub().await?;
+                pubsub.subscribe(&self.channel).await?;
+                Ok::<_, redis::RedisError>(pubsub)
+            })
+            .await;
+            let Ok(mut pubsub) = subscribed else {
+                continue;
+            };
+
+            self.entries.invalidate_all();
+            tracing::info!("Listening for cache invalidations on {}", self.channel);
+
+            let mut messages = pubsub.on_message();
+            while let Some(msg) = messages.next().await {
+                let Ok(payload) = msg.get_payload::<String>() else {
+                    continue;
+                };
+                let Ok(message) = serde_json::from_str::<InvalidationMessage>(&payload) else {
+                    tracing::warn!("Ignoring malformed cache invalidation: {}", payload);
+                    continue;
+                };
+                if message.origin == self.instance_id {
+                    continue;
+                }
+                for key in &message.keys {
+                    self.entries.invalidate(key);
+                }
+            }
+
+            tracing::warn!("Cache invalidation subscription closed, resubscribing");
+        }
+    }
+}
@@ -0,0 +1,466 @@
+use redis::{RedisError, Script};
+use sqlx::{PgPool, Postgres, pool::PoolConnection};
+use std::{
+    env, fmt,
+    future::Future,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+use crate::common::infrastructure::{database::DbExecutor, redis::RedisConnection};
+
+#[derive(Debug)]
+pub enum LockError {
+    Redis(RedisError),
+    Database(sqlx::Error),
+}
+
+impl fmt::Display for LockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LockError::Redis(e) => write!(f, "lock backend error: {}", e),
+            LockError::Database(e) => write!(f, "lock backend error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for LockError {}
+
+impl From<RedisError> for LockError {
+    fn from(err: RedisError) -> Self {
+        LockError::Redis(err)
+    }
+}
+
+impl From<sqlx::Error> for LockError {
+    fn from(err: sqlx::Error) -> Self {
+        LockError::Database(err)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LockOptions {
+    /// Lease length. The guard extends it every third of this while held,
+    /// so it only matters when the holder dies without releasing.
+    pub ttl: Duration,
+    /// How long `acquire` keeps retrying before giving up.
+    pub wait: Duration,
+    pub retry_interval: Duration,
+}
+
+impl Default for LockOptions {
+    fn default() -> Self {
+        Self {
+            ttl: Duration::from_secs(30),
+            wait: Duration::ZERO,
+            retry_interval: Duration::from_millis(100),
+        }
+    }
+}
+
+impl LockOptions {
+    pub fn ttl(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            ..Self::default()
+        }
+    }
+
+    pub fn wait(mut self, wait: Duration) -> Self {
+        self.wait = wait;
+        self
+    }
+}
+
+/// Mutual exclusion across replicas.
+///
+/// Every successful acquisition carries a fencing token that is strictly
+/// greater than the one of any earlier holder of the same lock. Pass it to
+/// the protected resource and reject writes with an older token: a holder
+/// that stalled past its lease can otherwise still act after losing the lock.
+pub trait DistributedLock: Send + Sync {
+    /// Makes a single attempt and returns `None` if the lock is taken.
+    fn try_acquire(
+        &self,
+        name: &str,
+        options: &LockOptions,
+    ) -> impl Future<Output = Result<Option<LockGuard>, LockError>> + Send;
+
+    /// Retries `try_acquire` until it succeeds or `options.wait` elapses.
+    fn acquire(
+        &self,
+        name: &str,
+        options: &LockOptions,
+    ) -> impl Future<Output = Result<Option<LockGuard>, LockError>> + Send {
+        async move {
+            let started = Instant::now();
+            loop {
+                if let Some(guard) = self.try_acquire(name, options).await? {
+                    return Ok(Some(guard));
+                }
+                if started.elapsed() + options.retry_interval > options.wait {
+                    return Ok(None);
+                }
+                tokio::time::sleep(options.retry_interval).await;
+            }
+        }
+    }
+}
+
+/// Backend side of a held lock, driven by the guard's background task.
+trait Lease: Send + 'static {
+    /// Renews the lease; `Ok(false)` means it has already been lost.
+    fn extend(&mut self) -> impl Future<Output = Result<bool, LockError>> + Send;
+
+    fn release(self) -> impl Future<Output = Result<(), LockError>> + Send;
+}
+
+/// Held lock. A background task keeps the lease alive until the guard is
+/// released or dropped.
+///
+/// Prefer `release().await`; dropping the guard releases it in the
+/// background, so another replica may briefly still see the lock as taken.
+pub struct LockGuard {
+    name: String,
+    fencing_token: u64,
+    lost: Arc<AtomicBool>,
+    release: Option<oneshot::Sender<oneshot::Sender<()>>>,
+}
+
+impl LockGuard {
+    fn spawn<L: Lease>(name: &str, fencing_token: u64, lease: L, ttl: Duration) -> Self {
+        let lost = Arc::new(AtomicBool::new(false));
+        let (release_tx, release_rx) = oneshot::channel();
+
+        tokio::spawn(hold_lease(
+            name.to_string(),
+            lease,
+            ttl,
+            lost.clone(),
+            release_rx,
+        ));
+
+        Self {
+            name: name.to_string(),
+            fencing_token,
+            lost,
+            release: Some(release_tx),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn fencing_token(&self) -> u64 {
+        self.fencing_token
+    }
+
+    /// `false` once the lease could not be extended and another replica may
+    /// have taken the lock; stop the protected work when this happens.
+    pub fn is_held(&self) -> bool {
+        !self.lost.load(Ordering::Acquire)
+    }
+
+    pub async fn release(mut self) {
+        if let Some(release) = self.release.take() {
+            let (done_tx, done_rx) = oneshot::channel();
+            if release.send(done_tx).is_ok() {
+                let _ = done_rx.await;
+            }
+        }
+    }
+}
+
+impl Drop for LockGuard {
+    fn drop(&mut self) {
+        // Dropping the sender wakes the lease task, which releases the lock.
+        self.release.take();
+    }
+}
+
+async fn hold_lease<L: Lease>(
+    name: String,
+    mut lease: L,
+    ttl: Duration,
+    lost: Arc<AtomicBool>,
+    mut release_rx: oneshot::Receiver<oneshot::Sender<()>>,
+) {
+    let mut interval = tokio::time::interval((ttl / 3).max(Duration::from_millis(1)));
+    interval.tick().await;
+    let mut last_extended = Instant::now();
+
+    let done = loop {
+        tokio::select! {
+            done = &mut release_rx => break done.ok(),
+            _ = interval.tick(), if !lost.load(Ordering::Acquire) => {
+                match lease.extend().await {
+                    Ok(true) => last_extended = Instant::now(),
+                    Ok(false) => {
+                        tracing::warn!("Lock {} was lost before it was released", name);
+                        lost.store(true, Ordering::Release);
+                    }
+                    Err(e) => {
+                        tracing::warn!("Failed to extend lock {}: {}", name, e);
+                        if last_extended.elapsed() >= ttl {
+                            lost.store(true, Ordering::Release);
+                        }
+                    }
+                }
+            }
+        }
+    };
+
+    if let Err(e) = lease.release().await {
+        tracing::warn!("Failed to release lock {}: {}", name, e);
+    }
+    if let Some(done) = done {
+        let _ = done.send(());
+    }
+}
+
+const ACQUIRE_SCRIPT: &str = r#"
+if redis.call('SET', KEYS[1], ARGV[1], 'NX', 'PX', ARGV[2]) then
+    return redis.call('INCR', KEYS[2])
+end
+return false
+"#;
+
+const EXTEND_SCRIPT: &str = r#"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('PEXPIRE', KEYS[1], ARGV[2])
+end
+return 0
+"#;
+
+const RELEASE_SCRIPT: &str = r#"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('DEL', KEYS[1])
+end
+return 0
+"#;
+
+/// `DistributedLock` on a single Redis key set with `SET NX PX`.
+///
+/// The key holds a random owner id so that extending and releasing, done in
+/// Lua, never touch a lock that has since passed to another holder. Fencing
+/// tokens come from a counter next to the key, in the same cluster slot.
+pub struct RedisLock {
+    conn: RedisConnection,
+    namespace: String,
+}
+
+impl RedisLock {
+    pub fn new(conn: RedisConnection, namespace: impl Into<String>) -> Self {
+        Self {
+            conn,
+            namespace: namespace.into(),
+        }
+    }
+
+    fn keys(&self, name: &str) -> (String, String) {
+        let key = format!("{}:lock:{{{}}}", self.namespace, name);
+        let fence_key = format!("{}:fence", key);
+        (key, fence_key)
+    }
+}
+
+impl DistributedLock for RedisLock {
+    async fn try_acquire(
+        &self,
+        name: &str,
+        options: &LockOptions,
+    ) -> Result<Option<LockGuard>, LockError> {
+        let (key, fence_key) = self.keys(name);
+        let owner = Uuid::new_v4().to_string();
+        let ttl_ms = options.ttl.as_millis() as u64;
+        let mut conn = self.conn.clone();
+
+        let token: Option<u64> = Script::new(ACQUIRE_SCRIPT)
+            .key(&key)
+            .key(&fence_key)
+            .arg(&owner)
+            .arg(ttl_ms)
+            .invoke_async(&mut conn)
+            .await?;
+
+        Ok(token.map(|token| {
+            let lease = RedisLease {
+                conn,
+                key,
+                owner,
+                ttl_ms,
+            };
+            LockGuard::spawn(name, token, lease, options.ttl)
+        }))
+    }
+}
+
+struct RedisLease {
+    conn: RedisConnection,
+    key: String,
+    owner: String,
+    ttl_ms: u64,
+}
+
+impl Lease for RedisLease {
+    async fn extend(&mut self) -> Result<bool, LockError> {
+        let extended: i64 = Script::new(EXTEND_SCRIPT)
+            .key(&self.key)
+            .arg(&self.owner)
+            .arg(self.ttl_ms)
+            .invoke_async(&mut self.conn)
+            .await?;
+        Ok(extended == 1)
+    }
+
+    async fn release(mut self) -> Result<(), LockError> {
+        Script::new(RELEASE_SCRIPT)
+            .key(&self.key)
+            .arg(&self.owner)
+            .invoke_async::<_, i64>(&mut self.conn)
+            .await?;
+        Ok(())
+    }
+}
+
+/// `DistributedLock` on Postgres session-level advisory locks, for apps
+/// without Redis.
+///
+/// Each held lock pins one pooled connection, and Postgres releases the lock
+/// by itself if that connection dies, so `ttl` only sets how often the
+/// connection is checked. Fencing tokens come from the `lock_fencing_tokens`
+/// sequence, drawn only once the lock is held.
+pub struct PostgresLock {
+    pool: PgPool,
+}
+
+impl PostgresLock {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+impl DistributedLock for PostgresLock {
+    async fn try_acquire(
+        &self,
+        name: &str,
+        options: &LockOptions,
+    ) -> Result<Option<LockGuard>, LockError> {
+        let mut conn = self.pool.acquire().await?;
+
+        let (acquired,): (bool,) = DbExecutor::from(&mut *conn)
+            .fetch_one(
+                sqlx::query_as("SELECT pg_try_advisory_lock(hashtextextended($1, 0))").bind(name),
+            )
+            .await?;
+
+        if !acquired {
+            return Ok(None);
+        }
+
+        let token: Result<(i64,), sqlx::Error> = DbExecutor::from(&mut *conn)
+            .fetch_one(sqlx::query_as("SELECT nextval('lock_fencing_tokens')"))
+            .await;
+        let (token,) = match token {
+            Ok(token) => token,
+            Err(e) => {
+                // Closing the session releases the lock; returning the
+                // connection to the pool would keep it held.
+                let _ = conn.close().await;
+                return Err(e.into());
+            }
+        };
+
+        let lease = PostgresLease {
+            conn,
+            name: name.to_string(),
+        };
+        Ok(Some(LockGuard::spawn(
+            name,
+            token as u64,
+            lease,
+            options.ttl,
+        )))
+    }
+}
+
+struct PostgresLease {
+    conn: PoolConnection<Postgres>,
+    name: String,
+}
+
+impl Lease for PostgresLease {
+    async fn extend(&mut self) -> Result<bool, LockError> {
+        // Only this session can unlock, so the lock is held for as long as
+        // the connection is alive.
+        match DbExecutor::from(&mut *self.conn)
+            .execute(sqlx::query("SELECT 1"))
+            .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                tracing::warn!("Connection holding lock {} failed: {}", self.name, e);
+                Ok(false)
+            }
+        }
+    }
+
+    async fn release(mut self) -> Result<(), LockError> {
+        let unlocked = DbExecutor::from(&mut *self.conn)
+            .execute(
+                sqlx::query("SELECT pg_advisory_unlock(hashtextextended($1, 0))").bind(&self.name),
+            )
+            .await;
+
+        if unlocked.is_err() {
+            // Never hand a connection that may still hold the lock back to
+            // the pool; closing it makes Postgres release the lock.
+            drop(self.conn.detach());
+        }
+        unlocked?;
+        Ok(())
+    }
+}
+
+/// The lock backend picked by `LOCK_BACKEND`.
+pub enum LockBackend {
+    Redis(RedisLock),
+    Postgres(PostgresLock),
+}
+
+impl LockBackend {
+    /// Whether `LOCK_BACKEND` selects the Redis backend.
+    pub fn uses_redis() -> bool {
+        env::var("LOCK_BACKEND")
+            .unwrap_or_else(|_| "redis".to_string())
+            .eq_ignore_ascii_case("redis")
+    }
+
+    pub fn from_env(conn: RedisConnection, pool: PgPool, namespace: &str) -> Self {
+        let backend = env::var("LOCK_BACKEND").unwrap_or_else(|_| "redis".to_string());
+        match backend.to_lowercase().as_str() {
+            "redis" => LockBackend::Redis(RedisLock::new(conn, namespace)),
+            "postgres" => LockBackend::Postgres(PostgresLock::new(pool)),
+            other => panic!("Invalid LOCK_BACKEND: {}", other),
+        }
+    }
+}
+
+impl DistributedLock for LockBackend {
+    async fn try_acquire(
+        &self,
+        name: &str,
+        options: &LockOptions,
+    ) -> Result<Option<LockGuard>, LockError> {
+        match self {
+            LockBackend::Redis(lock) => lock.try_acquire(name, options).await,
+            LockBackend::Postgres(lock) => lock.try_acquire(name, options).await,
+        }
+    }
+}
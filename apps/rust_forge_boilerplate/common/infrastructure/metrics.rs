@@ -0,0 +1,42 @@
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use std::sync::OnceLock;
+
+static PROMETHEUS_HANDLE: OnceLock<PrometheusHandle> = OnceLock::new();
+
+/// Upper bounds of the `db_query_duration_seconds` buckets, so it is exported
+/// as a histogram that can be aggregated across replicas.
+const DB_QUERY_BUCKETS: &[f64] = &[
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Installs the global Prometheus recorder on first call and returns the
+/// handle used to render the `/api/metrics` endpoint.
+pub fn install_recorder() -> PrometheusHandle {
+    PROMETHEUS_HANDLE
+        .get_or_init(|| {
+            PrometheusBuilder::new()
+                .set_buckets_for_metric(
+                    Matcher::Full("db_query_duration_seconds".to_string()),
+                    DB_QUERY_BUCKETS,
+                )
+                .expect("Invalid Prometheus buckets")
+                .install_recorder()
+                .expect("Failed to install Prometheus recorder")
+        })
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn db_query_duration_is_exported_as_a_histogram() {
+        let handle = install_recorder();
+        metrics::histogram!("db_query_duration_seconds").record(0.003);
+
+        let rendered = handle.render();
+        assert!(rendered.contains("# TYPE db_query_duration_seconds histogram"));
+        assert!(rendered.contains("db_query_duration_seconds_bucket{le=\"0.005\"} 1"));
+    }
+}
@@ -0,0 +1,814 @@
+use chrono::{DateTime, Utc};
+use sqlx::{
+    PgConnection,
+    migrate::{Migrate, MigrateError, Migration, MigrationType, Migrator},
+};
+use std::{
+    collections::HashMap,
+    env, fmt,
+    fs::OpenOptions,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+/// Records the SQL each applied migration ran, which `_sqlx_migrations` only
+/// keeps a checksum of, so a changed file can be diffed against it.
+const SCRIPTS_TABLE: &str = "_migration_scripts";
+
+/// Advisory lock key, scoped to the database so migrators of different
+/// databases on one server do not wait on each other.
+const LOCK_KEY: &str = "hashtext(current_database() || ':migrator')";
+
+#[derive(Debug)]
+pub enum MigrationError {
+    Migrate(MigrateError),
+    Io(io::Error),
+    /// `up --to` named a version with no migration file.
+    UnknownVersion(i64),
+    /// The migration has no `.down.sql` to revert it with.
+    Irreversible(i64),
+    InvalidName(String),
+    /// Applied migrations whose files changed since they ran.
+    Drift(Vec<MigrationDrift>),
+    /// Destructive statements that were not allowed to run.
+    Destructive(Vec<DestructiveStatement>),
+    /// Another migrator held the lock for longer than the timeout.
+    LockTimeout(Duration),
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationError::Migrate(e) => write!(f, "migration error: {}", e),
+            MigrationError::Io(e) => write!(f, "migration file error: {}", e),
+            MigrationError::UnknownVersion(version) => {
+                write!(f, "no migration with version {}", version)
+            }
+            MigrationError::Irreversible(version) => {
+                write!(f, "migration {} has no down migration", version)
+            }
+            MigrationError::InvalidName(name) => write!(f, "invalid migration name: {:?}", name),
+            MigrationError::Drift(drifts) => {
+                write!(
+                    f,
+                    "applied migrations changed since they ran; restore them and put the \
+                     change in a new migration"
+                )?;
+                for drift in drifts {
+                    write!(f, "\n\n{}", drift)?;
+                }
+                Ok(())
+            }
+            MigrationError::Destructive(statements) => {
+                write!(
+                    f,
+                    "refusing to run destructive statements without --allow-destructive:"
+                )?;
+                for statement in statements {
+                    write!(f, "\n  {}", statement)?;
+                }
+                Ok(())
+            }
+            MigrationError::LockTimeout(timeout) => write!(
+                f,
+                "another migrator held the migration lock for more than {:?}",
+                timeout
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MigrationError {}
+
+impl From<MigrateError> for MigrationError {
+    fn from(err: MigrateError) -> Self {
+        MigrationError::Migrate(err)
+    }
+}
+
+impl From<sqlx::Error> for MigrationError {
+    fn from(err: sqlx::Error) -> Self {
+        MigrationError::Migrate(MigrateError::Execute(err))
+    }
+}
+
+impl From<io::Error> for MigrationError {
+    fn from(err: io::Error) -> Self {
+        MigrationError::Io(err)
+    }
+}
+
+/// What to do with the migrations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrationPlan {
+    /// Applies pending migrations, up to and including `to` if given.
+    Up { to: Option<i64> },
+    /// Reverts the latest `steps` applied migrations.
+    Down { steps: usize },
+    /// Reverts the latest applied migration and applies it again.
+    Redo,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrationState {
+    Applied,
+    Pending,
+    /// Applied, but the file no longer matches what was run.
+    Changed,
+    /// Applied, but the file is gone.
+    Missing,
+}
+
+impl MigrationState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MigrationState::Applied => "applied",
+            MigrationState::Pending => "pending",
+            MigrationState::Changed => "changed",
+            MigrationState::Missing => "missing",
+        }
+    }
+}
+
+/// An applied migration whose file no longer matches what ran.
+#[derive(Debug, Clone)]
+pub struct MigrationDrift {
+    pub version: i64,
+    pub description: String,
+    pub path: Option<PathBuf>,
+    /// Line diff from the applied SQL to the file, when the applied SQL was
+    /// recorded.
+    pub diff: Option<String>,
+}
+
+impl fmt::Display for MigrationDrift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.version, self.description)?;
+        match &self.diff {
+            Some(diff) => write!(
+                f,
+                "\n--- applied\n+++ {}\n{}",
+                self.path
+                    .as_ref()
+                    .map(|path| path.display().to_string())
+                    .unwrap_or_default(),
+                diff.trim_end()
+            ),
+            None => write!(f, " (applied before its SQL was recorded; no diff)"),
+        }
+    }
+}
+
+/// A statement that drops or rewrites data, such as `DROP TABLE`.
+#[derive(Debug, Clone)]
+pub struct DestructiveStatement {
+    pub version: i64,
+    pub migration_type: MigrationType,
+    pub statement: String,
+}
+
+impl fmt::Display for DestructiveStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}: {}",
+            self.migration_type.label(),
+            self.version,
+            self.statement
+        )
+    }
+}
+
+/// How `Migrations::run` runs a plan.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RunOptions {
+    /// Return the plan without running it or taking the lock.
+    pub dry_run: bool,
+    /// Run statements `destructive_statements` flags. Without it the run is
+    /// refused before anything changes.
+    pub allow_destructive: bool,
+}
+
+/// A migration recorded in `_sqlx_migrations`.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct AppliedMigration {
+    pub version: i64,
+    pub description: String,
+    pub installed_on: DateTime<Utc>,
+    pub checksum: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub state: MigrationState,
+    /// Checksum of the file, or of what was applied when the file is gone.
+    pub checksum: Vec<u8>,
+    pub installed_on: Option<DateTime<Utc>>,
+    pub reversible: bool,
+}
+
+/// The migration files on disk, run the way `sqlx::migrate!` runs them so
+/// the bookkeeping in `_sqlx_migrations` stays compatible.
+///
+/// Files are read at runtime from `MIGRATIONS_DIR` rather than embedded, so
+/// a migration created with the migrator can be applied without a rebuild.
+pub struct Migrations {
+    dir: PathBuf,
+    migrator: Migrator,
+    lock_timeout: Duration,
+}
+
+impl Migrations {
+    pub async fn from_env() -> Result<Self, MigrationError> {
+        let dir = env::var("MIGRATIONS_DIR").unwrap_or_else(|_| "migrations".to_string());
+        let lock_timeout_secs: u64 = env::var("MIGRATOR_LOCK_TIMEOUT_SECS")
+            .unwrap_or_else(|_| "600".to_string())
+            .parse()
+            .expect("Invalid MIGRATOR_LOCK_TIMEOUT_SECS");
+
+        Ok(Self::load(dir)
+            .await?
+            .with_lock_timeout(Duration::from_secs(lock_timeout_secs)))
+    }
+
+    pub async fn load(dir: impl Into<PathBuf>) -> Result<Self, MigrationError> {
+        let dir = dir.into();
+        if !dir.is_dir() {
+            return Err(MigrationError::Io(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} is not a directory", dir.display()),
+            )));
+        }
+        let migrator = Migrator::new(dir.as_path()).await?;
+        Ok(Self {
+            dir,
+            migrator,
+            lock_timeout: Duration::from_secs(600),
+        })
+    }
+
+    /// How long `run` waits for another migrator to finish.
+    pub fn with_lock_timeout(mut self, lock_timeout: Duration) -> Self {
+        self.lock_timeout = lock_timeout;
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The migrations recorded as applied, oldest first. Reads nothing into
+    /// existence: a database never migrated has none.
+    pub async fn applied(
+        &self,
+        conn: &mut PgConnection,
+    ) -> Result<Vec<AppliedMigration>, MigrationError> {
+        let (exists,) =
+            sqlx::query_as::<_, (bool,)>("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+                .fetch_one(&mut *conn)
+                .await?;
+        if !exists {
+            return Ok(Vec::new());
+        }
+
+        Ok(sqlx::query_as::<_, AppliedMigration>(
+            "SELECT version, description, installed_on, checksum \
+             FROM _sqlx_migrations ORDER BY version",
+        )
+        .fetch_all(&mut *conn)
+        .await?)
+    }
+
+    /// Every migration on disk or in the database, oldest first.
+    pub fn status(&self, applied: &[AppliedMigration]) -> Vec<MigrationStatus> {
+        let mut applied: HashMap<i64, &AppliedMigration> =
+            applied.iter().map(|m| (m.version, m)).collect();
+
+        let mut statuses: Vec<MigrationStatus> = self
+            .ups()
+            .map(|migration| {
+                let record = applied.remove(&migration.version);
+                let state = match record {
+                    None => MigrationState::Pending,
+                    Some(record) if record.checksum == *migration.checksum => {
+                        MigrationState::Applied
+                    }
+                    Some(_) => MigrationState::Changed,
+                };
+                MigrationStatus {
+                    version: migration.version,
+                    description: migration.description.to_string(),
+                    state,
+                    checksum: migration.checksum.to_vec(),
+                    installed_on: record.map(|record| record.installed_on),
+                    reversible: self.down(migration.version).is_some(),
+                }
+            })
+            .collect();
+
+        statuses.extend(applied.into_values().map(|record| MigrationStatus {
+            version: record.version,
+            description: record.description.clone(),
+            state: MigrationState::Missing,
+            checksum: record.checksum.clone(),
+            installed_on: Some(record.installed_on),
+            reversible: false,
+        }));
+        statuses.sort_by_key(|status| status.version);
+        statuses
+    }
+
+    /// Runs `plan` and returns the migrations it ran, in order: up
+    /// migrations were applied, down migrations reverted. With `dry_run`,
+    /// returns them without touching the database.
+    ///
+    /// Refuses to run anything while an applied migration's file differs
+    /// from what ran, and holds an advisory lock throughout so parallel
+    /// migrators run one after the other: the later one finds the work done.
+    pub async fn run(
+        &self,
+        conn: &mut PgConnection,
+        plan: MigrationPlan,
+        options: RunOptions,
+    ) -> Result<Vec<Migration>, MigrationError> {
+        if options.dry_run {
+            let applied = self.applied(conn).await?;
+            self.check_drift(conn, &applied).await?;
+            return self.plan(&applied, plan);
+        }
+
+        self.lock(conn).await?;
+        let result = self.run_locked(conn, plan, options).await;
+        sqlx::query(&format!("SELECT pg_advisory_unlock({})", LOCK_KEY))
+            .execute(&mut *conn)
+            .await?;
+        result
+    }
+
+    async fn lock(&self, conn: &mut PgConnection) -> Result<(), MigrationError> {
+        let started = Instant::now();
+        let mut waiting = false;
+        loop {
+            let (locked,) =
+                sqlx::query_as::<_, (bool,)>(&format!("SELECT pg_try_advisory_lock({})", LOCK_KEY))
+                    .fetch_one(&mut *conn)
+                    .await?;
+            if locked {
+                return Ok(());
+            }
+            if started.elapsed() >= self.lock_timeout {
+                return Err(MigrationError::LockTimeout(self.lock_timeout));
+            }
+            if !waiting {
+                tracing::info!("Another migrator is running, waiting for it to finish...");
+                waiting = true;
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+    async fn run_locked(
+        &self,
+        conn: &mut PgConnection,
+        plan: MigrationPlan,
+        options: RunOptions,
+    ) -> Result<Vec<Migration>, MigrationError> {
+        conn.ensure_migrations_table().await?;
+        sqlx::query(&format!(
+            "CREATE TABLE IF NOT EXISTS {} ( \
+                 version BIGINT PRIMARY KEY, \
+                 sql TEXT NOT NULL, \
+                 recorded_at TIMESTAMPTZ NOT NULL DEFAULT NOW() \
+             )",
+            SCRIPTS_TABLE
+        ))
+        .execute(&mut *conn)
+        .await?;
+        if let Some(version) = conn.dirty_version().await? {
+            return Err(MigrateError::Dirty(version).into());
+        }
+
+        let applied = self.applied(conn).await?;
+        self.check_drift(conn, &applied).await?;
+        self.record_scripts(conn, &applied).await?;
+
+        let steps = self.plan(&applied, plan)?;
+        let destructive: Vec<DestructiveStatement> =
+            steps.iter().flat_map(destructive_statements).collect();
+        if !destructive.is_empty() {
+            if !options.allow_destructive {
+                return Err(MigrationError::Destructive(destructive));
+            }
+            for statement in &destructive {
+                tracing::warn!("Running destructive statement: {}", statement);
+            }
+        }
+
+        for migration in &steps {
+            let reverting = migration.migration_type.is_down_migration();
+            let elapsed = if reverting {
+                conn.revert(migration).await?
+            } else {
+                conn.apply(migration).await?
+            };
+            // Kept beside `_sqlx_migrations`, outside its transaction; a
+            // script lost to a crash here is recorded on the next run.
+            if reverting {
+                sqlx::query(&format!("DELETE FROM {} WHERE version = $1", SCRIPTS_TABLE))
+                    .bind(migration.version)
+                    .execute(&mut *conn)
+                    .await?;
+            } else {
+                sqlx::query(&format!(
+                    "INSERT INTO {} (version, sql) VALUES ($1, $2) \
+                     ON CONFLICT (version) DO UPDATE SET sql = EXCLUDED.sql, recorded_at = NOW()",
+                    SCRIPTS_TABLE
+                ))
+                .bind(migration.version)
+                .bind(&*migration.sql)
+                .execute(&mut *conn)
+                .await?;
+            }
+
+            tracing::info!(
+                "{} {}/{} in {:?}",
+                if reverting { "Reverted" } else { "Applied" },
+                migration.version,
+                migration.description,
+                elapsed
+            );
+        }
+        Ok(steps)
+    }
+
+    /// Fails with every applied migration whose file changed, diffed against
+    /// the recorded SQL where there is one.
+    async fn check_drift(
+        &self,
+        conn: &mut PgConnection,
+        applied: &[AppliedMigration],
+    ) -> Result<(), MigrationError> {
+        let files: HashMap<i64, &Migration> = self.ups().map(|m| (m.version, m)).collect();
+        let changed: Vec<(&AppliedMigration, &Migration)> = applied
+            .iter()
+            .filter_map(|record| {
+                files
+                    .get(&record.version)
+                    .filter(|file| *file.checksum != *record.checksum)
+                    .map(|file| (record, *file))
+            })
+            .collect();
+        if changed.is_empty() {
+            return Ok(());
+        }
+
+        let recorded = self.recorded_scripts(conn).await?;
+        let drifts = changed
+            .into_iter()
+            .map(|(record, file)| MigrationDrift {
+                version: record.version,
+                description: record.description.clone(),
+                path: self.path(file),
+                diff: recorded
+                    .get(&record.version)
+                    .map(|applied_sql| line_diff(applied_sql, &file.sql)),
+            })
+            .collect();
+        Err(MigrationError::Drift(drifts))
+    }
+
+    async fn recorded_scripts(
+        &self,
+        conn: &mut PgConnection,
+    ) -> Result<HashMap<i64, String>, MigrationError> {
+        let (exists,) = sqlx::query_as::<_, (bool,)>(&format!(
+            "SELECT to_regclass('{}') IS NOT NULL",
+            SCRIPTS_TABLE
+        ))
+        .fetch_one(&mut *conn)
+        .await?;
+        if !exists {
+            return Ok(HashMap::new());
+        }
+
+        let rows = sqlx::query_as::<_, (i64, String)>(&format!(
+            "SELECT version, sql FROM {}",
+            SCRIPTS_TABLE
+        ))
+        .fetch_all(&mut *conn)
+        .await?;
+        Ok(rows.into_iter().collect())
+    }
+
+    /// Records the SQL of applied migrations that ran before scripts were
+    /// recorded. Only called once drift is ruled out, so the files match.
+    async fn record_scripts(
+        &self,
+        conn: &mut PgConnection,
+        applied: &[AppliedMigration],
+    ) -> Result<(), MigrationError> {
+        let files: HashMap<i64, &Migration> = self.ups().map(|m| (m.version, m)).collect();
+        let (versions, scripts): (Vec<i64>, Vec<String>) = applied
+            .iter()
+            .filter_map(|record| files.get(&record.version))
+            .map(|file| (file.version, file.sql.to_string()))
+            .unzip();
+
+        sqlx::query(&format!(
+            "INSERT INTO {} (version, sql) \
+             SELECT * FROM UNNEST($1::BIGINT[], $2::TEXT[]) \
+             ON CONFLICT (version) DO NOTHING",
+            SCRIPTS_TABLE
+        ))
+        .bind(versions)
+        .bind(scripts)
+        .execute(&mut *conn)
+        .await?;
+        Ok(())
+    }
+
+    fn plan(
+        &self,
+        applied: &[AppliedMigration],
+        plan: MigrationPlan,
+    ) -> Result<Vec<Migration>, MigrationError> {
+        let files: HashMap<i64, &Migration> = self.ups().map(|m| (m.version, m)).collect();
+        if let Some(missing) = applied.iter().find(|m| !files.contains_key(&m.version)) {
+            return Err(MigrateError::VersionMissing(missing.version).into());
+        }
+
+        match plan {
+            MigrationPlan::Up { to } => {
+                if let Some(to) = to
+                    && !files.contains_key(&to)
+                {
+                    return Err(MigrationError::UnknownVersion(to));
+                }
+                Ok(self
+                    .ups()
+                    .filter(|m| to.is_none_or(|to| m.version <= to))
+                    .filter(|m| !applied.iter().any(|record| record.version == m.version))
+                    .cloned()
+                    .collect())
+            }
+            MigrationPlan::Down { steps } => applied
+                .iter()
+                .rev()
+                .take(steps)
+                .map(|record| {
+                    self.down(record.version)
+                        .cloned()
+                        .ok_or(MigrationError::Irreversible(record.version))
+                })
+                .collect(),
+            MigrationPlan::Redo => {
+                let Some(latest) = applied.last() else {
+                    return Ok(Vec::new());
+                };
+                let down = self
+                    .down(latest.version)
+                    .ok_or(MigrationError::Irreversible(latest.version))?;
+                Ok(vec![down.clone(), files[&latest.version].clone()])
+            }
+        }
+    }
+
+    fn ups(&self) -> impl Iterator<Item = &Migration> {
+        self.migrator
+            .iter()
+            .filter(|m| m.migration_type.is_up_migration())
+    }
+
+    fn down(&self, version: i64) -> Option<&Migration> {
+        self.migrator
+            .iter()
+            .find(|m| m.version == version && m.migration_type.is_down_migration())
+    }
+
+    /// The file `migration` was read from.
+    fn path(&self, migration: &Migration) -> Option<PathBuf> {
+        let prefix = format!("{}_", migration.version);
+        let suffix = migration.migration_type.suffix();
+        std::fs::read_dir(&self.dir)
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .find(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with(&prefix) && name.ends_with(suffix))
+            })
+    }
+
+    /// Writes an empty `<timestamp>_<name>.up.sql` and `.down.sql` pair and
+    /// returns their paths. The name is lowercased, with anything but
+    /// letters and digits turned into underscores.
+    pub fn create(&self, name: &str, now: DateTime<Utc>) -> Result<[PathBuf; 2], MigrationError> {
+        let slug = name
+            .trim()
+            .to_lowercase()
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join("_");
+        if slug.is_empty() {
+            return Err(MigrationError::InvalidName(name.to_string()));
+        }
+
+        let prefix = format!("{}_{}", now.format("%Y%m%d%H%M%S"), slug);
+        let write = |migration_type: MigrationType| -> Result<PathBuf, MigrationError> {
+            let path = self
+                .dir
+                .join(format!("{}{}", prefix, migration_type.suffix()));
+            // Never overwrite: a clash means a migration was created this second.
+            let mut file = OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)?;
+            file.write_all(migration_type.file_content().as_bytes())?;
+            Ok(path)
+        };
+        Ok([
+            write(MigrationType::ReversibleUp)?,
+            write(MigrationType::ReversibleDown)?,
+        ])
+    }
+}
+
+/// Statements in `migration` that drop or rewrite data: `DROP TABLE`,
+/// `DROP COLUMN`, `DROP SCHEMA`, `TRUNCATE`, `ALTER TYPE` and column type
+/// changes. Matches keywords outside comments, so it errs on the side of
+/// flagging; a keyword inside a string literal is flagged too.
+pub fn destructive_statements(migration: &Migration) -> Vec<DestructiveStatement> {
+    strip_comments(&migration.sql)
+        .split(';')
+        .filter(|statement| is_destructive(statement))
+        .map(|statement| DestructiveStatement {
+            version: migration.version,
+            migration_type: migration.migration_type,
+            statement: statement.split_whitespace().collect::<Vec<_>>().join(" "),
+        })
+        .collect()
+}
+
+fn is_destructive(statement: &str) -> bool {
+    let words: Vec<String> = statement
+        .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .filter(|word| !word.is_empty())
+        .map(str::to_uppercase)
+        .collect();
+    let word = |i: usize| words.get(i).map(String::as_str);
+
+    matches!(word(0), Some("TRUNCATE"))
+        || matches!((word(0), word(1)), (Some("ALTER"), Some("TYPE")))
+        || words.windows(2).any(|pair| {
+            pair[0] == "DROP" && matches!(pair[1].as_str(), "TABLE" | "COLUMN" | "SCHEMA")
+        })
+        || (0..words.len()).any(|i| {
+            words[i] == "TYPE"
+                && (i >= 2 && word(i - 2) == Some("ALTER")
+                    || i >= 3 && word(i - 3) == Some("ALTER") && word(i - 2) == Some("COLUMN")
+                    || i >= 2 && word(i - 2) == Some("SET") && word(i - 1) == Some("DATA"))
+        })
+}
+
+fn strip_comments(sql: &str) -> String {
+    let mut stripped = String::with_capacity(sql.len());
+    let mut rest = sql;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("--") {
+            rest = after.find('\n').map_or("", |end| &after[end..]);
+        } else if let Some(after) = rest.strip_prefix("/*") {
+            rest = after.find("*/").map_or("", |end| &after[end + 2..]);
+            stripped.push(' ');
+        } else {
+            let c = rest.chars().next().unwrap_or_default();
+            stripped.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    stripped
+}
+
+/// Line diff from `old` to `new`, with two lines of context around each
+/// change.
+fn line_diff(old: &str, new: &str) -> String {
+    const CONTEXT: usize = 2;
+
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // common[i][j]: length of the longest common subsequence of old[i..]
+    // and new[j..].
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines: Vec<(char, &str)> = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push((' ', old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(('-', old[i]));
+            i += 1;
+        } else {
+            lines.push(('+', new[j]));
+            j += 1;
+        }
+    }
+
+    let near_change = |index: usize| {
+        let start = index.saturating_sub(CONTEXT);
+        let end = (index + CONTEXT + 1).min(lines.len());
+        lines[start..end].iter().any(|(marker, _)| *marker != ' ')
+    };
+    let mut diff = String::new();
+    let mut skipped = false;
+    for (index, (marker, line)) in lines.iter().enumerate() {
+        if near_change(index) {
+            if skipped {
+                diff.push_str("  ...\n");
+                skipped = false;
+            }
+            diff.push_str(&format!("{} {}\n", marker, line));
+        } else {
+            skipped = true;
+        }
+    }
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_drops_and_truncates() {
+        assert!(is_destructive("DROP TABLE IF EXISTS users"));
+        assert!(is_destructive("drop schema audit cascade"));
+        assert!(is_destructive(
+            "ALTER TABLE users DROP COLUMN IF EXISTS nickname"
+        ));
+        assert!(is_destructive("TRUNCATE jobs"));
+        assert!(!is_destructive("DROP INDEX IF EXISTS users_email_idx"));
+    }
+
+    #[test]
+    fn flags_type_changes() {
+        assert!(is_destructive(
+            "ALTER TABLE users ALTER COLUMN age TYPE BIGINT"
+        ));
+        assert!(is_destructive("ALTER TABLE users ALTER age TYPE BIGINT"));
+        assert!(is_destructive(
+            "ALTER TABLE users ALTER COLUMN age SET DATA TYPE BIGINT"
+        ));
+        assert!(is_destructive(
+            "ALTER TYPE mood RENAME VALUE 'sad' TO 'blue'"
+        ));
+        assert!(!is_destructive("CREATE TABLE events (type TEXT NOT NULL)"));
+        assert!(!is_destructive("CREATE TYPE mood AS ENUM ('happy')"));
+    }
+
+    #[test]
+    fn ignores_keywords_in_comments() {
+        let sql = "CREATE TABLE t (id INT); -- DROP TABLE t\n/* TRUNCATE t */ SELECT 1";
+        assert_eq!(strip_comments(sql), "CREATE TABLE t (id INT); \n  SELECT 1");
+
+        let migration = Migration::new(
+            1,
+            "create t".into(),
+            MigrationType::ReversibleUp,
+            format!("{};\nDROP TABLE old;", sql).into(),
+        );
+        let statements = destructive_statements(&migration);
+        assert_eq!(statements.len(), 1);
+        assert_eq!(statements[0].statement, "DROP TABLE old");
+    }
+
+    #[test]
+    fn diffs_changed_lines_with_context() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh";
+        let new = "a\nb\nc\nd\nE\nf\ng\nh";
+        assert_eq!(line_diff(old, new), "  ...\n  c\n  d\n- e\n+ E\n  f\n  g\n");
+    }
+
+    #[test]
+    fn diffs_added_and_removed_lines() {
+        assert_eq!(line_diff("a\nb", "a\nb\nc"), "  a\n  b\n+ c\n");
+        assert_eq!(line_diff("a\nb\nc", "a\nc"), "  a\n- b\n  c\n");
+        assert_eq!(line_diff("same", "same"), "");
+    }
+}
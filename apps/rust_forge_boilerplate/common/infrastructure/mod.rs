@@ -1,2 +1,11 @@
 pub mod database;
-pub mod redis;
\ No newline at end of file
+pub mod local_cache;
+pub mod lock;
+pub mod metrics;
+pub mod migrations;
+pub mod mongo;
+pub mod outbox;
+pub mod queue;
+pub mod redis;
+pub mod scheduler;
+pub mod unit_of_work;
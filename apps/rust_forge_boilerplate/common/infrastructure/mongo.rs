@@ -0,0 +1,30 @@
+use mongodb::{Client, bson::doc};
+use std::future::Future;
+
+use crate::common::utils::backoff::{BackoffPolicy, retry_with_backoff};
+
+pub trait MongoClient {
+    fn create_client(
+        mongodb_url: &str,
+    ) -> impl Future<Output = Result<Client, mongodb::error::Error>> + Send;
+}
+
+pub struct MongoClientImpl;
+
+impl MongoClient for MongoClientImpl {
+    /// Builds a client and pings the deployment, retrying with the startup
+    /// `BackoffPolicy`. The driver connects lazily, so the ping is what
+    /// actually proves MongoDB is reachable.
+    async fn create_client(mongodb_url: &str) -> Result<Client, mongodb::error::Error> {
+        let policy = BackoffPolicy::from_env();
+        retry_with_backoff("MongoDB", &policy, || async {
+            let client = Client::with_uri_str(mongodb_url).await?;
+            client
+                .database("admin")
+                .run_command(doc! { "ping": 1 }, None)
+                .await?;
+            Ok(client)
+        })
+        .await
+    }
+}
@@ -0,0 +1,273 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{PgPool, postgres::PgListener};
+use std::{env, time::Duration};
+use uuid::Uuid;
+
+use crate::common::infrastructure::{database::DbExecutor, redis::RedisConnection};
+
+pub const OUTBOX_CHANNEL: &str = "outbox_events";
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct OutboxEvent {
+    pub id: Uuid,
+    pub stream: String,
+    pub event_type: String,
+    pub payload: serde_json::Value,
+    pub status: String,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub available_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub sent_at: Option<DateTime<Utc>>,
+}
+
+pub struct Outbox;
+
+impl Outbox {
+    /// Records an event to be published to the Redis stream `stream`.
+    ///
+    /// Pass the same transaction as the business write so the event is stored
+    /// if and only if that write commits; the worker relay publishes it later.
+    pub async fn enqueue<T: Serialize>(
+        mut db: DbExecutor<'_>,
+        stream: &str,
+        event_type: &str,
+        payload: &T,
+    ) -> Result<Uuid, sqlx::Error> {
+        let payload = serde_json::to_value(payload).map_err(|e| {
+            sqlx::Error::Protocol(format!("failed to serialize outbox payload: {}", e))
+        })?;
+        let id = Uuid::new_v4();
+
+        db.execute(
+            sqlx::query(
+                "INSERT INTO outbox_events (id, stream, event_type, payload) VALUES ($1, $2, $3, $4)",
+            )
+            .bind(id)
+            .bind(stream)
+            .bind(event_type)
+            .bind(payload),
+        )
+        .await?;
+
+        Ok(id)
+    }
+
+    /// Deletes events that were sent, or dead-lettered, before `before`.
+    /// Dead events count from their last attempt.
+    pub async fn purge_finished(
+        mut db: DbExecutor<'_>,
+        before: DateTime<Utc>,
+    ) -> Result<u64, sqlx::Error> {
+        let result = db
+            .execute(
+                sqlx::query(
+                    "DELETE FROM outbox_events \
+                     WHERE status IN ('sent', 'dead') \
+                       AND COALESCE(sent_at, available_at) < $1",
+                )
+                .bind(before),
+            )
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OutboxRelayConfig {
+    /// Whether the worker runs the relay; off for deployments without
+    /// Redis.
+    pub enabled: bool,
+    pub batch_size: i64,
+    pub poll_interval: Duration,
+    pub max_attempts: i32,
+    pub retry_delay: Duration,
+}
+
+impl OutboxRelayConfig {
+    pub fn from_env() -> Self {
+        let enabled: bool = env::var("OUTBOX_RELAY_ENABLED")
+            .unwrap_or_else(|_| "true".to_string())
+            .parse()
+            .expect("Invalid OUTBOX_RELAY_ENABLED");
+        let batch_size: i64 = env::var("OUTBOX_BATCH_SIZE")
+            .unwrap_or_else(|_| "100".to_string())
+            .parse()
+            .expect("Invalid OUTBOX_BATCH_SIZE");
+        let poll_interval_ms: u64 = env::var("OUTBOX_POLL_INTERVAL_MS")
+            .unwrap_or_else(|_| "1000".to_string())
+            .parse()
+            .expect("Invalid OUTBOX_POLL_INTERVAL_MS");
+        let max_attempts: i32 = env::var("OUTBOX_MAX_ATTEMPTS")
+            .unwrap_or_else(|_| "10".to_string())
+            .parse()
+            .expect("Invalid OUTBOX_MAX_ATTEMPTS");
+        let retry_delay_ms: u64 = env::var("OUTBOX_RETRY_DELAY_MS")
+            .unwrap_or_else(|_| "1000".to_string())
+            .parse()
+            .expect("Invalid OUTBOX_RETRY_DELAY_MS");
+
+        Self {
+            enabled,
+            batch_size,
+            poll_interval: Duration::from_millis(poll_interval_ms),
+            max_attempts,
+            retry_delay: Duration::from_millis(retry_delay_ms),
+        }
+    }
+}
+
+/// Moves pending outbox rows to their Redis streams.
+///
+/// Rows are claimed with `FOR UPDATE SKIP LOCKED`, so several workers can run
+/// a relay side by side. A row that keeps failing is retried with exponential
+/// backoff and marked `dead` once it reaches `max_attempts`. Delivery is
+/// at-least-once, so consumers should de-duplicate on `event_id`.
+pub struct OutboxRelay {
+    pool: PgPool,
+    redis_conn: RedisConnection,
+    config: OutboxRelayConfig,
+}
+
+impl OutboxRelay {
+    pub fn new(pool: PgPool, redis_conn: RedisConnection, config: OutboxRelayConfig) -> Self {
+        Self {
+            pool,
+            redis_conn,
+            config,
+        }
+    }
+
+    pub async fn run(mut self) {
+        let mut listener = match PgListener::connect_with(&self.pool).await {
+            Ok(mut listener) => match listener.listen(OUTBOX_CHANNEL).await {
+                Ok(()) => Some(listener),
+                Err(e) => {
+                    tracing::warn!("Outbox LISTEN failed, falling back to polling: {}", e);
+                    None
+                }
+            },
+            Err(e) => {
+                tracing::warn!(
+                    "Outbox listener unavailable, falling back to polling: {}",
+                    e
+                );
+                None
+            }
+        };
+
+        tracing::info!("Outbox relay started");
+
+        loop {
+            match self.relay_batch().await {
+                Ok(relayed) if relayed as i64 >= self.config.batch_size => continue,
+                Ok(_) => {}
+                Err(e) => tracing::error!("Outbox relay batch failed: {}", e),
+            }
+
+            match listener.as_mut() {
+                Some(listener) => {
+                    tokio::select! {
+                        notification = listener.recv() => {
+                            if let Err(e) = notification {
+                                tracing::warn!("Outbox listener error: {}", e);
+                            }
+                        }
+                        _ = tokio::time::sleep(self.config.poll_interval) => {}
+                    }
+                }
+                None => tokio::time::sleep(self.config.poll_interval).await,
+            }
+        }
+    }
+
+    /// Publishes one batch of due events and returns how many rows it claimed.
+    pub async fn relay_batch(&mut self) -> Result<usize, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let events = DbExecutor::from(&mut tx)
+            .fetch_all(
+                sqlx::query_as::<_, OutboxEvent>(
+                    "SELECT * FROM outbox_events \
+                     WHERE status = 'pending' AND available_at <= NOW() \
+                     ORDER BY created_at \
+                     LIMIT $1 \
+                     FOR UPDATE SKIP LOCKED",
+                )
+                .bind(self.config.batch_size),
+            )
+            .await?;
+
+        for event in &events {
+            match self.publish(event).await {
+                Ok(()) => {
+                    DbExecutor::from(&mut tx)
+                        .execute(
+                            sqlx::query(
+                                "UPDATE outbox_events SET status = 'sent', sent_at = NOW() WHERE id = $1",
+                            )
+                            .bind(event.id),
+                        )
+                        .await?;
+                }
+                Err(e) => {
+                    let attempts = event.attempts + 1;
+                    let status = if attempts >= self.config.max_attempts {
+                        tracing::error!(
+                            "Outbox event {} failed {} times, marking as dead: {}",
+                            event.id,
+                            attempts,
+                            e
+                        );
+                        "dead"
+                    } else {
+                        tracing::warn!(
+                            "Outbox event {} failed (attempt {}): {}",
+                            event.id,
+                            attempts,
+                            e
+                        );
+                        "pending"
+                    };
+                    let backoff = self.config.retry_delay * 2u32.pow(attempts.min(16) as u32 - 1);
+
+                    DbExecutor::from(&mut tx)
+                        .execute(
+                            sqlx::query(
+                                "UPDATE outbox_events \
+                                 SET status = $2, attempts = $3, last_error = $4, available_at = $5 \
+                                 WHERE id = $1",
+                            )
+                            .bind(event.id)
+                            .bind(status)
+                            .bind(attempts)
+                            .bind(e.to_string())
+                            .bind(Utc::now() + backoff),
+                        )
+                        .await?;
+                }
+            }
+        }
+
+        tx.commit().await?;
+        Ok(events.len())
+    }
+
+    async fn publish(&mut self, event: &OutboxEvent) -> Result<(), redis::RedisError> {
+        redis::cmd("XADD")
+            .arg(&event.stream)
+            .arg("*")
+            .arg("event_id")
+            .arg(event.id.to_string())
+            .arg("event_type")
+            .arg(&event.event_type)
+            .arg("payload")
+            .arg(event.payload.to_string())
+            .arg("created_at")
+            .arg(event.created_at.to_rfc3339())
+            .query_async::<_, String>(&mut self.redis_conn)
+            .await
+            .map(|_| ())
+    }
+}
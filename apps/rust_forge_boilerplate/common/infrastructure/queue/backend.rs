@@ -0,0 +1,333 @@
+use chrono::{DateTime, Utc};
+use redis::RedisError;
+use sqlx::PgPool;
+use std::{env, fmt, future::Future, time::Duration};
+use uuid::Uuid;
+
+use crate::common::infrastructure::{
+    queue::{
+        job::{Job, JobEnvelope},
+        postgres::PostgresBackend,
+        redis_streams::RedisStreamsBackend,
+        status::{JobState, JobStatusStore},
+        workflow::JobChain,
+    },
+    redis::RedisConnection,
+};
+
+#[derive(Debug)]
+pub enum QueueError {
+    Redis(RedisError),
+    Database(sqlx::Error),
+    Codec(String),
+    /// A job with settings the worker cannot honour, such as a bad backoff.
+    InvalidJob(String),
+    /// The delivery's lease ran out and the job was handed to another
+    /// worker.
+    LeaseLost,
+}
+
+impl fmt::Display for QueueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueueError::Redis(e) => write!(f, "job queue backend error: {}", e),
+            QueueError::Database(e) => write!(f, "job queue backend error: {}", e),
+            QueueError::Codec(e) => write!(f, "job queue codec error: {}", e),
+            QueueError::InvalidJob(e) => write!(f, "invalid job: {}", e),
+            QueueError::LeaseLost => write!(f, "job lease lost to another worker"),
+        }
+    }
+}
+
+impl std::error::Error for QueueError {}
+
+impl From<RedisError> for QueueError {
+    fn from(err: RedisError) -> Self {
+        QueueError::Redis(err)
+    }
+}
+
+impl From<sqlx::Error> for QueueError {
+    fn from(err: sqlx::Error) -> Self {
+        QueueError::Database(err)
+    }
+}
+
+impl From<serde_json::Error> for QueueError {
+    fn from(err: serde_json::Error) -> Self {
+        QueueError::Codec(err.to_string())
+    }
+}
+
+/// A job handed to one worker. It stays invisible to the others until it is
+/// acknowledged, or until its lease runs out because the heartbeats stopped.
+#[derive(Debug, Clone)]
+pub struct Delivery {
+    pub envelope: JobEnvelope,
+    /// Backend handle used to acknowledge the delivery.
+    pub receipt: String,
+}
+
+/// What `JobQueue::enqueue` did with a job.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Enqueued {
+    Queued(Uuid),
+    /// A job with the same unique key is already queued or ran within the
+    /// key's window; holds that job's id.
+    Duplicate(Uuid),
+}
+
+impl Enqueued {
+    pub fn id(&self) -> Uuid {
+        match self {
+            Enqueued::Queued(id) | Enqueued::Duplicate(id) => *id,
+        }
+    }
+}
+
+/// Storage and transport of queued jobs.
+pub trait QueueBackend: Send + Sync + 'static {
+    /// Queues `envelope` to run at its `run_at`, replacing any queued job
+    /// with the same id.
+    fn push(&self, envelope: &JobEnvelope) -> impl Future<Output = Result<(), QueueError>> + Send;
+
+    /// Queues `envelope` unless `key` was claimed less than `window` ago, in
+    /// which case it returns the id of the job that claimed it.
+    fn push_unique(
+        &self,
+        envelope: &JobEnvelope,
+        key: &str,
+        window: Duration,
+    ) -> impl Future<Output = Result<Option<Uuid>, QueueError>> + Send;
+
+    /// Claims up to `max` jobs for `consumer`, without waiting for new ones.
+    fn fetch(
+        &self,
+        consumer: &str,
+        max: usize,
+    ) -> impl Future<Output = Result<Vec<Delivery>, QueueError>> + Send;
+
+    /// Extends the lease on a delivery that is still running.
+    fn heartbeat(
+        &self,
+        consumer: &str,
+        delivery: &Delivery,
+    ) -> impl Future<Output = Result<(), QueueError>> + Send;
+
+    /// Removes a finished delivery from the queue.
+    fn ack(&self, delivery: &Delivery) -> impl Future<Output = Result<(), QueueError>> + Send;
+
+    /// Waits up to `max` for new jobs. Backends without push notifications
+    /// just sleep.
+    fn wait(&self, max: Duration) -> impl Future<Output = ()> + Send {
+        tokio::time::sleep(max)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct JobQueueConfig {
+    /// How long a delivery may go without a heartbeat before another worker
+    /// may claim it.
+    pub lease_timeout: Duration,
+    /// Waiting time after which a job is treated as one priority level
+    /// higher.
+    pub priority_aging: Duration,
+}
+
+impl JobQueueConfig {
+    pub fn from_env() -> Self {
+        let lease_timeout_secs: u64 = env::var("JOB_LEASE_TIMEOUT_SECS")
+            .unwrap_or_else(|_| "30".to_string())
+            .parse()
+            .expect("Invalid JOB_LEASE_TIMEOUT_SECS");
+        let priority_aging_secs: u64 = env::var("JOB_PRIORITY_AGING_SECS")
+            .unwrap_or_else(|_| "60".to_string())
+            .parse()
+            .expect("Invalid JOB_PRIORITY_AGING_SECS");
+
+        Self {
+            lease_timeout: Duration::from_secs(lease_timeout_secs.max(1)),
+            priority_aging: Duration::from_secs(priority_aging_secs),
+        }
+    }
+}
+
+fn backend_name() -> String {
+    env::var("JOB_QUEUE_BACKEND")
+        .unwrap_or_else(|_| "redis".to_string())
+        .to_lowercase()
+}
+
+enum Backend {
+    Redis(RedisStreamsBackend),
+    Postgres(PostgresBackend),
+}
+
+/// The configured backend, and the API the rest of the app enqueues with.
+///
+/// Every push first records the job as queued, or as retrying for a later
+/// attempt, in the job statuses.
+pub struct JobQueue {
+    backend: Backend,
+    statuses: JobStatusStore,
+}
+
+impl JobQueue {
+    /// `conn` may be `None` when `uses_redis` is false, so deployments on
+    /// the Postgres backend need no Redis.
+    pub fn from_env(conn: Option<RedisConnection>, pool: PgPool, namespace: &str) -> Self {
+        let config = JobQueueConfig::from_env();
+        let backend = match backend_name().as_str() {
+            "redis" => Backend::Redis(RedisStreamsBackend::new(
+                conn.expect("JOB_QUEUE_BACKEND=redis needs a Redis connection"),
+                namespace,
+                config,
+            )),
+            "postgres" => Backend::Postgres(PostgresBackend::new(pool.clone(), config)),
+            other => panic!("Invalid JOB_QUEUE_BACKEND: {}", other),
+        };
+        Self {
+            backend,
+            statuses: JobStatusStore::new(pool),
+        }
+    }
+
+    /// Whether `JOB_QUEUE_BACKEND` selects the Redis backend.
+    pub fn uses_redis() -> bool {
+        backend_name() == "redis"
+    }
+
+    pub fn statuses(&self) -> &JobStatusStore {
+        &self.statuses
+    }
+
+    /// Deletes unique keys whose window has passed. Redis expires them on
+    /// its own, so this only has work to do on the Postgres backend.
+    pub async fn purge_expired_unique_keys(&self) -> Result<u64, QueueError> {
+        match &self.backend {
+            Backend::Redis(_) => Ok(0),
+            Backend::Postgres(backend) => Ok(backend.purge_expired_unique_keys().await?),
+        }
+    }
+
+    pub async fn enqueue<J: Job>(&self, job: &J) -> Result<Enqueued, QueueError> {
+        self.enqueue_at(job, Utc::now()).await
+    }
+
+    /// Queues the first job of `chain` and returns its id.
+    pub async fn enqueue_chain(&self, chain: JobChain) -> Result<Uuid, QueueError> {
+        let envelope = chain.into_envelope()?;
+        self.push(&envelope).await?;
+        Ok(envelope.id)
+    }
+
+    pub async fn enqueue_in<J: Job>(
+        &self,
+        job: &J,
+        delay: Duration,
+    ) -> Result<Enqueued, QueueError> {
+        self.enqueue_at(job, Utc::now() + delay).await
+    }
+
+    pub async fn enqueue_at<J: Job>(
+        &self,
+        job: &J,
+        run_at: DateTime<Utc>,
+    ) -> Result<Enqueued, QueueError> {
+        let mut envelope = JobEnvelope::new(job)?;
+        envelope.run_at = run_at;
+
+        let Some(key) = job.unique_key() else {
+            self.push(&envelope).await?;
+            return Ok(Enqueued::Queued(envelope.id));
+        };
+        let key = format!("{}:{}", J::NAME, key);
+        Ok(
+            match self.push_unique(&envelope, &key, job.unique_for()).await? {
+                Some(existing) => Enqueued::Duplicate(existing),
+                None => Enqueued::Queued(envelope.id),
+            },
+        )
+    }
+}
+
+impl JobQueue {
+    /// Written before the push, so it can never overwrite what a worker
+    /// recorded after picking the job up.
+    async fn record_queued(&self, envelope: &JobEnvelope) -> Result<(), QueueError> {
+        let (status, error) = match envelope.history.last() {
+            Some(failed) if envelope.attempt > 0 => {
+                (JobState::Retrying, Some(failed.error.as_str()))
+            }
+            _ => (JobState::Queued, None),
+        };
+        self.statuses.record(envelope, status, error).await
+    }
+
+    async fn forget(&self, envelope: &JobEnvelope) {
+        if let Err(e) = self.statuses.remove(envelope.id).await {
+            tracing::warn!("Failed to remove status of job {}: {}", envelope.id, e);
+        }
+    }
+}
+
+impl QueueBackend for JobQueue {
+    async fn push(&self, envelope: &JobEnvelope) -> Result<(), QueueError> {
+        self.record_queued(envelope).await?;
+        let pushed = match &self.backend {
+            Backend::Redis(backend) => backend.push(envelope).await,
+            Backend::Postgres(backend) => backend.push(envelope).await,
+        };
+        // A retry that failed to queue is delivered again, which records it
+        // anew; a new job never existed.
+        if pushed.is_err() && envelope.attempt == 0 {
+            self.forget(envelope).await;
+        }
+        pushed
+    }
+
+    async fn push_unique(
+        &self,
+        envelope: &JobEnvelope,
+        key: &str,
+        window: Duration,
+    ) -> Result<Option<Uuid>, QueueError> {
+        self.record_queued(envelope).await?;
+        let pushed = match &self.backend {
+            Backend::Redis(backend) => backend.push_unique(envelope, key, window).await,
+            Backend::Postgres(backend) => backend.push_unique(envelope, key, window).await,
+        };
+        if !matches!(pushed, Ok(None)) {
+            self.forget(envelope).await;
+        }
+        pushed
+    }
+
+    async fn fetch(&self, consumer: &str, max: usize) -> Result<Vec<Delivery>, QueueError> {
+        match &self.backend {
+            Backend::Redis(backend) => backend.fetch(consumer, max).await,
+            Backend::Postgres(backend) => backend.fetch(consumer, max).await,
+        }
+    }
+
+    async fn heartbeat(&self, consumer: &str, delivery: &Delivery) -> Result<(), QueueError> {
+        match &self.backend {
+            Backend::Redis(backend) => backend.heartbeat(consumer, delivery).await,
+            Backend::Postgres(backend) => backend.heartbeat(consumer, delivery).await,
+        }
+    }
+
+    async fn ack(&self, delivery: &Delivery) -> Result<(), QueueError> {
+        match &self.backend {
+            Backend::Redis(backend) => backend.ack(delivery).await,
+            Backend::Postgres(backend) => backend.ack(delivery).await,
+        }
+    }
+
+    async fn wait(&self, max: Duration) {
+        match &self.backend {
+            Backend::Redis(backend) => backend.wait(max).await,
+            Backend::Postgres(backend) => backend.wait(max).await,
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, types::Json};
+use uuid::Uuid;
+
+use crate::common::infrastructure::{
+    database::DbExecutor,
+    queue::{
+        backend::{JobQueue, QueueBackend, QueueError},
+        job::{JobEnvelope, JobError},
+    },
+};
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct DeadLetter {
+    pub id: Uuid,
+    pub name: String,
+    /// The job as of its last run, attempt history included.
+    pub envelope: Json<JobEnvelope>,
+    pub attempts: i32,
+    pub last_error: String,
+    pub stack: Option<String>,
+    pub failed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DeadLetterFilter {
+    pub name: Option<String>,
+    pub failed_before: Option<DateTime<Utc>>,
+}
+
+/// Jobs that used up their attempts or failed with a non-retryable error.
+///
+/// Kept in Postgres whatever the queue backend, so they survive Redis
+/// evictions and can be queried by the admin API.
+#[derive(Clone)]
+pub struct DeadLetterStore {
+    pool: PgPool,
+}
+
+impl DeadLetterStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn insert(&self, envelope: &JobEnvelope, error: &JobError) -> Result<(), QueueError> {
+        let stack = [error.details.as_deref(), error.stack.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+
+        DbExecutor::from(&self.pool)
+            .execute(
+                sqlx::query(
+                    "INSERT INTO dead_letter_jobs (id, name, envelope, attempts, last_error, stack) \
+                     VALUES ($1, $2, $3, $4, $5, $6) \
+                     ON CONFLICT (id) DO UPDATE \
+                         SET envelope = EXCLUDED.envelope, attempts = EXCLUDED.attempts, \
+                             last_error = EXCLUDED.last_error, stack = EXCLUDED.stack, \
+                             failed_at = NOW()",
+                )
+                .bind(envelope.id)
+                .bind(&envelope.name)
+                .bind(Json(envelope))
+                .bind(envelope.attempt as i32 + 1)
+                .bind(&error.message)
+                .bind((!stack.is_empty()).then(|| stack.join("\n\n"))),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Newest first.
+    pub async fn list(
+        &self,
+        filter: &DeadLetterFilter,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<DeadLetter>, QueueError> {
+        let rows = DbExecutor::from(&self.pool)
+            .fetch_all(
+                sqlx::query_as::<_, DeadLetter>(
+                    "SELECT * FROM dead_letter_jobs \
+                     WHERE ($1::TEXT IS NULL OR name = $1) \
+                       AND ($2::TIMESTAMPTZ IS NULL OR failed_at < $2) \
+                     ORDER BY failed_at DESC \
+                     LIMIT $3 OFFSET $4",
+                )
+                .bind(&filter.name)
+                .bind(filter.failed_before)
+                .bind(limit)
+                .bind(offset),
+            )
+            .await?;
+        Ok(rows)
+    }
+
+    pub async fn get(&self, id: Uuid) -> Result<Option<DeadLetter>, QueueError> {
+        let row = DbExecutor::from(&self.pool)
+            .fetch_optional(
+                sqlx::query_as::<_, DeadLetter>("SELECT * FROM dead_letter_jobs WHERE id = $1")
+                    .bind(id),
+            )
+            .await?;
+        Ok(row)
+    }
+
+    /// Queues the job again with a fresh set of attempts and removes it from
+    /// the dead letters. Returns `false` if there is no such dead letter.
+    pub async fn replay(&self, id: Uuid, queue: &JobQueue) -> Result<bool, QueueError> {
+        let Some(dead_letter) = self.get(id).await? else {
+            return Ok(false);
+        };
+
+        // Queue first: if the delete fails the job may run twice, but it is
+        // never lost.
+        queue.push(&dead_letter.envelope.replay()).await?;
+        self.delete(id).await?;
+        Ok(true)
+    }
+
+    pub async fn delete(&self, id: Uuid) -> Result<bool, QueueError> {
+        let result = DbExecutor::from(&self.pool)
+            .execute(sqlx::query("DELETE FROM dead_letter_jobs WHERE id = $1").bind(id))
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Deletes every dead letter matching `filter` and returns how many.
+    pub async fn purge(&self, filter: &DeadLetterFilter) -> Result<u64, QueueError> {
+        let result = DbExecutor::from(&self.pool)
+            .execute(
+                sqlx::query(
+                    "DELETE FROM dead_letter_jobs \
+                     WHERE ($1::TEXT IS NULL OR name = $1) \
+                       AND ($2::TIMESTAMPTZ IS NULL OR failed_at < $2)",
+                )
+                .bind(&filter.name)
+                .bind(filter.failed_before),
+            )
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
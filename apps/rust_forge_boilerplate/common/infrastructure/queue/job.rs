@@ -0,0 +1,359 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use std::{
+    any::{Any, TypeId},
+    backtrace::{Backtrace, BacktraceStatus},
+    collections::HashMap,
+    fmt,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use uuid::Uuid;
+
+use crate::common::{
+    infrastructure::queue::{
+        backend::QueueError, registry::JobRegistry, status::JobStatusStore, workflow::JobWorkflow,
+    },
+    utils::backoff::BackoffPolicy,
+};
+
+/// A background job. The implementing type is the payload: it is serialized
+/// when enqueued and deserialized by the worker that runs it.
+pub trait Job: Serialize + DeserializeOwned + Send + Sync + 'static {
+    /// Unique name the worker uses to find the handler.
+    const NAME: &'static str;
+
+    /// Total runs allowed, the first one included.
+    fn max_attempts(&self) -> u32 {
+        3
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::from_secs(60)
+    }
+
+    /// Delay before each retry. Retries stop at `max_attempts`, so
+    /// `max_elapsed` is ignored.
+    fn backoff(&self) -> BackoffPolicy {
+        default_backoff()
+    }
+
+    fn priority(&self) -> JobPriority {
+        JobPriority::Normal
+    }
+
+    /// Jobs of this type with the same key are only queued once per
+    /// `unique_for`, counted from the first enqueue; later ones are dropped
+    /// as duplicates.
+    fn unique_key(&self) -> Option<String> {
+        None
+    }
+
+    fn unique_for(&self) -> Duration {
+        Duration::from_secs(3600)
+    }
+
+    fn run(&self, ctx: &JobContext) -> impl Future<Output = Result<(), JobError>> + Send;
+}
+
+/// Workers take higher priorities first. A job also gains one level for each
+/// `JOB_PRIORITY_AGING_SECS` it has been waiting, so low priorities still run
+/// under a steady stream of high-priority work.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl JobPriority {
+    /// Highest first.
+    pub const ALL: [JobPriority; 3] = [JobPriority::High, JobPriority::Normal, JobPriority::Low];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobPriority::Low => "low",
+            JobPriority::Normal => "normal",
+            JobPriority::High => "high",
+        }
+    }
+
+    pub fn level(&self) -> i16 {
+        match self {
+            JobPriority::Low => 0,
+            JobPriority::Normal => 1,
+            JobPriority::High => 2,
+        }
+    }
+
+    pub fn from_level(level: i16) -> Self {
+        match level {
+            i16::MIN..=0 => JobPriority::Low,
+            1 => JobPriority::Normal,
+            _ => JobPriority::High,
+        }
+    }
+}
+
+/// 1s, doubling up to an hour, with full jitter.
+pub fn default_backoff() -> BackoffPolicy {
+    BackoffPolicy {
+        initial_delay: Duration::from_secs(1),
+        max_delay: Duration::from_secs(3600),
+        multiplier: 2.0,
+        max_elapsed: None,
+    }
+}
+
+/// Why a run failed. Any error type converts into it, so handlers can use
+/// `?`; the source chain is kept for troubleshooting, along with a backtrace
+/// of the conversion when `RUST_BACKTRACE` is set.
+#[derive(Debug, Clone)]
+pub struct JobError {
+    pub message: String,
+    pub details: Option<String>,
+    pub stack: Option<String>,
+    /// `false` sends the job straight to its final failure, skipping any
+    /// remaining attempts.
+    pub retryable: bool,
+}
+
+impl JobError {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            details: None,
+            stack: None,
+            retryable: true,
+        }
+    }
+
+    pub fn fatal(message: impl Into<String>) -> Self {
+        Self {
+            retryable: false,
+            ..Self::new(message)
+        }
+    }
+}
+
+impl<E: std::error::Error> From<E> for JobError {
+    fn from(err: E) -> Self {
+        let mut details = Vec::new();
+        let mut source = err.source();
+        while let Some(cause) = source {
+            details.push(format!("caused by: {}", cause));
+            source = cause.source();
+        }
+
+        let backtrace = Backtrace::capture();
+
+        Self {
+            message: err.to_string(),
+            details: (!details.is_empty()).then(|| details.join("\n")),
+            stack: (backtrace.status() == BacktraceStatus::Captured).then(|| backtrace.to_string()),
+            retryable: true,
+        }
+    }
+}
+
+impl fmt::Display for JobError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// One failed run of a job.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobAttempt {
+    /// 1 for the first run.
+    pub attempt: u32,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub error: String,
+}
+
+/// A job as stored in the queue.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobEnvelope {
+    pub id: Uuid,
+    pub name: String,
+    pub payload: serde_json::Value,
+    /// Runs that already happened.
+    pub attempt: u32,
+    pub max_attempts: u32,
+    pub timeout_ms: u64,
+    pub backoff: BackoffPolicy,
+    pub priority: JobPriority,
+    /// Failed runs so far, oldest first.
+    pub history: Vec<JobAttempt>,
+    /// Not delivered before this time.
+    pub run_at: DateTime<Utc>,
+    pub enqueued_at: DateTime<Utc>,
+    pub workflow: JobWorkflow,
+}
+
+impl JobEnvelope {
+    pub fn new<J: Job>(job: &J) -> Result<Self, QueueError> {
+        let now = Utc::now();
+        let envelope = Self {
+            id: Uuid::new_v4(),
+            name: J::NAME.to_string(),
+            payload: serde_json::to_value(job)?,
+            attempt: 0,
+            max_attempts: job.max_attempts().max(1),
+            timeout_ms: job.timeout().as_millis() as u64,
+            backoff: job.backoff(),
+            priority: job.priority(),
+            history: Vec::new(),
+            run_at: now,
+            enqueued_at: now,
+            workflow: JobWorkflow::default(),
+        };
+        envelope.validate().map_err(QueueError::InvalidJob)?;
+        Ok(envelope)
+    }
+
+    /// Checks the settings a worker relies on, so a bad policy fails the job
+    /// rather than the worker.
+    pub fn validate(&self) -> Result<(), String> {
+        self.backoff.validate()
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+
+    /// Whether another run is allowed after the current one fails.
+    pub fn can_retry(&self) -> bool {
+        self.attempt + 1 < self.max_attempts
+    }
+
+    /// The next run after `failed`, delayed by the backoff policy.
+    pub fn retry(&self, failed: JobAttempt) -> Self {
+        let delay = self.backoff.delay_for(self.attempt);
+        let mut next = self.clone();
+        next.attempt += 1;
+        next.history.push(failed);
+        next.run_at = Utc::now() + delay;
+        next
+    }
+
+    /// The next job in the chain, taking `result` as its input and the rest
+    /// of the chain with it.
+    pub fn next_in_chain(&self, result: Option<serde_json::Value>) -> Option<Self> {
+        let (next, rest) = self.workflow.chain.split_first()?;
+        let mut next = next.clone();
+        next.workflow.chain = rest.to_vec();
+        next.workflow.input = result;
+        next.workflow.batch = self.workflow.batch.clone();
+        next.run_at = Utc::now();
+        Some(next)
+    }
+
+    /// A fresh copy for replaying a dead-lettered job: no attempts used, due
+    /// immediately.
+    pub fn replay(&self) -> Self {
+        let mut next = self.clone();
+        next.attempt = 0;
+        next.history.clear();
+        next.run_at = Utc::now();
+        next
+    }
+}
+
+/// Shared values registered on the `JobRegistry`, such as a database pool.
+#[derive(Default)]
+pub(crate) struct JobData {
+    values: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+}
+
+impl JobData {
+    pub(crate) fn insert<T: Send + Sync + 'static>(&mut self, value: T) {
+        self.values.insert(TypeId::of::<T>(), Box::new(value));
+    }
+
+    pub(crate) fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.values
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref())
+    }
+}
+
+/// What a handler knows about the run it is part of.
+pub struct JobContext {
+    pub job_id: Uuid,
+    /// 1 for the first run.
+    pub attempt: u32,
+    /// The batch this job, or the chain it belongs to, is part of.
+    pub batch_id: Option<Uuid>,
+    input: Option<serde_json::Value>,
+    result: Mutex<Option<serde_json::Value>>,
+    registry: Arc<JobRegistry>,
+    statuses: JobStatusStore,
+}
+
+impl JobContext {
+    pub(crate) fn new(
+        envelope: &JobEnvelope,
+        registry: Arc<JobRegistry>,
+        statuses: JobStatusStore,
+    ) -> Self {
+        Self {
+            job_id: envelope.id,
+            attempt: envelope.attempt + 1,
+            batch_id: envelope
+                .workflow
+                .batch
+                .as_ref()
+                .map(|membership| membership.batch_id),
+            input: envelope.workflow.input.clone(),
+            result: Mutex::new(None),
+            registry,
+            statuses,
+        }
+    }
+
+    /// A value registered with `JobRegistry::with_data`.
+    pub fn data<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.registry.data()
+    }
+
+    /// The result of the previous job in the chain, or the `BatchOutcome`
+    /// for a batch's completion callback.
+    pub fn input<T: DeserializeOwned>(&self) -> Result<T, JobError> {
+        let input = self
+            .input
+            .clone()
+            .ok_or_else(|| JobError::fatal("job has no input"))?;
+        serde_json::from_value(input)
+            .map_err(|e| JobError::fatal(format!("invalid job input: {}", e)))
+    }
+
+    /// Records this run's result, passed on to the next job in the chain
+    /// or to the batch. Only kept if the run succeeds.
+    pub fn set_result<T: Serialize>(&self, result: &T) -> Result<(), JobError> {
+        let result = serde_json::to_value(result)
+            .map_err(|e| JobError::fatal(format!("invalid job result: {}", e)))?;
+        *self.result.lock().unwrap() = Some(result);
+        Ok(())
+    }
+
+    /// Reports how far the run got, shown in the job's status. Failing to
+    /// record it does not fail the job.
+    pub async fn set_progress(&self, percent: Option<u8>, message: Option<&str>) {
+        if let Err(e) = self
+            .statuses
+            .set_progress(self.job_id, percent, message)
+            .await
+        {
+            tracing::warn!("Failed to record progress of job {}: {}", self.job_id, e);
+        }
+    }
+
+    pub(crate) fn take_result(&self) -> Option<serde_json::Value> {
+        self.result.lock().unwrap().take()
+    }
+}
@@ -0,0 +1,9 @@
+pub mod backend;
+pub mod dead_letter;
+pub mod job;
+pub mod postgres;
+pub mod redis_streams;
+pub mod registry;
+pub mod status;
+pub mod worker;
+pub mod workflow;
@@ -0,0 +1,324 @@
+use chrono::{DateTime, Utc};
+use sqlx::{
+    PgPool, Postgres,
+    postgres::{PgArguments, PgListener},
+    query::Query,
+    types::Json,
+};
+use std::{
+    sync::{
+        Arc, Weak,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+use tokio::sync::Notify;
+use uuid::Uuid;
+
+use crate::common::{
+    infrastructure::{
+        database::DbExecutor,
+        queue::{
+            backend::{Delivery, JobQueueConfig, QueueBackend, QueueError},
+            job::{JobAttempt, JobEnvelope, JobPriority, default_backoff},
+            workflow::JobWorkflow,
+        },
+    },
+    utils::backoff::BackoffPolicy,
+};
+
+pub const JOBS_CHANNEL: &str = "jobs";
+
+#[derive(sqlx::FromRow)]
+struct JobRow {
+    id: Uuid,
+    name: String,
+    payload: serde_json::Value,
+    attempts: i32,
+    max_attempts: i32,
+    timeout_ms: i64,
+    backoff: Option<Json<BackoffPolicy>>,
+    priority: i16,
+    history: Json<Vec<JobAttempt>>,
+    run_at: DateTime<Utc>,
+    enqueued_at: DateTime<Utc>,
+    workflow: Option<Json<JobWorkflow>>,
+    lease_id: Uuid,
+}
+
+impl From<JobRow> for Delivery {
+    fn from(row: JobRow) -> Self {
+        Delivery {
+            envelope: JobEnvelope {
+                id: row.id,
+                name: row.name,
+                payload: row.payload,
+                attempt: row.attempts as u32,
+                max_attempts: row.max_attempts as u32,
+                timeout_ms: row.timeout_ms as u64,
+                // Rows queued before the column existed.
+                backoff: row
+                    .backoff
+                    .map(|backoff| backoff.0)
+                    .unwrap_or_else(default_backoff),
+                priority: JobPriority::from_level(row.priority),
+                history: row.history.0,
+                run_at: row.run_at,
+                enqueued_at: row.enqueued_at,
+                workflow: row.workflow.map(|workflow| workflow.0).unwrap_or_default(),
+            },
+            receipt: row.lease_id.to_string(),
+        }
+    }
+}
+
+/// Jobs in the `jobs` table, claimed with `FOR UPDATE SKIP LOCKED`.
+///
+/// A claimed row records the worker in `locked_by` and a fresh `lease_id`;
+/// heartbeats bump `heartbeat_at`, and rows whose heartbeat is older than
+/// `lease_timeout` can be claimed again. Inserts fire `NOTIFY jobs`, which
+/// wakes idle workers before their next poll.
+///
+/// Jobs are taken in `sort_at` order: `run_at` moved earlier by
+/// `priority_aging` per priority level.
+pub struct PostgresBackend {
+    pool: PgPool,
+    config: JobQueueConfig,
+    wakeup: Arc<Notify>,
+    listening: AtomicBool,
+}
+
+impl PostgresBackend {
+    pub fn new(pool: PgPool, config: JobQueueConfig) -> Self {
+        Self {
+            pool,
+            config,
+            wakeup: Arc::new(Notify::new()),
+            listening: AtomicBool::new(false),
+        }
+    }
+
+    /// Unique keys are reclaimed when reused after their window; this
+    /// removes the rest, from the `purge_job_records` task.
+    pub async fn purge_expired_unique_keys(&self) -> Result<u64, sqlx::Error> {
+        let result = DbExecutor::from(&self.pool)
+            .execute(sqlx::query(
+                "DELETE FROM job_unique_keys WHERE expires_at < NOW()",
+            ))
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// A retry reuses the job id: put the row back in the queue and drop the
+    /// lease, so acknowledging the old delivery leaves it alone.
+    fn upsert<'q>(&self, envelope: &'q JobEnvelope) -> Query<'q, Postgres, PgArguments> {
+        let sort_at =
+            envelope.run_at - self.config.priority_aging * envelope.priority.level() as u32;
+
+        sqlx::query(
+            "INSERT INTO jobs \
+                 (id, name, payload, attempts, max_attempts, timeout_ms, backoff, priority, \
+                  history, run_at, sort_at, enqueued_at, workflow) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13) \
+             ON CONFLICT (id) DO UPDATE \
+                 SET payload = EXCLUDED.payload, attempts = EXCLUDED.attempts, \
+                     priority = EXCLUDED.priority, history = EXCLUDED.history, \
+                     run_at = EXCLUDED.run_at, sort_at = EXCLUDED.sort_at, \
+                     locked_by = NULL, lease_id = NULL, heartbeat_at = NULL",
+        )
+        .bind(envelope.id)
+        .bind(&envelope.name)
+        .bind(&envelope.payload)
+        .bind(envelope.attempt as i32)
+        .bind(envelope.max_attempts as i32)
+        .bind(envelope.timeout_ms as i64)
+        .bind(Json(&envelope.backoff))
+        .bind(envelope.priority.level())
+        .bind(Json(&envelope.history))
+        .bind(envelope.run_at)
+        .bind(sort_at)
+        .bind(envelope.enqueued_at)
+        .bind(Json(&envelope.workflow))
+    }
+
+    /// Starts the `LISTEN` task the first time a worker waits, so processes
+    /// that only enqueue do not hold a listener connection.
+    fn ensure_listener(&self) {
+        if self.listening.swap(true, Ordering::AcqRel) {
+            return;
+        }
+
+        let pool = self.pool.clone();
+        let wakeup = Arc::downgrade(&self.wakeup);
+        tokio::spawn(listen(pool, wakeup));
+    }
+}
+
+async fn listen(pool: PgPool, wakeup: Weak<Notify>) {
+    let mut retry_delay = Duration::from_millis(500);
+
+    while wakeup.strong_count() > 0 {
+        let mut listener = match PgListener::connect_with(&pool).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                tracing::warn!("Job queue listener unavailable, polling only: {}", e);
+                tokio::time::sleep(retry_delay).await;
+                retry_delay = (retry_delay * 2).min(Duration::from_secs(30));
+                continue;
+            }
+        };
+        if let Err(e) = listener.listen(JOBS_CHANNEL).await {
+            tracing::warn!("Job queue LISTEN failed, polling only: {}", e);
+            tokio::time::sleep(retry_delay).await;
+            retry_delay = (retry_delay * 2).min(Duration::from_secs(30));
+            continue;
+        }
+        retry_delay = Duration::from_millis(500);
+
+        loop {
+            let notification = listener.recv().await;
+            let Some(wakeup) = wakeup.upgrade() else {
+                return;
+            };
+            match notification {
+                Ok(_) => wakeup.notify_waiters(),
+                Err(e) => {
+                    tracing::warn!("Job queue listener error: {}", e);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+impl QueueBackend for PostgresBackend {
+    async fn push(&self, envelope: &JobEnvelope) -> Result<(), QueueError> {
+        DbExecutor::from(&self.pool)
+            .execute(self.upsert(envelope))
+            .await?;
+        Ok(())
+    }
+
+    async fn push_unique(
+        &self,
+        envelope: &JobEnvelope,
+        key: &str,
+        window: Duration,
+    ) -> Result<Option<Uuid>, QueueError> {
+        loop {
+            let mut tx = self.pool.begin().await?;
+
+            let claimed = DbExecutor::from(&mut tx)
+                .fetch_optional(
+                    sqlx::query_as::<_, (Uuid,)>(
+                        "INSERT INTO job_unique_keys (key, job_id, expires_at) \
+                         VALUES ($1, $2, NOW() + $3 * INTERVAL '1 millisecond') \
+                         ON CONFLICT (key) DO UPDATE \
+                             SET job_id = EXCLUDED.job_id, expires_at = EXCLUDED.expires_at \
+                             WHERE job_unique_keys.expires_at < NOW() \
+                         RETURNING job_id",
+                    )
+                    .bind(key)
+                    .bind(envelope.id)
+                    .bind(window.as_millis() as i64),
+                )
+                .await?;
+            if claimed.is_some() {
+                DbExecutor::from(&mut tx)
+                    .execute(self.upsert(envelope))
+                    .await?;
+                tx.commit().await?;
+                return Ok(None);
+            }
+
+            let existing = DbExecutor::from(&mut tx)
+                .fetch_optional(
+                    sqlx::query_as::<_, (Uuid,)>(
+                        "SELECT job_id FROM job_unique_keys WHERE key = $1",
+                    )
+                    .bind(key),
+                )
+                .await?;
+            // Otherwise the key was purged in between and is free again.
+            if let Some((job_id,)) = existing {
+                return Ok(Some(job_id));
+            }
+        }
+    }
+
+    async fn fetch(&self, consumer: &str, max: usize) -> Result<Vec<Delivery>, QueueError> {
+        let rows = DbExecutor::from(&self.pool)
+            .fetch_all(
+                // Expired leases and ready jobs are picked separately so that
+                // each side walks its own partial index (`jobs_locked_idx`,
+                // `jobs_ready_idx`); an OR across both would scan the table.
+                sqlx::query_as::<_, JobRow>(
+                    "WITH expired AS ( \
+                         SELECT id FROM jobs \
+                         WHERE locked_by IS NOT NULL \
+                           AND heartbeat_at < NOW() - $2 * INTERVAL '1 millisecond' \
+                           AND run_at <= NOW() \
+                         ORDER BY heartbeat_at \
+                         LIMIT $3 \
+                         FOR UPDATE SKIP LOCKED \
+                     ), ready AS ( \
+                         SELECT id FROM jobs \
+                         WHERE locked_by IS NULL AND run_at <= NOW() \
+                         ORDER BY sort_at \
+                         LIMIT $3 \
+                         FOR UPDATE SKIP LOCKED \
+                     ) \
+                     UPDATE jobs \
+                     SET locked_by = $1, lease_id = gen_random_uuid(), heartbeat_at = NOW() \
+                     WHERE id IN ( \
+                         SELECT id FROM expired \
+                         UNION ALL \
+                         SELECT id FROM ready \
+                         LIMIT $3 \
+                     ) \
+                     RETURNING id, name, payload, attempts, max_attempts, timeout_ms, \
+                               backoff, priority, history, run_at, enqueued_at, workflow, \
+                               lease_id",
+                )
+                .bind(consumer)
+                .bind(self.config.lease_timeout.as_millis() as i64)
+                .bind(max as i64),
+            )
+            .await?;
+
+        Ok(rows.into_iter().map(Delivery::from).collect())
+    }
+
+    async fn heartbeat(&self, _consumer: &str, delivery: &Delivery) -> Result<(), QueueError> {
+        // The lease id changes when another worker takes over the job.
+        let updated = DbExecutor::from(&self.pool)
+            .execute(
+                sqlx::query(
+                    "UPDATE jobs SET heartbeat_at = NOW() WHERE id = $1 AND lease_id = $2::UUID",
+                )
+                .bind(delivery.envelope.id)
+                .bind(&delivery.receipt),
+            )
+            .await?;
+        if updated.rows_affected() == 0 {
+            return Err(QueueError::LeaseLost);
+        }
+        Ok(())
+    }
+
+    async fn ack(&self, delivery: &Delivery) -> Result<(), QueueError> {
+        DbExecutor::from(&self.pool)
+            .execute(
+                sqlx::query("DELETE FROM jobs WHERE id = $1 AND lease_id = $2::UUID")
+                    .bind(delivery.envelope.id)
+                    .bind(&delivery.receipt),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn wait(&self, max: Duration) {
+        self.ensure_listener();
+        let _ = tokio::time::timeout(max, self.wakeup.notified()).await;
+    }
+}
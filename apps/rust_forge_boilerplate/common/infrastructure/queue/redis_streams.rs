@@ -0,0 +1,337 @@
+use redis::{RedisError, Script, Value};
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use uuid::Uuid;
+
+use crate::common::infrastructure::{
+    queue::{
+        backend::{Delivery, JobQueueConfig, QueueBackend, QueueError},
+        job::{JobEnvelope, JobPriority},
+    },
+    redis::RedisConnection,
+};
+
+const CONSUMER_GROUP: &str = "workers";
+
+/// Moves up to ARGV[2] due jobs from the priority sets (KEYS[2..], highest
+/// priority first) to the stream (KEYS[1]). The next job is the one with the
+/// earliest `run_at` once each set's is moved ARGV[1] ms earlier per level.
+const PROMOTE_SCRIPT: &str = r#"
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+local aging_ms = tonumber(ARGV[1])
+local count = tonumber(ARGV[2])
+
+local promoted = 0
+while promoted < count do
+    local best_key, best_job, best_score
+    for i = 2, #KEYS do
+        local level = #KEYS - i
+        local head = redis.call('ZRANGEBYSCORE', KEYS[i], '-inf', now, 'WITHSCORES', 'LIMIT', 0, 1)
+        if head[1] then
+            local score = tonumber(head[2]) - level * aging_ms
+            if best_score == nil or score < best_score then
+                best_key, best_job, best_score = KEYS[i], head[1], score
+            end
+        end
+    end
+    if best_key == nil then
+        break
+    end
+
+    redis.call('ZREM', best_key, best_job)
+    redis.call('XADD', KEYS[1], '*', 'job', best_job)
+    promoted = promoted + 1
+end
+return promoted
+"#;
+
+/// Queues ARGV[3] in KEYS[2] at score ARGV[4] unless the unique key KEYS[1]
+/// is held; returns the holder's job id, or nil once queued.
+const PUSH_UNIQUE_SCRIPT: &str = r#"
+local existing = redis.call('GET', KEYS[1])
+if existing then
+    return existing
+end
+
+redis.call('SET', KEYS[1], ARGV[1], 'PX', ARGV[2])
+redis.call('ZADD', KEYS[2], ARGV[4], ARGV[3])
+return false
+"#;
+
+/// Resets the idle time of a pending entry, but only while `ARGV[2]` still
+/// owns it: once the entry was auto-claimed by another consumer, claiming it
+/// back would run the job twice. Returns 1 if the entry is still ours.
+const HEARTBEAT_SCRIPT: &str = r#"
+local pending = redis.call('XPENDING', KEYS[1], ARGV[1], ARGV[3], ARGV[3], 1)
+if pending[1] == nil or pending[1][2] ~= ARGV[2] then
+    return 0
+end
+
+redis.call('XCLAIM', KEYS[1], ARGV[1], ARGV[2], 0, ARGV[3], 'JUSTID')
+return 1
+"#;
+
+/// Jobs on a Redis stream read through a consumer group.
+///
+/// Jobs wait in one sorted set per priority, scored by `run_at`. Each fetch
+/// moves as many due jobs as it asks for to the stream, so the stream stays
+/// short and ordering is decided by priority. A stream entry is delivered to
+/// one consumer and stays in its pending list until acknowledged; entries
+/// whose consumer stopped sending heartbeats for `lease_timeout` are claimed
+/// by the next worker that fetches.
+pub struct RedisStreamsBackend {
+    conn: RedisConnection,
+    namespace: String,
+    stream: String,
+    config: JobQueueConfig,
+    group_ready: AtomicBool,
+}
+
+impl RedisStreamsBackend {
+    pub fn new(conn: RedisConnection, namespace: &str, config: JobQueueConfig) -> Self {
+        // Every key carries the `{jobs}` hash tag, so the scripts work on a
+        // cluster.
+        Self {
+            conn,
+            namespace: namespace.to_string(),
+            stream: format!("{}:{{jobs}}", namespace),
+            config,
+            group_ready: AtomicBool::new(false),
+        }
+    }
+
+    fn priority_key(&self, priority: JobPriority) -> String {
+        format!("{}:{{jobs}}:{}", self.namespace, priority.as_str())
+    }
+
+    fn unique_key(&self, key: &str) -> String {
+        format!("{}:{{jobs}}:unique:{}", self.namespace, key)
+    }
+
+    /// Moves up to `max` due jobs to the stream.
+    async fn promote(&self, conn: &mut RedisConnection, max: usize) -> Result<(), RedisError> {
+        let script = Script::new(PROMOTE_SCRIPT);
+        let mut invocation = script.key(&self.stream);
+        for priority in JobPriority::ALL {
+            invocation.key(self.priority_key(priority));
+        }
+        invocation
+            .arg(self.config.priority_aging.as_millis() as u64)
+            .arg(max)
+            .invoke_async::<_, i64>(conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn ensure_group(&self, conn: &mut RedisConnection) -> Result<(), RedisError> {
+        if self.group_ready.load(Ordering::Acquire) {
+            return Ok(());
+        }
+
+        let created: Result<(), RedisError> = redis::cmd("XGROUP")
+            .arg("CREATE")
+            .arg(&self.stream)
+            .arg(CONSUMER_GROUP)
+            .arg("0")
+            .arg("MKSTREAM")
+            .query_async(conn)
+            .await;
+        match created {
+            Ok(()) => {}
+            Err(e) if e.code() == Some("BUSYGROUP") => {}
+            Err(e) => return Err(e),
+        }
+
+        self.group_ready.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    /// Turns stream entries into deliveries, dropping entries that cannot be
+    /// decoded so they do not come back forever.
+    async fn decode_entries(
+        &self,
+        conn: &mut RedisConnection,
+        entries: &[Value],
+    ) -> Result<Vec<Delivery>, QueueError> {
+        let mut deliveries = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let Some((id, job)) = parse_entry(entry) else {
+                continue;
+            };
+
+            match job.map(|job| serde_json::from_str::<JobEnvelope>(&job)) {
+                Some(Ok(envelope)) => deliveries.push(Delivery {
+                    envelope,
+                    receipt: id,
+                }),
+                Some(Err(e)) => {
+                    tracing::error!("Dropping undecodable job {}: {}", id, e);
+                    self.remove(conn, &id).await?;
+                }
+                None => {
+                    tracing::error!("Dropping job {} without a payload", id);
+                    self.remove(conn, &id).await?;
+                }
+            }
+        }
+        Ok(deliveries)
+    }
+
+    async fn remove(&self, conn: &mut RedisConnection, id: &str) -> Result<(), RedisError> {
+        redis::cmd("XACK")
+            .arg(&self.stream)
+            .arg(CONSUMER_GROUP)
+            .arg(id)
+            .query_async::<_, ()>(conn)
+            .await?;
+        redis::cmd("XDEL")
+            .arg(&self.stream)
+            .arg(id)
+            .query_async::<_, ()>(conn)
+            .await
+    }
+
+    fn reset_group_on_error(&self, err: &RedisError) {
+        // The stream was deleted, e.g. by a FLUSHALL: recreate the group on
+        // the next fetch.
+        if err.code() == Some("NOGROUP") {
+            self.group_ready.store(false, Ordering::Release);
+        }
+    }
+}
+
+/// Reads `[id, [field, value, ...]]` and returns the id and the `job` field.
+fn parse_entry(entry: &Value) -> Option<(String, Option<String>)> {
+    let Value::Bulk(parts) = entry else {
+        return None;
+    };
+    let id: String = redis::from_redis_value(parts.first()?).ok()?;
+
+    let job = match parts.get(1) {
+        Some(Value::Bulk(fields)) => fields.chunks(2).find_map(|pair| {
+            let name: String = redis::from_redis_value(&pair[0]).ok()?;
+            if name != "job" {
+                return None;
+            }
+            redis::from_redis_value(pair.get(1)?).ok()
+        }),
+        _ => None,
+    };
+
+    Some((id, job))
+}
+
+impl QueueBackend for RedisStreamsBackend {
+    async fn push(&self, envelope: &JobEnvelope) -> Result<(), QueueError> {
+        let job = serde_json::to_string(envelope)?;
+        let mut conn = self.conn.clone();
+        redis::cmd("ZADD")
+            .arg(self.priority_key(envelope.priority))
+            .arg(envelope.run_at.timestamp_millis())
+            .arg(job)
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn push_unique(
+        &self,
+        envelope: &JobEnvelope,
+        key: &str,
+        window: Duration,
+    ) -> Result<Option<Uuid>, QueueError> {
+        let job = serde_json::to_string(envelope)?;
+        let mut conn = self.conn.clone();
+        let existing: Option<String> = Script::new(PUSH_UNIQUE_SCRIPT)
+            .key(self.unique_key(key))
+            .key(self.priority_key(envelope.priority))
+            .arg(envelope.id.to_string())
+            .arg(window.as_millis().max(1) as u64)
+            .arg(job)
+            .arg(envelope.run_at.timestamp_millis())
+            .invoke_async(&mut conn)
+            .await?;
+
+        existing
+            .map(|id| Uuid::parse_str(&id).map_err(|e| QueueError::Codec(e.to_string())))
+            .transpose()
+    }
+
+    async fn fetch(&self, consumer: &str, max: usize) -> Result<Vec<Delivery>, QueueError> {
+        let mut conn = self.conn.clone();
+        self.ensure_group(&mut conn).await?;
+
+        // Entries abandoned by crashed workers first.
+        let claimed: Value = redis::cmd("XAUTOCLAIM")
+            .arg(&self.stream)
+            .arg(CONSUMER_GROUP)
+            .arg(consumer)
+            .arg(self.config.lease_timeout.as_millis() as u64)
+            .arg("0-0")
+            .arg("COUNT")
+            .arg(max)
+            .query_async(&mut conn)
+            .await
+            .inspect_err(|e| self.reset_group_on_error(e))?;
+        if let Value::Bulk(parts) = &claimed
+            && let Some(Value::Bulk(entries)) = parts.get(1)
+            && !entries.is_empty()
+        {
+            return self.decode_entries(&mut conn, entries).await;
+        }
+
+        self.promote(&mut conn, max).await?;
+
+        let read: Value = redis::cmd("XREADGROUP")
+            .arg("GROUP")
+            .arg(CONSUMER_GROUP)
+            .arg(consumer)
+            .arg("COUNT")
+            .arg(max)
+            .arg("STREAMS")
+            .arg(&self.stream)
+            .arg(">")
+            .query_async(&mut conn)
+            .await
+            .inspect_err(|e| self.reset_group_on_error(e))?;
+
+        // [[stream, [entry, ...]]], or nil when there is nothing new.
+        let Value::Bulk(streams) = read else {
+            return Ok(Vec::new());
+        };
+        let mut deliveries = Vec::new();
+        for stream in &streams {
+            if let Value::Bulk(parts) = stream
+                && let Some(Value::Bulk(entries)) = parts.get(1)
+            {
+                deliveries.extend(self.decode_entries(&mut conn, entries).await?);
+            }
+        }
+        Ok(deliveries)
+    }
+
+    async fn heartbeat(&self, consumer: &str, delivery: &Delivery) -> Result<(), QueueError> {
+        let mut conn = self.conn.clone();
+        let owned: bool = Script::new(HEARTBEAT_SCRIPT)
+            .key(&self.stream)
+            .arg(CONSUMER_GROUP)
+            .arg(consumer)
+            .arg(&delivery.receipt)
+            .invoke_async(&mut conn)
+            .await?;
+        if !owned {
+            return Err(QueueError::LeaseLost);
+        }
+        Ok(())
+    }
+
+    async fn ack(&self, delivery: &Delivery) -> Result<(), QueueError> {
+        let mut conn = self.conn.clone();
+        self.remove(&mut conn, &delivery.receipt).await?;
+        Ok(())
+    }
+}
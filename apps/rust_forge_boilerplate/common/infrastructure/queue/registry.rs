@@ -0,0 +1,67 @@
+use futures::future::BoxFuture;
+use std::collections::HashMap;
+
+use crate::common::infrastructure::queue::job::{Job, JobContext, JobData, JobError};
+
+/// What a successful run produced, see `JobContext::set_result`.
+pub(crate) type JobOutput = Option<serde_json::Value>;
+
+type JobHandler = Box<
+    dyn Fn(serde_json::Value, JobContext) -> BoxFuture<'static, Result<JobOutput, JobError>>
+        + Send
+        + Sync,
+>;
+
+/// Maps job names to handlers, and holds the shared values handlers reach
+/// through `JobContext::data`.
+#[derive(Default)]
+pub struct JobRegistry {
+    handlers: HashMap<&'static str, JobHandler>,
+    data: JobData,
+}
+
+impl JobRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<J: Job>(mut self) -> Self {
+        let handler: JobHandler = Box::new(|payload, ctx| {
+            Box::pin(async move {
+                let job: J = serde_json::from_value(payload).map_err(|e| {
+                    JobError::fatal(format!("invalid payload for job {}: {}", J::NAME, e))
+                })?;
+                job.run(&ctx).await?;
+                Ok(ctx.take_result())
+            })
+        });
+
+        if self.handlers.insert(J::NAME, handler).is_some() {
+            panic!("Job {} is registered twice", J::NAME);
+        }
+        self
+    }
+
+    pub fn with_data<T: Send + Sync + 'static>(mut self, value: T) -> Self {
+        self.data.insert(value);
+        self
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.handlers.contains_key(name)
+    }
+
+    pub(crate) fn data<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.data.get()
+    }
+
+    /// Starts the handler for `name`, or returns `None` if none is registered.
+    pub(crate) fn run(
+        &self,
+        name: &str,
+        payload: serde_json::Value,
+        ctx: JobContext,
+    ) -> Option<BoxFuture<'static, Result<JobOutput, JobError>>> {
+        self.handlers.get(name).map(|handler| handler(payload, ctx))
+    }
+}
@@ -0,0 +1,314 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{PgPool, postgres::PgListener};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+use tokio::sync::broadcast::{self, error::RecvError};
+use uuid::Uuid;
+
+use crate::common::infrastructure::{
+    database::DbExecutor,
+    queue::{backend::QueueError, job::JobEnvelope},
+};
+
+pub const JOB_STATUS_CHANNEL: &str = "job_status";
+
+/// Status changes a subscriber may fall behind by before it misses some.
+const FEED_CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+pub enum JobState {
+    Queued,
+    Running,
+    Succeeded,
+    /// Failed with a non-retryable error and moved to the dead letters.
+    Failed,
+    /// Failed and waiting for its next attempt.
+    Retrying,
+    /// Used up its attempts and moved to the dead letters.
+    Dead,
+}
+
+impl JobState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobState::Queued => "queued",
+            JobState::Running => "running",
+            JobState::Succeeded => "succeeded",
+            JobState::Failed => "failed",
+            JobState::Retrying => "retrying",
+            JobState::Dead => "dead",
+        }
+    }
+
+    /// No further changes follow, unless a dead letter is replayed.
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            JobState::Succeeded | JobState::Failed | JobState::Dead
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct JobStatus {
+    pub id: Uuid,
+    pub name: String,
+    pub status: JobState,
+    /// Runs started so far.
+    pub attempts: i32,
+    pub max_attempts: i32,
+    pub last_error: Option<String>,
+    /// Percentage reported by the handler; 100 once the job succeeded.
+    pub progress: Option<i16>,
+    pub progress_message: Option<String>,
+    pub batch_id: Option<Uuid>,
+    pub run_at: DateTime<Utc>,
+    pub enqueued_at: DateTime<Utc>,
+    /// Start of the latest run.
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// The status of every job, kept in Postgres whatever the queue backend.
+///
+/// `JobQueue` records a job as queued or retrying before each push and the
+/// worker records the rest. Every write fires `NOTIFY job_status` with the
+/// job id, which `JobStatusFeed` turns into a stream of changes.
+#[derive(Clone)]
+pub struct JobStatusStore {
+    pool: PgPool,
+}
+
+impl JobStatusStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Records `status` for the job as described by `envelope`. Progress is
+    /// reset whenever a new run is queued or started.
+    pub(crate) async fn record(
+        &self,
+        envelope: &JobEnvelope,
+        status: JobState,
+        error: Option<&str>,
+    ) -> Result<(), QueueError> {
+        let now = Utc::now();
+        let attempts = match status {
+            JobState::Queued | JobState::Retrying => envelope.attempt,
+            _ => envelope.attempt + 1,
+        };
+
+        DbExecutor::from(&self.pool)
+            .execute(
+                sqlx::query(
+                    "INSERT INTO job_statuses \
+                         (id, name, status, attempts, max_attempts, last_error, progress, \
+                          batch_id, run_at, enqueued_at, started_at, finished_at) \
+                     VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12) \
+                     ON CONFLICT (id) DO UPDATE \
+                         SET status = EXCLUDED.status, attempts = EXCLUDED.attempts, \
+                             last_error = COALESCE(EXCLUDED.last_error, job_statuses.last_error), \
+                             progress = CASE WHEN EXCLUDED.status IN ('failed', 'dead') \
+                                 THEN job_statuses.progress ELSE EXCLUDED.progress END, \
+                             progress_message = CASE \
+                                 WHEN EXCLUDED.status IN ('succeeded', 'failed', 'dead') \
+                                 THEN job_statuses.progress_message END, \
+                             run_at = EXCLUDED.run_at, \
+                             started_at = COALESCE(EXCLUDED.started_at, job_statuses.started_at), \
+                             finished_at = EXCLUDED.finished_at, updated_at = NOW()",
+                )
+                .bind(envelope.id)
+                .bind(&envelope.name)
+                .bind(status)
+                .bind(attempts as i32)
+                .bind(envelope.max_attempts as i32)
+                .bind(error)
+                .bind((status == JobState::Succeeded).then_some(100i16))
+                .bind(
+                    envelope
+                        .workflow
+                        .batch
+                        .as_ref()
+                        .map(|membership| membership.batch_id),
+                )
+                .bind(envelope.run_at)
+                .bind(envelope.enqueued_at)
+                .bind((status == JobState::Running).then_some(now))
+                .bind(status.is_finished().then_some(now)),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Drops the status of a job that never made it into the queue.
+    pub(crate) async fn remove(&self, id: Uuid) -> Result<(), QueueError> {
+        DbExecutor::from(&self.pool)
+            .execute(sqlx::query("DELETE FROM job_statuses WHERE id = $1").bind(id))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get(&self, id: Uuid) -> Result<Option<JobStatus>, QueueError> {
+        let status = DbExecutor::from(&self.pool)
+            .fetch_optional(
+                sqlx::query_as::<_, JobStatus>("SELECT * FROM job_statuses WHERE id = $1").bind(id),
+            )
+            .await?;
+        Ok(status)
+    }
+
+    /// Updates the progress of a running job. Returns `false` if the job is
+    /// not running.
+    pub async fn set_progress(
+        &self,
+        id: Uuid,
+        percent: Option<u8>,
+        message: Option<&str>,
+    ) -> Result<bool, QueueError> {
+        let result = DbExecutor::from(&self.pool)
+            .execute(
+                sqlx::query(
+                    "UPDATE job_statuses \
+                     SET progress = $2, progress_message = $3, updated_at = NOW() \
+                     WHERE id = $1 AND status = 'running'",
+                )
+                .bind(id)
+                .bind(percent.map(|percent| percent.min(100) as i16))
+                .bind(message),
+            )
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Removes the statuses of jobs that finished before `before`.
+    pub async fn purge_finished(&self, before: DateTime<Utc>) -> Result<u64, QueueError> {
+        let result = DbExecutor::from(&self.pool)
+            .execute(sqlx::query("DELETE FROM job_statuses WHERE finished_at < $1").bind(before))
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
+
+/// Number of open watches per job id.
+type Watched = Arc<Mutex<HashMap<Uuid, usize>>>;
+
+/// Status changes of the jobs someone watches, whichever process wrote them.
+///
+/// One `LISTEN job_status` connection per process feeds a broadcast channel.
+/// Notifications only carry the job id and the status is read back, so long
+/// errors never hit the `NOTIFY` payload limit; only the statuses of watched
+/// jobs are read.
+#[derive(Clone)]
+pub struct JobStatusFeed {
+    sender: broadcast::Sender<JobStatus>,
+    watched: Watched,
+}
+
+impl JobStatusFeed {
+    /// Starts listening in the background.
+    pub fn start(statuses: JobStatusStore) -> Self {
+        let (sender, _) = broadcast::channel(FEED_CAPACITY);
+        let watched = Watched::default();
+        tokio::spawn(listen(statuses, sender.clone(), watched.clone()));
+        Self { sender, watched }
+    }
+
+    /// Changes of job `id`, until the watch is dropped.
+    pub fn watch(&self, id: Uuid) -> JobStatusWatch {
+        *lock(&self.watched).entry(id).or_default() += 1;
+        JobStatusWatch {
+            id,
+            receiver: self.sender.subscribe(),
+            watched: self.watched.clone(),
+        }
+    }
+}
+
+fn lock(watched: &Watched) -> MutexGuard<'_, HashMap<Uuid, usize>> {
+    watched.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Keeps its job's notifications being read while it is alive.
+pub struct JobStatusWatch {
+    id: Uuid,
+    receiver: broadcast::Receiver<JobStatus>,
+    watched: Watched,
+}
+
+impl JobStatusWatch {
+    /// The next status of the watched job. `RecvError::Lagged` means some
+    /// changes were missed.
+    pub async fn recv(&mut self) -> Result<JobStatus, RecvError> {
+        loop {
+            let status = self.receiver.recv().await?;
+            if status.id == self.id {
+                return Ok(status);
+            }
+        }
+    }
+}
+
+impl Drop for JobStatusWatch {
+    fn drop(&mut self) {
+        let mut watched = lock(&self.watched);
+        if let Some(count) = watched.get_mut(&self.id) {
+            *count -= 1;
+            if *count == 0 {
+                watched.remove(&self.id);
+            }
+        }
+    }
+}
+
+async fn listen(statuses: JobStatusStore, sender: broadcast::Sender<JobStatus>, watched: Watched) {
+    let mut retry_delay = Duration::from_millis(500);
+
+    loop {
+        let mut listener = match PgListener::connect_with(&statuses.pool).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                tracing::warn!("Job status listener unavailable: {}", e);
+                tokio::time::sleep(retry_delay).await;
+                retry_delay = (retry_delay * 2).min(Duration::from_secs(30));
+                continue;
+            }
+        };
+        if let Err(e) = listener.listen(JOB_STATUS_CHANNEL).await {
+            tracing::warn!("Job status LISTEN failed: {}", e);
+            tokio::time::sleep(retry_delay).await;
+            retry_delay = (retry_delay * 2).min(Duration::from_secs(30));
+            continue;
+        }
+        retry_delay = Duration::from_millis(500);
+
+        loop {
+            let notification = match listener.recv().await {
+                Ok(notification) => notification,
+                Err(e) => {
+                    tracing::warn!("Job status listener error: {}", e);
+                    break;
+                }
+            };
+            let Ok(id) = Uuid::parse_str(notification.payload()) else {
+                continue;
+            };
+            if !lock(&watched).contains_key(&id) {
+                continue;
+            }
+            match statuses.get(id).await {
+                Ok(Some(status)) => {
+                    let _ = sender.send(status);
+                }
+                Ok(None) => {}
+                Err(e) => tracing::warn!("Failed to read status of job {}: {}", id, e),
+            }
+        }
+    }
+}
@@ -0,0 +1,357 @@
+use chrono::Utc;
+use std::{
+    env,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{sync::watch, task::JoinSet};
+use uuid::Uuid;
+
+use crate::common::infrastructure::queue::{
+    backend::{Delivery, JobQueue, JobQueueConfig, QueueBackend, QueueError},
+    dead_letter::DeadLetterStore,
+    job::{JobAttempt, JobContext, JobEnvelope, JobError},
+    registry::{JobOutput, JobRegistry},
+    status::JobState,
+    workflow::BatchStore,
+};
+
+#[derive(Debug, Clone)]
+pub struct WorkerConfig {
+    /// Name this worker claims jobs under; defaults to `HOSTNAME`, which is
+    /// the pod name on Kubernetes.
+    pub consumer: String,
+    /// Jobs run at the same time.
+    pub concurrency: usize,
+    /// Longest pause between fetches while the queue is empty; backends
+    /// with notifications wake up sooner.
+    pub poll_interval: Duration,
+    pub heartbeat_interval: Duration,
+}
+
+impl WorkerConfig {
+    pub fn from_env() -> Self {
+        let concurrency: usize = env::var("JOB_WORKER_CONCURRENCY")
+            .unwrap_or_else(|_| "4".to_string())
+            .parse()
+            .expect("Invalid JOB_WORKER_CONCURRENCY");
+        let poll_interval_ms: u64 = env::var("JOB_WORKER_POLL_INTERVAL_MS")
+            .unwrap_or_else(|_| "500".to_string())
+            .parse()
+            .expect("Invalid JOB_WORKER_POLL_INTERVAL_MS");
+
+        Self {
+            consumer: env::var("JOB_WORKER_ID")
+                .or_else(|_| env::var("HOSTNAME"))
+                .unwrap_or_else(|_| format!("worker-{}", Uuid::new_v4())),
+            concurrency: concurrency.max(1),
+            poll_interval: Duration::from_millis(poll_interval_ms),
+            heartbeat_interval: JobQueueConfig::from_env().lease_timeout / 3,
+        }
+    }
+}
+
+/// Runs jobs from the queue on `concurrency` executors.
+///
+/// Each job runs in its own task, so a panic fails the job rather than the
+/// worker, and is cancelled when it exceeds its timeout. A failed run is
+/// retried after the job's backoff until `max_attempts` is used up, then the
+/// job moves to the dead letters. A successful run queues the next job of its
+/// chain, and the last job of a batch member reports to the batch. Delivery
+/// is at-least-once: a job whose worker dies mid-run is run again elsewhere,
+/// so handlers should be idempotent.
+pub struct JobWorker {
+    queue: Arc<JobQueue>,
+    registry: Arc<JobRegistry>,
+    dead_letters: DeadLetterStore,
+    batches: BatchStore,
+    config: WorkerConfig,
+}
+
+impl JobWorker {
+    pub fn new(
+        queue: Arc<JobQueue>,
+        registry: JobRegistry,
+        dead_letters: DeadLetterStore,
+        batches: BatchStore,
+        config: WorkerConfig,
+    ) -> Self {
+        Self {
+            queue,
+            registry: Arc::new(registry),
+            dead_letters,
+            batches,
+            config,
+        }
+    }
+
+    /// Runs until Ctrl-C, then lets running jobs finish.
+    pub async fn run(self) {
+        let worker = Arc::new(self);
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
+        let mut executors = JoinSet::new();
+        for _ in 0..worker.config.concurrency {
+            executors.spawn(worker.clone().execute(shutdown_rx.clone()));
+        }
+        tracing::info!(
+            "Job worker {} started with {} executors",
+            worker.config.consumer,
+            worker.config.concurrency
+        );
+
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("Failed to listen for shutdown signal: {}", e);
+        }
+        tracing::info!("Job worker shutting down, waiting for running jobs");
+        let _ = shutdown_tx.send(true);
+        while executors.join_next().await.is_some() {}
+    }
+
+    async fn execute(self: Arc<Self>, mut shutdown: watch::Receiver<bool>) {
+        while !*shutdown.borrow() {
+            let deliveries = match self.queue.fetch(&self.config.consumer, 1).await {
+                Ok(deliveries) => deliveries,
+                Err(e) => {
+                    tracing::warn!("Failed to fetch jobs: {}", e);
+                    Vec::new()
+                }
+            };
+
+            if deliveries.is_empty() {
+                tokio::select! {
+                    _ = shutdown.changed() => {}
+                    _ = self.queue.wait(self.config.poll_interval) => {}
+                }
+                continue;
+            }
+
+            for delivery in deliveries {
+                self.process(delivery).await;
+            }
+        }
+    }
+
+    async fn process(&self, delivery: Delivery) {
+        let envelope = &delivery.envelope;
+        // Queued by another version, or pushed without `JobEnvelope::new`.
+        if let Err(e) = envelope.validate() {
+            tracing::error!(
+                "Job {} ({}) is invalid, dead-lettering it: {}",
+                envelope.name,
+                envelope.id,
+                e
+            );
+            let error = JobError::fatal(format!("invalid job: {}", e));
+            if self.fail(envelope, &error).await {
+                self.ack(&delivery).await;
+            }
+            return;
+        }
+        let ctx = JobContext::new(
+            envelope,
+            self.registry.clone(),
+            self.queue.statuses().clone(),
+        );
+        let Some(run) = self
+            .registry
+            .run(&envelope.name, envelope.payload.clone(), ctx)
+        else {
+            tracing::error!(
+                "No handler registered for job {} ({}), dead-lettering it",
+                envelope.name,
+                envelope.id
+            );
+            let error = JobError::fatal(format!("no handler registered for {}", envelope.name));
+            if self.fail(envelope, &error).await {
+                self.ack(&delivery).await;
+            }
+            return;
+        };
+
+        self.record(envelope, JobState::Running, None).await;
+        let started_at = Utc::now();
+        let started = Instant::now();
+        let timeout = envelope.timeout();
+        let mut task = tokio::spawn(tokio::time::timeout(timeout, run));
+        let mut heartbeat = tokio::time::interval(self.config.heartbeat_interval);
+        heartbeat.tick().await;
+
+        let joined = loop {
+            tokio::select! {
+                joined = &mut task => break joined,
+                _ = heartbeat.tick() => {
+                    match self.queue.heartbeat(&self.config.consumer, &delivery).await {
+                        Ok(()) => {}
+                        Err(QueueError::LeaseLost) => {
+                            // Another worker owns the job now: leave the
+                            // result, the ack and any retry to it.
+                            tracing::warn!(
+                                "Lost the lease on job {} ({}), abandoning it",
+                                envelope.name,
+                                envelope.id
+                            );
+                            task.abort();
+                            return;
+                        }
+                        Err(e) => {
+                            tracing::warn!("Heartbeat for job {} failed: {}", envelope.id, e);
+                        }
+                    }
+                }
+            }
+        };
+        let result = match joined {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err(JobError::new(format!("timed out after {:?}", timeout))),
+            Err(e) if e.is_panic() => Err(JobError::new(format!(
+                "panicked: {}",
+                panic_message(e.into_panic())
+            ))),
+            Err(e) => Err(JobError::new(e.to_string())),
+        };
+
+        let outcome = match &result {
+            Ok(_) => "succeeded",
+            Err(e) if e.retryable && envelope.can_retry() => "retrying",
+            Err(_) => "dead",
+        };
+        metrics::histogram!("job_duration_seconds", "job" => envelope.name.clone(), "outcome" => outcome)
+            .record(started.elapsed().as_secs_f64());
+
+        let error = match result {
+            Ok(output) => {
+                tracing::info!(
+                    "Job {} ({}) succeeded in {:?}",
+                    envelope.name,
+                    envelope.id,
+                    started.elapsed()
+                );
+                if self.advance(envelope, output).await {
+                    self.record(envelope, JobState::Succeeded, None).await;
+                    self.ack(&delivery).await;
+                }
+                return;
+            }
+            Err(error) => error,
+        };
+
+        let failed = JobAttempt {
+            attempt: envelope.attempt + 1,
+            started_at,
+            finished_at: Utc::now(),
+            error: error.message.clone(),
+        };
+        if error.retryable && envelope.can_retry() {
+            let retry = envelope.retry(failed);
+            tracing::warn!(
+                "Job {} ({}) failed on attempt {}/{}, retrying at {}: {}",
+                envelope.name,
+                envelope.id,
+                envelope.attempt + 1,
+                envelope.max_attempts,
+                retry.run_at,
+                error
+            );
+            if let Err(e) = self.queue.push(&retry).await {
+                // Leave the delivery unacknowledged: it is redelivered
+                // once its lease runs out.
+                tracing::error!("Failed to requeue job {}: {}", envelope.id, e);
+                return;
+            }
+        } else {
+            tracing::error!(
+                "Job {} ({}) failed on attempt {}/{}, dead-lettering it: {}",
+                envelope.name,
+                envelope.id,
+                envelope.attempt + 1,
+                envelope.max_attempts,
+                error
+            );
+            let mut dead = envelope.clone();
+            dead.history.push(failed);
+            if !self.fail(&dead, &error).await {
+                return;
+            }
+        }
+
+        self.ack(&delivery).await;
+    }
+
+    /// Queues the next job of the chain, or reports the finished member to
+    /// its batch. Returns `false` if that failed, in which case the delivery
+    /// must stay unacknowledged.
+    async fn advance(&self, envelope: &JobEnvelope, output: JobOutput) -> bool {
+        if let Some(next) = envelope.next_in_chain(output.clone()) {
+            if let Err(e) = self.queue.push(&next).await {
+                tracing::error!("Failed to queue the job after {}: {}", envelope.id, e);
+                return false;
+            }
+        } else if let Some(membership) = &envelope.workflow.batch
+            && let Err(e) = self
+                .batches
+                .finish_member(&self.queue, membership, Ok(output))
+                .await
+        {
+            tracing::error!("Failed to report job {} to its batch: {}", envelope.id, e);
+            return false;
+        }
+        true
+    }
+
+    /// Dead-letters the job and reports it to its batch as failed. Returns
+    /// `false` if that failed, in which case the delivery must stay
+    /// unacknowledged.
+    async fn fail(&self, envelope: &JobEnvelope, error: &JobError) -> bool {
+        if let Err(e) = self.dead_letters.insert(envelope, error).await {
+            tracing::error!("Failed to dead-letter job {}: {}", envelope.id, e);
+            return false;
+        }
+        if let Some(membership) = &envelope.workflow.batch
+            && let Err(e) = self
+                .batches
+                .finish_member(&self.queue, membership, Err(error.message.clone()))
+                .await
+        {
+            tracing::error!("Failed to report job {} to its batch: {}", envelope.id, e);
+            return false;
+        }
+
+        let status = if error.retryable {
+            JobState::Dead
+        } else {
+            JobState::Failed
+        };
+        self.record(envelope, status, Some(&error.message)).await;
+        true
+    }
+
+    /// Statuses are informational: failing to record one does not hold up
+    /// the job.
+    async fn record(&self, envelope: &JobEnvelope, status: JobState, error: Option<&str>) {
+        if let Err(e) = self.queue.statuses().record(envelope, status, error).await {
+            tracing::warn!(
+                "Failed to record job {} as {}: {}",
+                envelope.id,
+                status.as_str(),
+                e
+            );
+        }
+    }
+
+    async fn ack(&self, delivery: &Delivery) {
+        if let Err(e) = self.queue.ack(delivery).await {
+            tracing::warn!("Failed to acknowledge job {}: {}", delivery.envelope.id, e);
+        }
+    }
+}
+
+pub(crate) fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
@@ -0,0 +1,375 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, types::Json};
+use uuid::Uuid;
+
+use crate::common::infrastructure::{
+    database::DbExecutor,
+    queue::{
+        backend::{JobQueue, QueueBackend, QueueError},
+        job::{Job, JobEnvelope},
+    },
+};
+
+/// Where a job sits in a chain or batch.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JobWorkflow {
+    /// Jobs still to run, in order, once this one succeeds.
+    pub chain: Vec<JobEnvelope>,
+    /// The previous job's result, or the callback's `BatchOutcome`.
+    pub input: Option<serde_json::Value>,
+    pub batch: Option<BatchMembership>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchMembership {
+    pub batch_id: Uuid,
+    /// The first job of the member; later jobs in its chain report under
+    /// the same id.
+    pub member_id: Uuid,
+}
+
+/// Jobs that run one after the other, each once the previous one succeeded,
+/// and each receiving the previous one's result as its input. A job that
+/// ends up dead-lettered stops the chain.
+pub struct JobChain {
+    jobs: Result<Vec<JobEnvelope>, QueueError>,
+}
+
+impl JobChain {
+    pub fn start<J: Job>(job: &J) -> Self {
+        Self {
+            jobs: JobEnvelope::new(job).map(|envelope| vec![envelope]),
+        }
+    }
+
+    pub fn then<J: Job>(mut self, job: &J) -> Self {
+        self.jobs = self.jobs.and_then(|mut jobs| {
+            jobs.push(JobEnvelope::new(job)?);
+            Ok(jobs)
+        });
+        self
+    }
+
+    /// The first job, carrying the rest of the chain.
+    pub(crate) fn into_envelope(self) -> Result<JobEnvelope, QueueError> {
+        let mut jobs = self.jobs?;
+        let mut first = jobs.remove(0);
+        first.workflow.chain = jobs;
+        Ok(first)
+    }
+}
+
+/// Jobs that run in parallel, followed by an optional callback once every
+/// member has either succeeded or been dead-lettered.
+#[derive(Default)]
+pub struct JobBatch {
+    description: Option<String>,
+    members: Vec<JobChain>,
+    callback: Option<JobChain>,
+}
+
+impl JobBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Shown with the batch's progress, e.g. `"import 42"`.
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn job<J: Job>(self, job: &J) -> Self {
+        self.chain(JobChain::start(job))
+    }
+
+    /// Adds a chain as one member: it counts as finished when its last job
+    /// succeeds or any of its jobs is dead-lettered.
+    pub fn chain(mut self, chain: JobChain) -> Self {
+        self.members.push(chain);
+        self
+    }
+
+    /// Runs `callback` with the batch's `BatchOutcome` as its input.
+    pub fn on_complete(mut self, callback: JobChain) -> Self {
+        self.callback = Some(callback);
+        self
+    }
+}
+
+/// Input of a batch's completion callback.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchOutcome {
+    pub batch_id: Uuid,
+    pub total: i32,
+    pub succeeded: i32,
+    pub failed: i32,
+    /// Member results in the order they were added; `None` for failed
+    /// members and those that set no result.
+    pub results: Vec<Option<serde_json::Value>>,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct BatchProgress {
+    pub id: Uuid,
+    pub description: Option<String>,
+    pub total: i32,
+    pub succeeded: i32,
+    pub failed: i32,
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+impl BatchProgress {
+    pub fn pending(&self) -> i32 {
+        self.total - self.succeeded - self.failed
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished_at.is_some()
+    }
+}
+
+/// Batch bookkeeping, kept in Postgres whatever the queue backend.
+///
+/// Each member is counted once, however often it is delivered. The callback
+/// runs as job `batch_id` and is queued once the transaction that counts the
+/// last member has committed; until then every delivery of a member tries
+/// again, and a crash right after queueing it may queue it a second time
+/// under the same id.
+#[derive(Clone)]
+pub struct BatchStore {
+    pool: PgPool,
+}
+
+impl BatchStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Records the batch and queues its members. Returns the batch id.
+    pub async fn enqueue(&self, queue: &JobQueue, batch: JobBatch) -> Result<Uuid, QueueError> {
+        let batch_id = Uuid::new_v4();
+        let callback = batch
+            .callback
+            .map(JobChain::into_envelope)
+            .transpose()?
+            .map(|mut callback| {
+                callback.id = batch_id;
+                callback
+            });
+        let mut members = batch
+            .members
+            .into_iter()
+            .map(JobChain::into_envelope)
+            .collect::<Result<Vec<_>, _>>()?;
+        for member in &mut members {
+            member.workflow.batch = Some(BatchMembership {
+                batch_id,
+                member_id: member.id,
+            });
+        }
+
+        let mut tx = self.pool.begin().await?;
+        DbExecutor::from(&mut tx)
+            .execute(
+                sqlx::query(
+                    "INSERT INTO job_batches (id, description, total, callback) \
+                     VALUES ($1, $2, $3, $4)",
+                )
+                .bind(batch_id)
+                .bind(&batch.description)
+                .bind(members.len() as i32)
+                .bind(callback.as_ref().map(Json)),
+            )
+            .await?;
+        DbExecutor::from(&mut tx)
+            .execute(
+                sqlx::query(
+                    "INSERT INTO job_batch_members (batch_id, job_id, position) \
+                     SELECT $1, job_id, position - 1 \
+                     FROM UNNEST($2::UUID[]) WITH ORDINALITY AS members (job_id, position)",
+                )
+                .bind(batch_id)
+                .bind(members.iter().map(|member| member.id).collect::<Vec<_>>()),
+            )
+            .await?;
+        if members.is_empty() {
+            self.complete(&mut tx, batch_id).await?;
+        }
+        tx.commit().await?;
+        if members.is_empty() {
+            self.queue_callback(queue, batch_id).await?;
+        }
+
+        for (index, member) in members.iter().enumerate() {
+            if let Err(e) = queue.push(member).await {
+                // Count the members that never made it into the queue as
+                // failed, so the batch can still complete.
+                for unqueued in &members[index..] {
+                    let membership = unqueued.workflow.batch.as_ref().expect("batch member");
+                    let failed = Err(format!("failed to enqueue: {}", e));
+                    if let Err(e) = self.finish_member(queue, membership, failed).await {
+                        tracing::error!("Failed to record batch member {}: {}", unqueued.id, e);
+                    }
+                }
+                return Err(e);
+            }
+        }
+
+        Ok(batch_id)
+    }
+
+    pub async fn progress(&self, batch_id: Uuid) -> Result<Option<BatchProgress>, QueueError> {
+        let progress = DbExecutor::from(&self.pool)
+            .fetch_optional(
+                sqlx::query_as::<_, BatchProgress>(
+                    "SELECT id, description, total, succeeded, failed, created_at, finished_at \
+                     FROM job_batches WHERE id = $1",
+                )
+                .bind(batch_id),
+            )
+            .await?;
+        Ok(progress)
+    }
+
+    /// Counts a member as succeeded with its result, or as failed with an
+    /// error, and queues the callback if it was the last one.
+    pub(crate) async fn finish_member(
+        &self,
+        queue: &JobQueue,
+        membership: &BatchMembership,
+        outcome: Result<Option<serde_json::Value>, String>,
+    ) -> Result<(), QueueError> {
+        let (status, result, error) = match outcome {
+            Ok(result) => ("succeeded", result, None),
+            Err(error) => ("failed", None, Some(error)),
+        };
+
+        let mut tx = self.pool.begin().await?;
+        let counted = DbExecutor::from(&mut tx)
+            .execute(
+                sqlx::query(
+                    "UPDATE job_batch_members \
+                     SET status = $3, result = $4, error = $5, finished_at = NOW() \
+                     WHERE batch_id = $1 AND job_id = $2 AND status = 'pending'",
+                )
+                .bind(membership.batch_id)
+                .bind(membership.member_id)
+                .bind(status)
+                .bind(result)
+                .bind(error),
+            )
+            .await?;
+        // Already counted by an earlier delivery, which may have failed to
+        // queue the callback.
+        if counted.rows_affected() == 0 {
+            drop(tx);
+            return self.queue_callback(queue, membership.batch_id).await;
+        }
+
+        let (total, succeeded, failed) = DbExecutor::from(&mut tx)
+            .fetch_one(
+                sqlx::query_as::<_, (i32, i32, i32)>(
+                    "UPDATE job_batches \
+                     SET succeeded = succeeded + $2, failed = failed + $3 \
+                     WHERE id = $1 \
+                     RETURNING total, succeeded, failed",
+                )
+                .bind(membership.batch_id)
+                .bind((status == "succeeded") as i32)
+                .bind((status == "failed") as i32),
+            )
+            .await?;
+        let finished = succeeded + failed == total;
+        if finished {
+            self.complete(&mut tx, membership.batch_id).await?;
+        }
+        tx.commit().await?;
+
+        if finished {
+            self.queue_callback(queue, membership.batch_id).await?;
+        }
+        Ok(())
+    }
+
+    async fn complete(
+        &self,
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        batch_id: Uuid,
+    ) -> Result<(), QueueError> {
+        let (succeeded, failed) = DbExecutor::from(&mut *tx)
+            .fetch_one(
+                sqlx::query_as::<_, (i32, i32)>(
+                    "UPDATE job_batches SET finished_at = NOW() WHERE id = $1 \
+                     RETURNING succeeded, failed",
+                )
+                .bind(batch_id),
+            )
+            .await?;
+        tracing::info!(
+            "Batch {} finished: {} succeeded, {} failed",
+            batch_id,
+            succeeded,
+            failed
+        );
+        Ok(())
+    }
+
+    /// Queues the callback of a finished batch, unless it has none or it was
+    /// queued already. The batch row stays locked until the callback is
+    /// cleared, so concurrent deliveries queue it once.
+    async fn queue_callback(&self, queue: &JobQueue, batch_id: Uuid) -> Result<(), QueueError> {
+        let mut tx = self.pool.begin().await?;
+        let pending = DbExecutor::from(&mut tx)
+            .fetch_optional(
+                sqlx::query_as::<_, (i32, i32, i32, Json<JobEnvelope>)>(
+                    "SELECT total, succeeded, failed, callback FROM job_batches \
+                     WHERE id = $1 AND finished_at IS NOT NULL AND callback IS NOT NULL \
+                     FOR UPDATE",
+                )
+                .bind(batch_id),
+            )
+            .await?;
+        let Some((total, succeeded, failed, Json(mut callback))) = pending else {
+            return Ok(());
+        };
+
+        let results = DbExecutor::from(&mut tx)
+            .fetch_all(
+                sqlx::query_as::<_, (Option<serde_json::Value>,)>(
+                    "SELECT result FROM job_batch_members WHERE batch_id = $1 ORDER BY position",
+                )
+                .bind(batch_id),
+            )
+            .await?;
+        let outcome = BatchOutcome {
+            batch_id,
+            total,
+            succeeded,
+            failed,
+            results: results.into_iter().map(|(result,)| result).collect(),
+        };
+
+        callback.workflow.input = Some(serde_json::to_value(outcome)?);
+        callback.run_at = Utc::now();
+        queue.push(&callback).await?;
+
+        DbExecutor::from(&mut tx)
+            .execute(
+                sqlx::query("UPDATE job_batches SET callback = NULL WHERE id = $1").bind(batch_id),
+            )
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Removes batches that finished before `before`, with their members.
+    pub async fn purge_finished(&self, before: DateTime<Utc>) -> Result<u64, QueueError> {
+        let result = DbExecutor::from(&self.pool)
+            .execute(sqlx::query("DELETE FROM job_batches WHERE finished_at < $1").bind(before))
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
@@ -1,17 +1,1084 @@
-use redis::{aio::ConnectionManager, Client};
-use std::future::Future;
+use rand::Rng;
+use redis::{
+    AsyncCommands, Client, Cmd, ErrorKind, IntoConnectionInfo, Pipeline, RedisError, RedisFuture,
+    TlsMode, Value,
+    aio::{ConnectionLike, ConnectionManager},
+    cluster::ClusterClientBuilder,
+    cluster_async::ClusterConnection,
+    sentinel::{Sentinel, SentinelNodeConnectionInfo},
+};
+use serde::{Serialize, de::DeserializeOwned};
+use std::{
+    collections::{HashMap, HashSet},
+    env, fmt,
+    future::Future,
+    str::FromStr,
+    sync::{Arc, Mutex, OnceLock, RwLock},
+    time::{Duration, Instant},
+};
+
+use crate::common::{
+    infrastructure::local_cache::{LocalCacheConfig, LocalCacheTier},
+    utils::backoff::{BackoffPolicy, retry_with_backoff},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedisMode {
+    Single,
+    Cluster,
+    Sentinel,
+}
+
+impl FromStr for RedisMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "single" => Ok(Self::Single),
+            "cluster" => Ok(Self::Cluster),
+            "sentinel" => Ok(Self::Sentinel),
+            other => Err(format!("unknown Redis mode: {}", other)),
+        }
+    }
+}
+
+/// How a dropped connection is re-established: up to `retries` attempts with
+/// a random delay of at most `factor_ms * exponent_base^attempt`.
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    pub retries: usize,
+    pub factor_ms: u64,
+    pub exponent_base: u64,
+    /// Caps each delay in cluster mode only; the single and sentinel
+    /// connection managers have no such setting.
+    pub max_wait_ms: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct RedisConfig {
+    pub mode: RedisMode,
+    /// `redis://` or `rediss://` URLs: the server in single mode, the seed
+    /// nodes in cluster mode and the sentinels in sentinel mode.
+    pub urls: Vec<String>,
+    pub sentinel_service_name: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Use TLS for the master discovered through sentinel.
+    pub tls: bool,
+    pub connection_timeout: Duration,
+    pub response_timeout: Option<Duration>,
+    pub reconnect: ReconnectPolicy,
+}
+
+impl RedisConfig {
+    pub fn from_env() -> Self {
+        let mode: RedisMode = env::var("REDIS_MODE")
+            .unwrap_or_else(|_| "single".to_string())
+            .parse()
+            .expect("Invalid REDIS_MODE");
+        let urls: Vec<String> = match mode {
+            RedisMode::Single => {
+                vec![env::var("REDIS_URL").expect("REDIS_URL environment variable is required")]
+            }
+            RedisMode::Cluster => split_urls(
+                &env::var("REDIS_CLUSTER_NODES")
+                    .expect("REDIS_CLUSTER_NODES environment variable is required"),
+            ),
+            RedisMode::Sentinel => split_urls(
+                &env::var("REDIS_SENTINEL_NODES")
+                    .expect("REDIS_SENTINEL_NODES environment variable is required"),
+            ),
+        };
+        let sentinel_service_name = match mode {
+            RedisMode::Sentinel => Some(
+                env::var("REDIS_SENTINEL_SERVICE_NAME")
+                    .expect("REDIS_SENTINEL_SERVICE_NAME environment variable is required"),
+            ),
+            _ => None,
+        };
+        let tls: bool = env::var("REDIS_TLS")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .expect("Invalid REDIS_TLS");
+        let connection_timeout_ms: u64 = env::var("REDIS_CONNECTION_TIMEOUT_MS")
+            .unwrap_or_else(|_| "5000".to_string())
+            .parse()
+            .expect("Invalid REDIS_CONNECTION_TIMEOUT_MS");
+        let response_timeout_ms: u64 = env::var("REDIS_RESPONSE_TIMEOUT_MS")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .expect("Invalid REDIS_RESPONSE_TIMEOUT_MS");
+        let reconnect_retries: usize = env::var("REDIS_RECONNECT_RETRIES")
+            .unwrap_or_else(|_| "6".to_string())
+            .parse()
+            .expect("Invalid REDIS_RECONNECT_RETRIES");
+        let reconnect_factor_ms: u64 = env::var("REDIS_RECONNECT_FACTOR_MS")
+            .unwrap_or_else(|_| "100".to_string())
+            .parse()
+            .expect("Invalid REDIS_RECONNECT_FACTOR_MS");
+        let reconnect_max_wait_ms: u64 = env::var("REDIS_RECONNECT_MAX_WAIT_MS")
+            .unwrap_or_else(|_| "10000".to_string())
+            .parse()
+            .expect("Invalid REDIS_RECONNECT_MAX_WAIT_MS");
+
+        Self {
+            mode,
+            urls,
+            sentinel_service_name,
+            username: env::var("REDIS_USERNAME").ok().filter(|v| !v.is_empty()),
+            password: env::var("REDIS_PASSWORD").ok().filter(|v| !v.is_empty()),
+            tls,
+            connection_timeout: Duration::from_millis(connection_timeout_ms),
+            response_timeout: (response_timeout_ms > 0)
+                .then(|| Duration::from_millis(response_timeout_ms)),
+            reconnect: ReconnectPolicy {
+                retries: reconnect_retries,
+                factor_ms: reconnect_factor_ms,
+                exponent_base: 2,
+                max_wait_ms: reconnect_max_wait_ms,
+            },
+        }
+    }
+}
+
+fn split_urls(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|url| !url.is_empty())
+        .map(String::from)
+        .collect()
+}
 
 pub trait RedisClient {
-    fn create_connection(redis_url: &str) -> impl Future<Output = Result<ConnectionManager, redis::RedisError>> + Send;
+    fn create_connection(
+        config: &RedisConfig,
+    ) -> impl Future<Output = Result<RedisConnection, redis::RedisError>> + Send;
 }
 
 pub struct RedisClientImpl;
 
 impl RedisClient for RedisClientImpl {
-    fn create_connection(redis_url: &str) -> impl Future<Output = Result<ConnectionManager, redis::RedisError>> + Send {
+    /// Connects to Redis, retrying with the startup `BackoffPolicy`.
+    async fn create_connection(config: &RedisConfig) -> Result<RedisConnection, redis::RedisError> {
+        let policy = BackoffPolicy::from_env();
+        let node = retry_with_backoff("Redis", &policy, || RedisNode::connect(config)).await?;
+        Ok(RedisConnection::established(node, config))
+    }
+}
+
+/// Cloneable Redis handle over a single node, a cluster or a sentinel-managed
+/// primary, which may still be connecting in the background.
+///
+/// Commands sent before the connection is established fail with an IO error,
+/// which lets the server start in degraded mode and report not-ready until
+/// Redis shows up.
+#[derive(Clone)]
+pub struct RedisConnection {
+    inner: Arc<OnceLock<RedisNode>>,
+    response_timeout: Option<Duration>,
+}
+
+impl RedisConnection {
+    fn established(node: RedisNode, config: &RedisConfig) -> Self {
+        Self {
+            inner: Arc::new(OnceLock::from(node)),
+            response_timeout: config.response_timeout,
+        }
+    }
+
+    pub fn connect_in_background(config: &RedisConfig) -> Result<Self, RedisError> {
+        for url in &config.urls {
+            url.as_str().into_connection_info()?;
+        }
+
+        let connection = Self {
+            inner: Arc::new(OnceLock::new()),
+            response_timeout: config.response_timeout,
+        };
+
+        let inner = connection.inner.clone();
+        let config = config.clone();
+        tokio::spawn(async move {
+            let policy = BackoffPolicy::from_env().unbounded();
+            if let Ok(node) =
+                retry_with_backoff("Redis", &policy, || RedisNode::connect(&config)).await
+            {
+                let _ = inner.set(node);
+            }
+        });
+
+        Ok(connection)
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.inner.get().is_some()
+    }
+
+    /// Opens a dedicated pub/sub connection; these cannot be multiplexed with
+    /// regular commands.
+    pub async fn pubsub(&self) -> Result<redis::aio::PubSub, RedisError> {
+        let client = self.node()?.pubsub_client().await?;
+        Ok(client.get_async_connection().await?.into_pubsub())
+    }
+
+    fn node(&self) -> Result<RedisNode, RedisError> {
+        self.inner.get().cloned().ok_or_else(|| {
+            RedisError::from((ErrorKind::IoError, "Redis connection not established yet"))
+        })
+    }
+}
+
+impl ConnectionLike for RedisConnection {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        let node = self.node();
+        let response_timeout = self.response_timeout;
+        Box::pin(async move {
+            let mut node = node?;
+            with_timeout(response_timeout, node.req_packed_command(cmd)).await
+        })
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        let node = self.node();
+        let response_timeout = self.response_timeout;
+        Box::pin(async move {
+            let mut node = node?;
+            with_timeout(
+                response_timeout,
+                node.req_packed_commands(cmd, offset, count),
+            )
+            .await
+        })
+    }
+
+    fn get_db(&self) -> i64 {
+        self.inner.get().map(|node| node.get_db()).unwrap_or(0)
+    }
+}
+
+async fn with_timeout<T>(
+    timeout: Option<Duration>,
+    future: impl Future<Output = Result<T, RedisError>>,
+) -> Result<T, RedisError> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, future)
+            .await
+            .map_err(|_| RedisError::from((ErrorKind::IoError, "Redis response timed out")))?,
+        None => future.await,
+    }
+}
+
+#[derive(Clone)]
+enum RedisNode {
+    Single(ConnectionManager, Arc<Client>),
+    Cluster(ClusterConnection, Arc<Client>),
+    Sentinel(SentinelConnection),
+}
+
+impl RedisNode {
+    async fn connect(config: &RedisConfig) -> Result<Self, RedisError> {
+        let connect = async {
+            match config.mode {
+                RedisMode::Single => {
+                    let client = first_node_client(config)?;
+                    let manager = connection_manager(client.clone(), &config.reconnect).await?;
+                    Ok(RedisNode::Single(manager, Arc::new(client)))
+                }
+                RedisMode::Cluster => {
+                    let mut builder = ClusterClientBuilder::new(config.urls.clone())
+                        .retries(config.reconnect.retries as u32)
+                        .min_retry_wait(config.reconnect.factor_ms)
+                        .max_retry_wait(config.reconnect.max_wait_ms)
+                        .retry_wait_formula(
+                            config.reconnect.factor_ms,
+                            config.reconnect.exponent_base,
+                        );
+                    if let Some(username) = &config.username {
+                        builder = builder.username(username.clone());
+                    }
+                    if let Some(password) = &config.password {
+                        builder = builder.password(password.clone());
+                    }
+                    let cluster = builder.build()?.get_async_connection().await?;
+                    Ok(RedisNode::Cluster(
+                        cluster,
+                        Arc::new(first_node_client(config)?),
+                    ))
+                }
+                RedisMode::Sentinel => Ok(RedisNode::Sentinel(
+                    SentinelConnection::connect(config).await?,
+                )),
+            }
+        };
+
+        tokio::time::timeout(config.connection_timeout, connect)
+            .await
+            .map_err(|_| RedisError::from((ErrorKind::IoError, "Redis connection timed out")))?
+    }
+
+    /// Client for a node that can take pub/sub connections. Redis Cluster
+    /// propagates PUBLISH to every node, so any seed node will do.
+    async fn pubsub_client(&self) -> Result<Client, RedisError> {
+        match self {
+            RedisNode::Single(_, client) | RedisNode::Cluster(_, client) => {
+                Ok(client.as_ref().clone())
+            }
+            RedisNode::Sentinel(conn) => conn.master_client().await,
+        }
+    }
+}
+
+fn first_node_client(config: &RedisConfig) -> Result<Client, RedisError> {
+    let url = config.urls.first().ok_or_else(|| {
+        RedisError::from((ErrorKind::InvalidClientConfig, "no Redis URL configured"))
+    })?;
+    let mut info = url.as_str().into_connection_info()?;
+    if config.username.is_some() {
+        info.redis.username = config.username.clone();
+    }
+    if config.password.is_some() {
+        info.redis.password = config.password.clone();
+    }
+    Client::open(info)
+}
+
+impl ConnectionLike for RedisNode {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        match self {
+            RedisNode::Single(conn, _) => conn.req_packed_command(cmd),
+            RedisNode::Cluster(conn, _) => conn.req_packed_command(cmd),
+            RedisNode::Sentinel(conn) => conn.req_packed_command(cmd),
+        }
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        match self {
+            RedisNode::Single(conn, _) => conn.req_packed_commands(cmd, offset, count),
+            RedisNode::Cluster(conn, _) => conn.req_packed_commands(cmd, offset, count),
+            RedisNode::Sentinel(conn) => conn.req_packed_commands(cmd, offset, count),
+        }
+    }
+
+    fn get_db(&self) -> i64 {
+        match self {
+            RedisNode::Single(conn, _) => conn.get_db(),
+            RedisNode::Cluster(conn, _) => conn.get_db(),
+            RedisNode::Sentinel(conn) => conn.current().get_db(),
+        }
+    }
+}
+
+async fn connection_manager(
+    client: Client,
+    reconnect: &ReconnectPolicy,
+) -> Result<ConnectionManager, RedisError> {
+    ConnectionManager::new_with_backoff(
+        client,
+        reconnect.exponent_base,
+        reconnect.factor_ms,
+        reconnect.retries,
+    )
+    .await
+}
+
+/// Connection to the primary currently advertised by the sentinels.
+///
+/// `ConnectionManager` alone would keep reconnecting to the old address after
+/// a failover, so connection and READONLY errors trigger a fresh lookup.
+#[derive(Clone)]
+struct SentinelConnection {
+    sentinel: Arc<tokio::sync::Mutex<Sentinel>>,
+    service_name: Arc<str>,
+    node_info: SentinelNodeConnectionInfo,
+    reconnect: ReconnectPolicy,
+    manager: Arc<RwLock<ConnectionManager>>,
+}
+
+impl SentinelConnection {
+    async fn connect(config: &RedisConfig) -> Result<Self, RedisError> {
+        let service_name = config.sentinel_service_name.clone().ok_or_else(|| {
+            RedisError::from((
+                ErrorKind::InvalidClientConfig,
+                "REDIS_SENTINEL_SERVICE_NAME is required in sentinel mode",
+            ))
+        })?;
+        let mut sentinel = Sentinel::build(config.urls.clone())?;
+
+        let node_info = SentinelNodeConnectionInfo {
+            tls_mode: config.tls.then_some(TlsMode::Secure),
+            redis_connection_info: Some(redis::RedisConnectionInfo {
+                db: 0,
+                username: config.username.clone(),
+                password: config.password.clone(),
+            }),
+        };
+
+        let client = sentinel
+            .async_master_for(&service_name, Some(&node_info))
+            .await?;
+        let manager = connection_manager(client, &config.reconnect).await?;
+
+        Ok(Self {
+            sentinel: Arc::new(tokio::sync::Mutex::new(sentinel)),
+            service_name: service_name.into(),
+            node_info,
+            reconnect: config.reconnect.clone(),
+            manager: Arc::new(RwLock::new(manager)),
+        })
+    }
+
+    async fn master_client(&self) -> Result<Client, RedisError> {
+        self.sentinel
+            .lock()
+            .await
+            .async_master_for(&self.service_name, Some(&self.node_info))
+            .await
+    }
+
+    fn current(&self) -> ConnectionManager {
+        self.manager
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    fn on_error(&self, err: &RedisError) {
+        let failover = err.is_io_error()
+            || err.is_connection_dropped()
+            || err.is_connection_refusal()
+            || err.kind() == ErrorKind::ReadOnly;
+        if !failover {
+            return;
+        }
+
+        let Ok(mut sentinel) = self.sentinel.clone().try_lock_owned() else {
+            return;
+        };
+        let this = self.clone();
+        tokio::spawn(async move {
+            let rediscovered = async {
+                let client = sentinel
+                    .async_master_for(&this.service_name, Some(&this.node_info))
+                    .await?;
+                connection_manager(client, &this.reconnect).await
+            };
+
+            match rediscovered.await {
+                Ok(manager) => {
+                    tracing::info!("Redis sentinel switched to the current primary");
+                    *this.manager.write().unwrap_or_else(|e| e.into_inner()) = manager;
+                }
+                Err(e) => tracing::warn!("Redis sentinel primary lookup failed: {}", e),
+            }
+        });
+    }
+}
+
+impl ConnectionLike for SentinelConnection {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        let mut manager = self.current();
+        Box::pin(async move {
+            let result = manager.req_packed_command(cmd).await;
+            if let Err(err) = &result {
+                self.on_error(err);
+            }
+            result
+        })
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        let mut manager = self.current();
+        Box::pin(async move {
+            let result = manager.req_packed_commands(cmd, offset, count).await;
+            if let Err(err) = &result {
+                self.on_error(err);
+            }
+            result
+        })
+    }
+
+    fn get_db(&self) -> i64 {
+        self.current().get_db()
+    }
+}
+
+#[derive(Debug)]
+pub enum CacheError {
+    Redis(RedisError),
+    Codec(String),
+}
+
+impl fmt::Display for CacheError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CacheError::Redis(e) => write!(f, "cache backend error: {}", e),
+            CacheError::Codec(e) => write!(f, "cache codec error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for CacheError {}
+
+impl From<RedisError> for CacheError {
+    fn from(err: RedisError) -> Self {
+        CacheError::Redis(err)
+    }
+}
+
+/// Serialization format of cached values.
+pub trait CacheCodec: Send + Sync + 'static {
+    fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, CacheError>;
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, CacheError>;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonCodec;
+
+impl CacheCodec for JsonCodec {
+    fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, CacheError> {
+        serde_json::to_vec(value).map_err(|e| CacheError::Codec(e.to_string()))
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, CacheError> {
+        serde_json::from_slice(bytes).map_err(|e| CacheError::Codec(e.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MessagePackCodec;
+
+impl CacheCodec for MessagePackCodec {
+    fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, CacheError> {
+        rmp_serde::to_vec_named(value).map_err(|e| CacheError::Codec(e.to_string()))
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, CacheError> {
+        rmp_serde::from_slice(bytes).map_err(|e| CacheError::Codec(e.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CacheOptions {
+    /// `None` keeps the entry until it is deleted or invalidated.
+    pub ttl: Option<Duration>,
+    pub tags: Vec<String>,
+}
+
+impl CacheOptions {
+    pub fn ttl(ttl: Duration) -> Self {
+        Self {
+            ttl: Some(ttl),
+            tags: Vec::new(),
+        }
+    }
+
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CacheConfig {
+    /// Prefix of every key, so apps sharing a Redis do not collide.
+    pub namespace: String,
+    /// Up to this fraction of the TTL is added at random to each entry so
+    /// keys written together do not all expire together.
+    pub ttl_jitter: f64,
+}
+
+impl CacheConfig {
+    pub fn from_env() -> Self {
+        let ttl_jitter: f64 = env::var("CACHE_TTL_JITTER")
+            .unwrap_or_else(|_| "0.1".to_string())
+            .parse()
+            .expect("Invalid CACHE_TTL_JITTER");
+
+        Self {
+            namespace: env::var("CACHE_NAMESPACE")
+                .unwrap_or_else(|_| env!("CARGO_PKG_NAME").to_string()),
+            ttl_jitter,
+        }
+    }
+
+    fn key(&self, key: &str) -> String {
+        format!("{}:{}", self.namespace, key)
+    }
+
+    fn tag_key(&self, tag: &str) -> String {
+        format!("{}:tag:{}", self.namespace, tag)
+    }
+
+    fn jittered(&self, ttl: Duration) -> Duration {
+        if self.ttl_jitter <= 0.0 {
+            return ttl;
+        }
+        ttl + ttl.mul_f64(rand::thread_rng().gen_range(0.0..self.ttl_jitter))
+    }
+}
+
+/// Per-key locks that let only one caller run the loader for a missing key
+/// while the others wait for its result.
+#[derive(Default)]
+pub struct SingleFlight {
+    flights: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl SingleFlight {
+    async fn acquire(&self, key: &str) -> SingleFlightGuard<'_> {
+        let lock = self
+            .flights
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(key.to_string())
+            .or_default()
+            .clone();
+        let guard = lock.clone().lock_owned().await;
+
+        SingleFlightGuard {
+            flights: self,
+            key: key.to_string(),
+            lock,
+            _guard: guard,
+        }
+    }
+}
+
+struct SingleFlightGuard<'a> {
+    flights: &'a SingleFlight,
+    key: String,
+    lock: Arc<tokio::sync::Mutex<()>>,
+    _guard: tokio::sync::OwnedMutexGuard<()>,
+}
+
+impl Drop for SingleFlightGuard<'_> {
+    fn drop(&mut self) {
+        let mut flights = self
+            .flights
+            .flights
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        // Only the map, this guard and its own lock handle are left: nobody
+        // else is waiting on this key.
+        if Arc::strong_count(&self.lock) <= 3 {
+            flights.remove(&self.key);
+        }
+    }
+}
+
+/// Typed cache with cache-aside loading.
+///
+/// Keys are given without the namespace; implementations add it. Backend
+/// errors on the read path are logged and treated as misses by `get_or_load`
+/// so an unavailable cache degrades to hitting the source of truth.
+pub trait Cache: Send + Sync {
+    fn get<T>(&self, key: &str) -> impl Future<Output = Result<Option<T>, CacheError>> + Send
+    where
+        T: DeserializeOwned + Send;
+
+    fn set<T>(
+        &self,
+        key: &str,
+        value: &T,
+        options: &CacheOptions,
+    ) -> impl Future<Output = Result<(), CacheError>> + Send
+    where
+        T: Serialize + Sync;
+
+    fn delete(&self, key: &str) -> impl Future<Output = Result<(), CacheError>> + Send;
+
+    /// Deletes every entry stored with `tag` and returns how many there were.
+    fn invalidate_tag(&self, tag: &str) -> impl Future<Output = Result<u64, CacheError>> + Send;
+
+    fn single_flight(&self) -> &SingleFlight;
+
+    fn get_or_load<T, E, F, Fut>(
+        &self,
+        key: &str,
+        options: &CacheOptions,
+        loader: F,
+    ) -> impl Future<Output = Result<T, E>> + Send
+    where
+        T: Serialize + DeserializeOwned + Send + Sync,
+        E: Send,
+        F: FnOnce() -> Fut + Send,
+        Fut: Future<Output = Result<T, E>> + Send,
+    {
         async move {
-            let client = Client::open(redis_url)?;
-            ConnectionManager::new(client).await
+            match self.get::<T>(key).await {
+                Ok(Some(value)) => return Ok(value),
+                Ok(None) => {}
+                Err(e) => tracing::warn!("Cache read for {} failed: {}", key, e),
+            }
+
+            let _flight = self.single_flight().acquire(key).await;
+
+            // Another caller may have loaded the value while we waited.
+            if let Ok(Some(value)) = self.get::<T>(key).await {
+                return Ok(value);
+            }
+
+            let value = loader().await?;
+            if let Err(e) = self.set(key, &value, options).await {
+                tracing::warn!("Cache write for {} failed: {}", key, e);
+            }
+
+            Ok(value)
+        }
+    }
+}
+
+pub struct RedisCache<C: CacheCodec = JsonCodec> {
+    conn: RedisConnection,
+    codec: C,
+    config: CacheConfig,
+    single_flight: SingleFlight,
+    local: Option<Arc<LocalCacheTier>>,
+}
+
+impl RedisCache<JsonCodec> {
+    pub fn new(conn: RedisConnection, config: CacheConfig) -> Self {
+        Self::with_codec(conn, config, JsonCodec)
+    }
+}
+
+impl<C: CacheCodec> RedisCache<C> {
+    pub fn with_codec(conn: RedisConnection, config: CacheConfig, codec: C) -> Self {
+        Self {
+            conn,
+            codec,
+            config,
+            single_flight: SingleFlight::default(),
+            local: None,
+        }
+    }
+
+    /// Puts an in-process tier in front of Redis. Call
+    /// `spawn_invalidation_listener` afterwards so writes made by other
+    /// replicas evict entries here too.
+    pub fn with_local_tier(mut self, config: &LocalCacheConfig) -> Self {
+        self.local = Some(Arc::new(LocalCacheTier::new(
+            config,
+            &self.config.namespace,
+        )));
+        self
+    }
+
+    pub fn spawn_invalidation_listener(&self) {
+        if let Some(local) = self.local.clone() {
+            let conn = self.conn.clone();
+            tokio::spawn(async move { local.run_invalidation_listener(conn).await });
+        }
+    }
+
+    async fn invalidate_local(&self, keys: Vec<String>) {
+        if let Some(local) = &self.local {
+            local.invalidate(&self.conn, keys).await;
+        }
+    }
+}
+
+impl<C: CacheCodec> Cache for RedisCache<C> {
+    async fn get<T>(&self, key: &str) -> Result<Option<T>, CacheError>
+    where
+        T: DeserializeOwned + Send,
+    {
+        let key = self.config.key(key);
+        if let Some(bytes) = self.local.as_ref().and_then(|local| local.get(&key)) {
+            return self.codec.decode(&bytes).map(Some);
+        }
+
+        let mut conn = self.conn.clone();
+        let bytes: Option<Vec<u8>> = conn.get(&key).await?;
+        let result = if bytes.is_some() { "hit" } else { "miss" };
+        metrics::counter!("cache_requests_total", "tier" => "remote", "result" => result)
+            .increment(1);
+
+        let Some(bytes) = bytes else {
+            return Ok(None);
+        };
+        let value = self.codec.decode(&bytes)?;
+
+        if let Some(local) = &self.local {
+            // Bound the local copy by what is left of the Redis TTL so it
+            // never outlives the entry it mirrors.
+            let remaining: i64 = conn.pttl(&key).await?;
+            match remaining {
+                -1 => local.insert(&key, bytes, None),
+                ms if ms > 0 => local.insert(&key, bytes, Some(Duration::from_millis(ms as u64))),
+                _ => {}
+            }
         }
+
+        Ok(Some(value))
+    }
+
+    async fn set<T>(&self, key: &str, value: &T, options: &CacheOptions) -> Result<(), CacheError>
+    where
+        T: Serialize + Sync,
+    {
+        let bytes = self.codec.encode(value)?;
+        let key = self.config.key(key);
+        // Redis rejects a zero expiry, so sub-millisecond TTLs round up.
+        let ttl_ms = options
+            .ttl
+            .map(|ttl| self.config.jittered(ttl).as_millis().max(1) as u64);
+        let mut conn = self.conn.clone();
+
+        match ttl_ms {
+            Some(ttl_ms) => conn.pset_ex::<_, _, ()>(&key, bytes, ttl_ms).await?,
+            None => conn.set::<_, _, ()>(&key, bytes).await?,
+        }
+
+        // Commands are sent one by one rather than pipelined or scripted so
+        // that keys and tag sets may live in different cluster slots.
+        for tag in &options.tags {
+            let tag_key = self.config.tag_key(tag);
+            conn.sadd::<_, _, ()>(&tag_key, &key).await?;
+
+            match ttl_ms {
+                // A set just created by SADD has no expiry (-1) yet.
+                Some(ttl_ms) => {
+                    let remaining: i64 = conn.pttl(&tag_key).await?;
+                    if remaining < ttl_ms as i64 {
+                        conn.pexpire::<_, ()>(&tag_key, ttl_ms as i64).await?;
+                    }
+                }
+                None => conn.persist::<_, ()>(&tag_key).await?,
+            }
+        }
+
+        self.invalidate_local(vec![key]).await;
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), CacheError> {
+        let key = self.config.key(key);
+        let mut conn = self.conn.clone();
+        conn.del::<_, ()>(&key).await?;
+        self.invalidate_local(vec![key]).await;
+        Ok(())
+    }
+
+    async fn invalidate_tag(&self, tag: &str) -> Result<u64, CacheError> {
+        let tag_key = self.config.tag_key(tag);
+        let mut conn = self.conn.clone();
+        let keys: Vec<String> = conn.smembers(&tag_key).await?;
+
+        let mut deleted = 0;
+        for key in &keys {
+            deleted += conn.del::<_, u64>(key).await?;
+        }
+        conn.del::<_, ()>(&tag_key).await?;
+        self.invalidate_local(keys).await;
+
+        Ok(deleted)
+    }
+
+    fn single_flight(&self) -> &SingleFlight {
+        &self.single_flight
+    }
+}
+
+struct InMemoryEntry {
+    bytes: Vec<u8>,
+    expires_at: Option<Instant>,
+    tags: Vec<String>,
+}
+
+impl InMemoryEntry {
+    fn expired(&self, now: Instant) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+/// Process-local `Cache` with the same semantics as `RedisCache`, for tests
+/// and single-node setups without Redis.
+pub struct InMemoryCache<C: CacheCodec = JsonCodec> {
+    entries: Mutex<HashMap<String, InMemoryEntry>>,
+    tags: Mutex<HashMap<String, HashSet<String>>>,
+    codec: C,
+    config: CacheConfig,
+    single_flight: SingleFlight,
+}
+
+impl InMemoryCache<JsonCodec> {
+    pub fn new(config: CacheConfig) -> Self {
+        Self::with_codec(config, JsonCodec)
+    }
+}
+
+impl<C: CacheCodec> InMemoryCache<C> {
+    pub fn with_codec(config: CacheConfig, codec: C) -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            tags: Mutex::new(HashMap::new()),
+            codec,
+            config,
+            single_flight: SingleFlight::default(),
+        }
+    }
+
+    /// Drops `key` from the tag sets of its removed entry, and the sets it
+    /// leaves empty. Called with the entries lock held, which is always
+    /// taken before the tags lock.
+    fn untag(&self, key: &str, entry: &InMemoryEntry) {
+        let mut tags = self.tags.lock().unwrap_or_else(|e| e.into_inner());
+        for tag in &entry.tags {
+            if let Some(keys) = tags.get_mut(tag) {
+                keys.remove(key);
+                if keys.is_empty() {
+                    tags.remove(tag);
+                }
+            }
+        }
+    }
+}
+
+impl<C: CacheCodec> Cache for InMemoryCache<C> {
+    async fn get<T>(&self, key: &str) -> Result<Option<T>, CacheError>
+    where
+        T: DeserializeOwned + Send,
+    {
+        let key = self.config.key(key);
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+
+        let expired = match entries.get(&key) {
+            None => return Ok(None),
+            Some(entry) => entry.expired(Instant::now()),
+        };
+        if expired {
+            if let Some(entry) = entries.remove(&key) {
+                self.untag(&key, &entry);
+            }
+            return Ok(None);
+        }
+
+        entries
+            .get(&key)
+            .map(|entry| self.codec.decode(&entry.bytes))
+            .transpose()
+    }
+
+    async fn set<T>(&self, key: &str, value: &T, options: &CacheOptions) -> Result<(), CacheError>
+    where
+        T: Serialize + Sync,
+    {
+        let bytes = self.codec.encode(value)?;
+        let key = self.config.key(key);
+        let expires_at = options
+            .ttl
+            .map(|ttl| Instant::now() + self.config.jittered(ttl));
+
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        // The new value replaces the old one's tags as well.
+        if let Some(previous) = entries.remove(&key) {
+            self.untag(&key, &previous);
+        }
+        {
+            let mut tags = self.tags.lock().unwrap_or_else(|e| e.into_inner());
+            for tag in &options.tags {
+                tags.entry(tag.clone()).or_default().insert(key.clone());
+            }
+        }
+        entries.insert(
+            key,
+            InMemoryEntry {
+                bytes,
+                expires_at,
+                tags: options.tags.clone(),
+            },
+        );
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), CacheError> {
+        let key = self.config.key(key);
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(entry) = entries.remove(&key) {
+            self.untag(&key, &entry);
+        }
+        Ok(())
+    }
+
+    async fn invalidate_tag(&self, tag: &str) -> Result<u64, CacheError> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let keys = self
+            .tags
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(tag)
+            .unwrap_or_default();
+
+        let now = Instant::now();
+        let mut deleted = 0;
+        for key in &keys {
+            if let Some(entry) = entries.remove(key) {
+                self.untag(key, &entry);
+                // Expired entries are already gone as far as callers can tell.
+                if !entry.expired(now) {
+                    deleted += 1;
+                }
+            }
+        }
+        Ok(deleted)
+    }
+
+    fn single_flight(&self) -> &SingleFlight {
+        &self.single_flight
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache() -> InMemoryCache {
+        InMemoryCache::new(CacheConfig {
+            namespace: "test".to_string(),
+            ttl_jitter: 0.0,
+        })
+    }
+
+    #[tokio::test]
+    async fn tags_forget_deleted_and_expired_keys() {
+        let cache = cache();
+        let options = CacheOptions::default().tag("users");
+        cache.set("a", &1, &options).await.unwrap();
+        cache.set("b", &2, &options).await.unwrap();
+        cache.delete("a").await.unwrap();
+        assert_eq!(cache.invalidate_tag("users").await.unwrap(), 1);
+
+        let expiring = CacheOptions::ttl(Duration::from_millis(1)).tag("users");
+        cache.set("c", &3, &expiring).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        assert_eq!(cache.get::<i32>("c").await.unwrap(), None);
+        assert!(cache.tags.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn invalidate_tag_skips_expired_entries() {
+        let cache = cache();
+        let expiring = CacheOptions::ttl(Duration::from_millis(1)).tag("users");
+        cache.set("a", &1, &expiring).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        assert_eq!(cache.invalidate_tag("users").await.unwrap(), 0);
+        assert!(cache.entries.lock().unwrap().is_empty());
     }
 }
@@ -0,0 +1,294 @@
+use chrono::{DateTime, FixedOffset, Local, Utc};
+use chrono_tz::Tz;
+use cron::Schedule;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, env, fmt, fs, str::FromStr, time::Duration};
+
+/// Zone a task's cron expression is evaluated in: UTC, the host's local
+/// zone, an IANA name such as `Europe/Berlin` or a fixed offset. Named zones
+/// follow their daylight saving changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskTimeZone {
+    Utc,
+    Local,
+    Named(Tz),
+    Fixed(FixedOffset),
+}
+
+impl FromStr for TaskTimeZone {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "UTC" | "utc" | "Z" => Ok(TaskTimeZone::Utc),
+            "Local" | "local" => Ok(TaskTimeZone::Local),
+            zone => zone
+                .parse()
+                .map(TaskTimeZone::Named)
+                .or_else(|_| zone.parse().map(TaskTimeZone::Fixed))
+                .map_err(|_| {
+                    format!(
+                        "unsupported time zone {}, use UTC, Local, a name such as \
+                         Europe/Berlin or an offset such as +02:00",
+                        zone
+                    )
+                }),
+        }
+    }
+}
+
+impl TaskTimeZone {
+    /// The first occurrence of `schedule` strictly after `after`.
+    pub fn next_after(&self, schedule: &Schedule, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            TaskTimeZone::Utc => schedule.after(&after).next(),
+            TaskTimeZone::Local => schedule
+                .after(&after.with_timezone(&Local))
+                .next()
+                .map(|next| next.with_timezone(&Utc)),
+            TaskTimeZone::Named(tz) => schedule
+                .after(&after.with_timezone(tz))
+                .next()
+                .map(|next| next.with_timezone(&Utc)),
+            TaskTimeZone::Fixed(offset) => schedule
+                .after(&after.with_timezone(offset))
+                .next()
+                .map(|next| next.with_timezone(&Utc)),
+        }
+    }
+}
+
+impl fmt::Display for TaskTimeZone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TaskTimeZone::Utc => write!(f, "UTC"),
+            TaskTimeZone::Local => write!(f, "Local"),
+            TaskTimeZone::Named(tz) => write!(f, "{}", tz.name()),
+            TaskTimeZone::Fixed(offset) => write!(f, "{}", offset),
+        }
+    }
+}
+
+/// What happens to the occurrences that passed while no scheduler was
+/// leading, e.g. during a deploy.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+pub enum MisfirePolicy {
+    /// Carries on with the next occurrence.
+    #[default]
+    Skip,
+    /// Runs the latest missed occurrence.
+    RunOnce,
+    /// Runs every missed occurrence, oldest first.
+    RunAll,
+}
+
+/// What happens to an occurrence that comes due while the previous run is
+/// still going.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+pub enum OverlapPolicy {
+    /// Records the occurrence as skipped.
+    #[default]
+    Skip,
+    /// Runs it once the runs before it finished.
+    Queue,
+    /// Runs it right away, alongside the previous run.
+    Concurrent,
+}
+
+#[derive(Debug, Clone)]
+pub struct TaskConfig {
+    pub schedule: Schedule,
+    pub time_zone: TaskTimeZone,
+    pub enabled: bool,
+    /// A run still going after this is cancelled and counts as failed.
+    pub timeout: Duration,
+    pub misfire: MisfirePolicy,
+    pub overlap: OverlapPolicy,
+}
+
+impl TaskConfig {
+    /// The first occurrence strictly after `after`.
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.time_zone.next_after(&self.schedule, after)
+    }
+}
+
+#[derive(Deserialize)]
+struct RawTaskConfig {
+    cron: String,
+    #[serde(default = "default_time_zone")]
+    time_zone: String,
+    #[serde(default = "default_enabled")]
+    enabled: bool,
+    #[serde(default = "default_timeout_secs")]
+    timeout_secs: u64,
+    #[serde(default)]
+    misfire: MisfirePolicy,
+    #[serde(default)]
+    overlap: OverlapPolicy,
+}
+
+fn default_time_zone() -> String {
+    "UTC".to_string()
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_timeout_secs() -> u64 {
+    300
+}
+
+impl TryFrom<RawTaskConfig> for TaskConfig {
+    type Error = String;
+
+    fn try_from(raw: RawTaskConfig) -> Result<Self, Self::Error> {
+        // Standard five-field expressions run at second 0.
+        let cron = if raw.cron.split_whitespace().count() == 5 {
+            format!("0 {}", raw.cron)
+        } else {
+            raw.cron
+        };
+
+        Ok(Self {
+            schedule: Schedule::from_str(&cron)
+                .map_err(|e| format!("invalid cron expression {}: {}", cron, e))?,
+            time_zone: raw.time_zone.parse()?,
+            enabled: raw.enabled,
+            timeout: Duration::from_secs(raw.timeout_secs.max(1)),
+            misfire: raw.misfire,
+            overlap: raw.overlap,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct RawSchedulerConfig {
+    #[serde(default)]
+    tasks: HashMap<String, RawTaskConfig>,
+}
+
+/// Schedules of the registered tasks, keyed by task name.
+#[derive(Debug, Clone, Default)]
+pub struct SchedulerConfig {
+    pub tasks: HashMap<String, TaskConfig>,
+}
+
+impl SchedulerConfig {
+    /// Reads the JSON file at `SCHEDULER_CONFIG_FILE_PATH`:
+    ///
+    /// ```json
+    /// { "tasks": { "purge_job_records": { "cron": "0 30 3 * * *", "time_zone": "UTC",
+    ///                                     "enabled": true, "timeout_secs": 300,
+    ///                                     "misfire": "run_once", "overlap": "skip" } } }
+    /// ```
+    ///
+    /// `cron` takes six fields with seconds first, or the usual five.
+    /// `misfire` is `skip`, `run_once` or `run_all` and `overlap` is `skip`,
+    /// `queue` or `concurrent`; both default to `skip`.
+    pub fn from_env() -> Self {
+        let default_path = "apps/rust_forge_boilerplate/scheduler.json";
+        let file_path =
+            env::var("SCHEDULER_CONFIG_FILE_PATH").unwrap_or_else(|_| default_path.to_string());
+
+        let content = fs::read_to_string(&file_path).unwrap_or_else(|e| {
+            panic!("Failed to read scheduler config file {}: {}", file_path, e)
+        });
+        let raw: RawSchedulerConfig = serde_json::from_str(&content)
+            .unwrap_or_else(|e| panic!("Invalid scheduler config file {}: {}", file_path, e));
+
+        let tasks = raw
+            .tasks
+            .into_iter()
+            .map(|(name, raw)| {
+                let config = TaskConfig::try_from(raw).unwrap_or_else(|e| {
+                    panic!("Invalid scheduler config for task {}: {}", name, e)
+                });
+                (name, config)
+            })
+            .collect();
+
+        Self { tasks }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn raw(cron: &str, time_zone: &str) -> RawTaskConfig {
+        RawTaskConfig {
+            cron: cron.to_string(),
+            time_zone: time_zone.to_string(),
+            enabled: true,
+            timeout_secs: 0,
+            misfire: MisfirePolicy::default(),
+            overlap: OverlapPolicy::default(),
+        }
+    }
+
+    #[test]
+    fn parses_time_zones() {
+        assert_eq!("UTC".parse(), Ok(TaskTimeZone::Utc));
+        assert_eq!("local".parse(), Ok(TaskTimeZone::Local));
+        assert_eq!(
+            "Europe/Berlin".parse(),
+            Ok(TaskTimeZone::Named(chrono_tz::Europe::Berlin))
+        );
+        assert_eq!(
+            "+02:00".parse(),
+            Ok(TaskTimeZone::Fixed(
+                FixedOffset::east_opt(2 * 3600).unwrap()
+            ))
+        );
+        assert!("Mars/Olympus".parse::<TaskTimeZone>().is_err());
+    }
+
+    #[test]
+    fn five_field_cron_runs_at_second_zero() {
+        let config = TaskConfig::try_from(raw("30 3 * * *", "UTC")).unwrap();
+        let after = Utc.with_ymd_and_hms(2026, 1, 1, 12, 0, 0).unwrap();
+        assert_eq!(
+            config.next_after(after),
+            Some(Utc.with_ymd_and_hms(2026, 1, 2, 3, 30, 0).unwrap())
+        );
+        assert_eq!(config.timeout, Duration::from_secs(1));
+    }
+
+    #[test]
+    fn invalid_cron_is_rejected() {
+        assert!(TaskConfig::try_from(raw("not a cron", "UTC")).is_err());
+        assert!(TaskConfig::try_from(raw("0 30 3 * * *", "Nowhere")).is_err());
+    }
+
+    #[test]
+    fn named_zone_follows_daylight_saving() {
+        let config = TaskConfig::try_from(raw("0 0 3 * * *", "Europe/Berlin")).unwrap();
+        let winter = Utc.with_ymd_and_hms(2026, 1, 10, 12, 0, 0).unwrap();
+        let summer = Utc.with_ymd_and_hms(2026, 7, 10, 12, 0, 0).unwrap();
+        assert_eq!(
+            config.next_after(winter),
+            Some(Utc.with_ymd_and_hms(2026, 1, 11, 2, 0, 0).unwrap())
+        );
+        assert_eq!(
+            config.next_after(summer),
+            Some(Utc.with_ymd_and_hms(2026, 7, 11, 1, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn fixed_offset_shifts_occurrences() {
+        let config = TaskConfig::try_from(raw("0 0 3 * * *", "+02:00")).unwrap();
+        let after = Utc.with_ymd_and_hms(2026, 1, 10, 12, 0, 0).unwrap();
+        assert_eq!(
+            config.next_after(after),
+            Some(Utc.with_ymd_and_hms(2026, 1, 11, 1, 0, 0).unwrap())
+        );
+    }
+}
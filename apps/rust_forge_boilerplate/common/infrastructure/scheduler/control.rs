@@ -0,0 +1,249 @@
+use chrono::{DateTime, Utc};
+use cron::Schedule;
+use serde::Serialize;
+use sqlx::{PgPool, postgres::PgListener};
+use std::{str::FromStr, time::Duration};
+use tokio::sync::broadcast;
+
+use crate::common::infrastructure::{
+    database::DbExecutor,
+    scheduler::config::{MisfirePolicy, OverlapPolicy, TaskConfig, TaskTimeZone},
+};
+
+pub const TASK_TRIGGER_CHANNEL: &str = "scheduled_task_trigger";
+
+/// Trigger requests a schedule loop may fall behind by before it misses
+/// some.
+const FEED_CAPACITY: usize = 64;
+
+const TASK_COLUMNS: &str = "name, schedule, time_zone, enabled, misfire, overlap, timeout_secs, \
+                            paused, paused_at, trigger_requested_at, registered_at";
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct RegisteredTask {
+    pub name: String,
+    pub schedule: String,
+    pub time_zone: String,
+    /// Whether the scheduler config runs the task at all.
+    pub enabled: bool,
+    pub misfire: MisfirePolicy,
+    pub overlap: OverlapPolicy,
+    pub timeout_secs: i64,
+    /// Paused tasks skip their occurrences until resumed, but can still be
+    /// triggered.
+    pub paused: bool,
+    pub paused_at: Option<DateTime<Utc>>,
+    /// A manual run waiting for the leader to start it.
+    pub trigger_requested_at: Option<DateTime<Utc>>,
+    /// When a scheduler last started with the task registered.
+    pub registered_at: DateTime<Utc>,
+}
+
+impl RegisteredTask {
+    /// The first occurrence after `after`, unless the task is paused or
+    /// disabled.
+    pub fn next_run_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        if self.paused || !self.enabled {
+            return None;
+        }
+        let schedule = Schedule::from_str(&self.schedule).ok()?;
+        let time_zone: TaskTimeZone = self.time_zone.parse().ok()?;
+        time_zone.next_after(&schedule, after)
+    }
+}
+
+/// The tasks registered with the scheduler, with the controls operators set
+/// on them: whether a task is paused and whether a manual run was asked for.
+///
+/// Schedulers register their tasks on start, keeping the controls. Asking
+/// for a run fires `NOTIFY scheduled_task_trigger` with the task name, which
+/// the leading scheduler answers by running it.
+#[derive(Clone)]
+pub struct RegisteredTaskStore {
+    pool: PgPool,
+}
+
+impl RegisteredTaskStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub(crate) async fn register(
+        &self,
+        name: &str,
+        config: &TaskConfig,
+    ) -> Result<(), sqlx::Error> {
+        DbExecutor::from(&self.pool)
+            .execute(
+                sqlx::query(
+                    "INSERT INTO scheduled_tasks \
+                         (name, schedule, time_zone, enabled, misfire, overlap, timeout_secs) \
+                     VALUES ($1, $2, $3, $4, $5, $6, $7) \
+                     ON CONFLICT (name) DO UPDATE \
+                         SET schedule = EXCLUDED.schedule, \
+                             time_zone = EXCLUDED.time_zone, \
+                             enabled = EXCLUDED.enabled, \
+                             misfire = EXCLUDED.misfire, \
+                             overlap = EXCLUDED.overlap, \
+                             timeout_secs = EXCLUDED.timeout_secs, \
+                             registered_at = NOW()",
+                )
+                .bind(name)
+                .bind(config.schedule.to_string())
+                .bind(config.time_zone.to_string())
+                .bind(config.enabled)
+                .bind(config.misfire)
+                .bind(config.overlap)
+                .bind(config.timeout.as_secs() as i64),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn list(&self) -> Result<Vec<RegisteredTask>, sqlx::Error> {
+        DbExecutor::from(&self.pool)
+            .fetch_all(sqlx::query_as::<_, RegisteredTask>(&format!(
+                "SELECT {} FROM scheduled_tasks ORDER BY name",
+                TASK_COLUMNS
+            )))
+            .await
+    }
+
+    pub async fn get(&self, name: &str) -> Result<Option<RegisteredTask>, sqlx::Error> {
+        DbExecutor::from(&self.pool)
+            .fetch_optional(
+                sqlx::query_as::<_, RegisteredTask>(&format!(
+                    "SELECT {} FROM scheduled_tasks WHERE name = $1",
+                    TASK_COLUMNS
+                ))
+                .bind(name),
+            )
+            .await
+    }
+
+    /// Pauses or resumes the task. Returns `None` if it is not registered.
+    pub async fn set_paused(
+        &self,
+        name: &str,
+        paused: bool,
+    ) -> Result<Option<RegisteredTask>, sqlx::Error> {
+        DbExecutor::from(&self.pool)
+            .fetch_optional(
+                sqlx::query_as::<_, RegisteredTask>(&format!(
+                    "UPDATE scheduled_tasks \
+                     SET paused = $2, \
+                         paused_at = CASE WHEN $2 THEN COALESCE(paused_at, NOW()) END \
+                     WHERE name = $1 RETURNING {}",
+                    TASK_COLUMNS
+                ))
+                .bind(name)
+                .bind(paused),
+            )
+            .await
+    }
+
+    /// Asks the leading scheduler to run the task now. A request still
+    /// waiting absorbs the new one. Returns `None` if the task is not
+    /// registered.
+    pub async fn request_trigger(&self, name: &str) -> Result<Option<RegisteredTask>, sqlx::Error> {
+        DbExecutor::from(&self.pool)
+            .fetch_optional(
+                sqlx::query_as::<_, RegisteredTask>(&format!(
+                    "UPDATE scheduled_tasks \
+                     SET trigger_requested_at = COALESCE(trigger_requested_at, NOW()) \
+                     WHERE name = $1 RETURNING {}",
+                    TASK_COLUMNS
+                ))
+                .bind(name),
+            )
+            .await
+    }
+
+    /// Clears the task's manual run request, returning when it was made.
+    pub(crate) async fn take_trigger(
+        &self,
+        name: &str,
+    ) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+        let requested = DbExecutor::from(&self.pool)
+            .fetch_optional(
+                sqlx::query_as::<_, (DateTime<Utc>,)>(
+                    "WITH requested AS ( \
+                         SELECT name, trigger_requested_at FROM scheduled_tasks \
+                         WHERE name = $1 AND trigger_requested_at IS NOT NULL \
+                         FOR UPDATE \
+                     ) \
+                     UPDATE scheduled_tasks SET trigger_requested_at = NULL \
+                     FROM requested WHERE scheduled_tasks.name = requested.name \
+                     RETURNING requested.trigger_requested_at",
+                )
+                .bind(name),
+            )
+            .await?;
+        Ok(requested.map(|(requested_at,)| requested_at))
+    }
+
+    pub(crate) async fn is_paused(&self, name: &str) -> Result<bool, sqlx::Error> {
+        let paused = DbExecutor::from(&self.pool)
+            .fetch_optional(
+                sqlx::query_as::<_, (bool,)>("SELECT paused FROM scheduled_tasks WHERE name = $1")
+                    .bind(name),
+            )
+            .await?;
+        Ok(paused.is_some_and(|(paused,)| paused))
+    }
+}
+
+/// Names of tasks a manual run was asked for. `None` means requests may
+/// have been missed, e.g. while reconnecting, and every task should check.
+pub(crate) struct TaskTriggerFeed {
+    sender: broadcast::Sender<Option<String>>,
+}
+
+impl TaskTriggerFeed {
+    /// Starts listening in the background.
+    pub(crate) fn start(pool: PgPool) -> Self {
+        let (sender, _) = broadcast::channel(FEED_CAPACITY);
+        tokio::spawn(listen(pool, sender.clone()));
+        Self { sender }
+    }
+
+    pub(crate) fn subscribe(&self) -> broadcast::Receiver<Option<String>> {
+        self.sender.subscribe()
+    }
+}
+
+async fn listen(pool: PgPool, sender: broadcast::Sender<Option<String>>) {
+    let mut retry_delay = Duration::from_millis(500);
+
+    loop {
+        let mut listener = match PgListener::connect_with(&pool).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                tracing::warn!("Task trigger listener unavailable: {}", e);
+                tokio::time::sleep(retry_delay).await;
+                retry_delay = (retry_delay * 2).min(Duration::from_secs(30));
+                continue;
+            }
+        };
+        if let Err(e) = listener.listen(TASK_TRIGGER_CHANNEL).await {
+            tracing::warn!("Task trigger LISTEN failed: {}", e);
+            tokio::time::sleep(retry_delay).await;
+            retry_delay = (retry_delay * 2).min(Duration::from_secs(30));
+            continue;
+        }
+        retry_delay = Duration::from_millis(500);
+        let _ = sender.send(None);
+
+        loop {
+            match listener.recv().await {
+                Ok(notification) => {
+                    let _ = sender.send(Some(notification.payload().to_string()));
+                }
+                Err(e) => {
+                    tracing::warn!("Task trigger listener error: {}", e);
+                    break;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::common::infrastructure::database::DbExecutor;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+pub enum RunOutcome {
+    Running,
+    Succeeded,
+    /// Returned an error, panicked or timed out.
+    Failed,
+    /// Not run because the previous run was still going or the task was
+    /// paused.
+    Skipped,
+}
+
+impl RunOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RunOutcome::Running => "running",
+            RunOutcome::Succeeded => "succeeded",
+            RunOutcome::Failed => "failed",
+            RunOutcome::Skipped => "skipped",
+        }
+    }
+}
+
+/// What fired a run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+pub enum RunTrigger {
+    /// An occurrence of the task's schedule.
+    Schedule,
+    /// An operator asking for a run through the admin API.
+    Manual,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct TaskRun {
+    pub id: Uuid,
+    pub task: String,
+    /// The occurrence, or when a manual run was asked for.
+    pub scheduled_at: DateTime<Utc>,
+    pub trigger: RunTrigger,
+    /// Scheduler replica that fired the occurrence.
+    pub instance: String,
+    pub outcome: RunOutcome,
+    pub error: Option<String>,
+    /// The job the run handed its work to, for tasks that enqueue one.
+    pub job_id: Option<Uuid>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+/// Every run the scheduler fired, scheduled or manual, with its outcome.
+///
+/// A run is recorded as running before the task starts, which also claims
+/// the occurrence: a second replica firing it finds the claim taken and
+/// leaves it alone. A run whose scheduler died stays running until the next
+/// leader fails it, once it is past the task's timeout.
+#[derive(Clone)]
+pub struct TaskRunStore {
+    pool: PgPool,
+}
+
+impl TaskRunStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Claims the occurrence and records it as running. Returns `None` if
+    /// it was already claimed.
+    pub(crate) async fn start(
+        &self,
+        task: &str,
+        scheduled_at: DateTime<Utc>,
+        trigger: RunTrigger,
+        instance: &str,
+    ) -> Result<Option<Uuid>, sqlx::Error> {
+        let id = Uuid::new_v4();
+        let result = DbExecutor::from(&self.pool)
+            .execute(
+                sqlx::query(
+                    "INSERT INTO scheduled_task_runs \
+                         (id, task, scheduled_at, trigger, instance, outcome, started_at) \
+                     VALUES ($1, $2, $3, $4, $5, $6, NOW()) \
+                     ON CONFLICT (task, scheduled_at) DO NOTHING",
+                )
+                .bind(id)
+                .bind(task)
+                .bind(scheduled_at)
+                .bind(trigger)
+                .bind(instance)
+                .bind(RunOutcome::Running),
+            )
+            .await?;
+        Ok((result.rows_affected() > 0).then_some(id))
+    }
+
+    pub(crate) async fn finish(
+        &self,
+        id: Uuid,
+        outcome: RunOutcome,
+        error: Option<&str>,
+        job_id: Option<Uuid>,
+    ) -> Result<(), sqlx::Error> {
+        DbExecutor::from(&self.pool)
+            .execute(
+                sqlx::query(
+                    "UPDATE scheduled_task_runs \
+                     SET outcome = $2, error = $3, job_id = $4, finished_at = NOW() \
+                     WHERE id = $1",
+                )
+                .bind(id)
+                .bind(outcome)
+                .bind(error)
+                .bind(job_id),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(crate) async fn skip(
+        &self,
+        task: &str,
+        scheduled_at: DateTime<Utc>,
+        trigger: RunTrigger,
+        instance: &str,
+        reason: &str,
+    ) -> Result<(), sqlx::Error> {
+        DbExecutor::from(&self.pool)
+            .execute(
+                sqlx::query(
+                    "INSERT INTO scheduled_task_runs \
+                         (id, task, scheduled_at, trigger, instance, outcome, error) \
+                     VALUES ($1, $2, $3, $4, $5, $6, $7) \
+                     ON CONFLICT (task, scheduled_at) DO NOTHING",
+                )
+                .bind(Uuid::new_v4())
+                .bind(task)
+                .bind(scheduled_at)
+                .bind(trigger)
+                .bind(instance)
+                .bind(RunOutcome::Skipped)
+                .bind(reason),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Fails the runs of `task` still recorded as running although they
+    /// started more than `timeout` ago: their scheduler would have cancelled
+    /// them by now, so it must have died. Returns how many there were.
+    pub(crate) async fn fail_stale(
+        &self,
+        task: &str,
+        timeout: Duration,
+    ) -> Result<u64, sqlx::Error> {
+        let result = DbExecutor::from(&self.pool)
+            .execute(
+                sqlx::query(
+                    "UPDATE scheduled_task_runs \
+                     SET outcome = $2, error = $3, finished_at = NOW() \
+                     WHERE task = $1 AND outcome = $4 \
+                       AND started_at < NOW() - $5 * INTERVAL '1 millisecond'",
+                )
+                .bind(task)
+                .bind(RunOutcome::Failed)
+                .bind("abandoned: the scheduler running it stopped")
+                .bind(RunOutcome::Running)
+                .bind(timeout.as_millis() as i64),
+            )
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// The latest occurrence of `task`'s schedule any replica fired.
+    pub(crate) async fn last_scheduled_at(
+        &self,
+        task: &str,
+    ) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+        let (last,) = DbExecutor::from(&self.pool)
+            .fetch_one(
+                sqlx::query_as::<_, (Option<DateTime<Utc>>,)>(
+                    "SELECT MAX(scheduled_at) FROM scheduled_task_runs \
+                     WHERE task = $1 AND trigger = $2",
+                )
+                .bind(task)
+                .bind(RunTrigger::Schedule),
+            )
+            .await?;
+        Ok(last)
+    }
+
+    /// The latest runs of `task`, newest first.
+    pub async fn list(&self, task: &str, limit: i64) -> Result<Vec<TaskRun>, sqlx::Error> {
+        DbExecutor::from(&self.pool)
+            .fetch_all(
+                sqlx::query_as::<_, TaskRun>(
+                    "SELECT id, task, scheduled_at, trigger, instance, outcome, error, job_id, \
+                         started_at, finished_at \
+                     FROM scheduled_task_runs WHERE task = $1 \
+                     ORDER BY scheduled_at DESC LIMIT $2",
+                )
+                .bind(task)
+                .bind(limit),
+            )
+            .await
+    }
+
+    /// Deletes finished runs that ended before `before`.
+    pub async fn purge_finished(&self, before: DateTime<Utc>) -> Result<u64, sqlx::Error> {
+        let result = DbExecutor::from(&self.pool)
+            .execute(
+                sqlx::query(
+                    "DELETE FROM scheduled_task_runs \
+                     WHERE outcome <> $1 AND COALESCE(finished_at, scheduled_at) < $2",
+                )
+                .bind(RunOutcome::Running)
+                .bind(before),
+            )
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
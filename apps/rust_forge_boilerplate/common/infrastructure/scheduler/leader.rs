@@ -0,0 +1,229 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use std::{env, sync::Arc, time::Duration};
+use tokio::sync::watch;
+use uuid::Uuid;
+
+use crate::common::infrastructure::{
+    database::DbExecutor,
+    lock::{DistributedLock, LockBackend, LockGuard, LockOptions},
+};
+
+pub const LEADER_LOCK: &str = "scheduler:leader";
+
+#[derive(Debug, Clone)]
+pub struct LeaderElectionConfig {
+    /// Name this replica is known by; defaults to `HOSTNAME`, which is the
+    /// pod name on Kubernetes.
+    pub instance: String,
+    /// How long a leader that stopped renewing keeps the role. A replica
+    /// takes over at most a third of this after it runs out.
+    pub lease: Duration,
+}
+
+impl LeaderElectionConfig {
+    pub fn from_env() -> Self {
+        let lease_secs: u64 = env::var("SCHEDULER_LEADER_LEASE_SECS")
+            .unwrap_or_else(|_| "15".to_string())
+            .parse()
+            .expect("Invalid SCHEDULER_LEADER_LEASE_SECS");
+
+        Self {
+            instance: env::var("SCHEDULER_INSTANCE_ID")
+                .or_else(|_| env::var("HOSTNAME"))
+                .unwrap_or_else(|_| format!("scheduler-{}", Uuid::new_v4())),
+            lease: Duration::from_secs(lease_secs.max(3)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct SchedulerLeader {
+    pub instance: String,
+    /// Grows with every election, so a change means a failover happened.
+    pub fencing_token: i64,
+    pub elected_at: DateTime<Utc>,
+    pub renewed_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Who leads the schedulers, as recorded by the leader itself so other
+/// processes such as the server can report it.
+#[derive(Clone)]
+pub struct SchedulerLeaderStore {
+    pool: PgPool,
+}
+
+impl SchedulerLeaderStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// The leader, unless its lease ran out without being renewed.
+    pub async fn current(&self) -> Result<Option<SchedulerLeader>, sqlx::Error> {
+        DbExecutor::from(&self.pool)
+            .fetch_optional(
+                sqlx::query_as::<_, SchedulerLeader>(
+                    "SELECT instance, fencing_token, elected_at, renewed_at, expires_at \
+                     FROM scheduler_leaders WHERE name = $1 AND expires_at > NOW()",
+                )
+                .bind(LEADER_LOCK),
+            )
+            .await
+    }
+
+    /// Records `instance` as the leader until `lease` from now. Returns
+    /// `false` if a leader with a newer fencing token holds the record.
+    async fn renew(
+        &self,
+        instance: &str,
+        fencing_token: i64,
+        lease: Duration,
+    ) -> Result<bool, sqlx::Error> {
+        let result = DbExecutor::from(&self.pool)
+            .execute(
+                sqlx::query(
+                    "INSERT INTO scheduler_leaders \
+                         (name, instance, fencing_token, elected_at, renewed_at, expires_at) \
+                     VALUES ($1, $2, $3, NOW(), NOW(), NOW() + $4 * INTERVAL '1 millisecond') \
+                     ON CONFLICT (name) DO UPDATE \
+                         SET instance = EXCLUDED.instance, \
+                             fencing_token = EXCLUDED.fencing_token, \
+                             elected_at = CASE \
+                                 WHEN scheduler_leaders.fencing_token = EXCLUDED.fencing_token \
+                                 THEN scheduler_leaders.elected_at \
+                                 ELSE EXCLUDED.elected_at END, \
+                             renewed_at = EXCLUDED.renewed_at, \
+                             expires_at = EXCLUDED.expires_at \
+                         WHERE scheduler_leaders.fencing_token <= EXCLUDED.fencing_token \
+                            OR scheduler_leaders.expires_at < NOW()",
+                )
+                .bind(LEADER_LOCK)
+                .bind(instance)
+                .bind(fencing_token)
+                .bind(lease.as_millis() as i64),
+            )
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn resign(&self, fencing_token: i64) -> Result<(), sqlx::Error> {
+        DbExecutor::from(&self.pool)
+            .execute(
+                sqlx::query("DELETE FROM scheduler_leaders WHERE name = $1 AND fencing_token = $2")
+                    .bind(LEADER_LOCK)
+                    .bind(fencing_token),
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+/// Makes one scheduler replica the leader, the only one that fires tasks.
+///
+/// Every replica keeps trying to take the leader lock. The leader renews
+/// its record a few times per lease and steps down as soon as the lock is
+/// lost or a newer leader shows up in the record; on shutdown it releases
+/// the lock so a follower takes over right away.
+pub struct LeaderElection {
+    lock: LockBackend,
+    leaders: SchedulerLeaderStore,
+    config: LeaderElectionConfig,
+    is_leader: watch::Sender<bool>,
+}
+
+impl LeaderElection {
+    pub fn new(
+        lock: LockBackend,
+        leaders: SchedulerLeaderStore,
+        config: LeaderElectionConfig,
+    ) -> Self {
+        Self {
+            lock,
+            leaders,
+            config,
+            is_leader: watch::Sender::new(false),
+        }
+    }
+
+    pub fn instance(&self) -> &str {
+        &self.config.instance
+    }
+
+    pub fn is_leader(&self) -> bool {
+        *self.is_leader.borrow()
+    }
+
+    /// Follows whether this replica leads.
+    pub fn subscribe(&self) -> watch::Receiver<bool> {
+        self.is_leader.subscribe()
+    }
+
+    /// Campaigns until `shutdown`, then steps down.
+    pub async fn run(self: Arc<Self>, mut shutdown: watch::Receiver<bool>) {
+        let retry_interval = self.config.lease / 3;
+        let options = LockOptions::ttl(self.config.lease);
+
+        while !*shutdown.borrow() {
+            match self.lock.try_acquire(LEADER_LOCK, &options).await {
+                Ok(Some(guard)) => {
+                    self.lead(guard, &mut shutdown).await;
+                    continue;
+                }
+                Ok(None) => {}
+                Err(e) => tracing::warn!("Scheduler leader election failed: {}", e),
+            }
+
+            tokio::select! {
+                _ = shutdown.changed() => {}
+                _ = tokio::time::sleep(retry_interval) => {}
+            }
+        }
+    }
+
+    async fn lead(&self, guard: LockGuard, shutdown: &mut watch::Receiver<bool>) {
+        let fencing_token = guard.fencing_token() as i64;
+        tracing::info!(
+            "Scheduler {} is the leader (fencing token {})",
+            self.config.instance,
+            fencing_token
+        );
+        self.is_leader.send_replace(true);
+
+        let mut renew = tokio::time::interval(self.config.lease / 3);
+        loop {
+            tokio::select! {
+                _ = shutdown.changed() => break,
+                _ = renew.tick() => {}
+            }
+            if !guard.is_held() {
+                tracing::warn!("Scheduler {} lost the leader lock", self.config.instance);
+                break;
+            }
+            match self
+                .leaders
+                .renew(&self.config.instance, fencing_token, self.config.lease)
+                .await
+            {
+                Ok(true) => {}
+                Ok(false) => {
+                    tracing::warn!(
+                        "Scheduler {} was replaced by a newer leader",
+                        self.config.instance
+                    );
+                    break;
+                }
+                // The lock decides who leads; the record is only reported.
+                Err(e) => tracing::warn!("Failed to record the scheduler leader: {}", e),
+            }
+        }
+
+        self.is_leader.send_replace(false);
+        if let Err(e) = self.leaders.resign(fencing_token).await {
+            tracing::warn!("Failed to clear the scheduler leader record: {}", e);
+        }
+        guard.release().await;
+        tracing::info!("Scheduler {} stepped down", self.config.instance);
+    }
+}
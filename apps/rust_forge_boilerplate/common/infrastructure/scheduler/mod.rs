@@ -0,0 +1,6 @@
+pub mod config;
+pub mod control;
+pub mod history;
+pub mod leader;
+pub mod runner;
+pub mod task;
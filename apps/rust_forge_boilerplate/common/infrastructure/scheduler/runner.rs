@@ -0,0 +1,469 @@
+use chrono::{DateTime, Utc};
+use std::{collections::VecDeque, iter, sync::Arc, time::Instant};
+use tokio::{
+    sync::{broadcast, watch},
+    task::JoinSet,
+};
+
+use crate::common::infrastructure::{
+    queue::worker::panic_message,
+    scheduler::{
+        config::{MisfirePolicy, OverlapPolicy, SchedulerConfig, TaskConfig},
+        control::{RegisteredTaskStore, TaskTriggerFeed},
+        history::{RunOutcome, RunTrigger, TaskRunStore},
+        leader::LeaderElection,
+        task::{TaskContext, TaskError, TaskRegistry, TaskState},
+    },
+};
+
+/// Runs each enabled task at the occurrences of its cron schedule.
+///
+/// Every replica follows the schedules, but only the elected leader fires
+/// them, so each occurrence runs once however many replicas are deployed.
+/// Every firing is recorded in the task run history. On becoming leader a
+/// replica applies each task's misfire policy to the occurrences that passed
+/// since the last one fired, and an occurrence that comes due while a run is
+/// still going is handled by the task's overlap policy. Paused tasks skip
+/// their occurrences, and manual runs asked for through the admin API go
+/// through the same policies and history. Each run is cancelled once it
+/// exceeds the task's timeout, and a panic fails the run rather than the
+/// scheduler.
+pub struct Scheduler {
+    registry: Arc<TaskRegistry>,
+    config: SchedulerConfig,
+    state: Arc<TaskState>,
+    election: Arc<LeaderElection>,
+    history: TaskRunStore,
+    tasks: RegisteredTaskStore,
+}
+
+impl Scheduler {
+    pub fn new(
+        registry: TaskRegistry,
+        config: SchedulerConfig,
+        state: TaskState,
+        election: LeaderElection,
+    ) -> Self {
+        for name in config.tasks.keys() {
+            if !registry.contains(name) {
+                tracing::warn!("Scheduler config lists unknown task {}", name);
+            }
+        }
+        for name in registry.names() {
+            if !config.tasks.contains_key(name) {
+                tracing::warn!("Task {} has no schedule in the scheduler config", name);
+            }
+        }
+
+        Self {
+            registry: Arc::new(registry),
+            config,
+            history: TaskRunStore::new(state.db.clone()),
+            tasks: RegisteredTaskStore::new(state.db.clone()),
+            state: Arc::new(state),
+            election: Arc::new(election),
+        }
+    }
+
+    /// Runs until Ctrl-C, then lets running tasks finish.
+    pub async fn run(self) {
+        let scheduler = Arc::new(self);
+        scheduler.register_tasks().await;
+        let triggers = TaskTriggerFeed::start(scheduler.state.db.clone());
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let (resign_tx, resign_rx) = watch::channel(false);
+
+        let election = tokio::spawn(scheduler.election.clone().run(resign_rx));
+        let mut loops = JoinSet::new();
+        for name in scheduler.registry.names() {
+            match scheduler.config.tasks.get(name) {
+                Some(config) if config.enabled => {
+                    loops.spawn(scheduler.clone().schedule(
+                        name,
+                        shutdown_rx.clone(),
+                        triggers.subscribe(),
+                    ));
+                }
+                Some(_) => tracing::info!("Task {} is disabled", name),
+                None => {}
+            }
+        }
+        tracing::info!(
+            "Scheduler {} started with {} tasks",
+            scheduler.election.instance(),
+            loops.len()
+        );
+
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("Failed to listen for shutdown signal: {}", e);
+        }
+        tracing::info!("Scheduler shutting down, waiting for running tasks");
+        let _ = shutdown_tx.send(true);
+        while loops.join_next().await.is_some() {}
+        // Only now, so no other replica starts a run while ours are still going.
+        let _ = resign_tx.send(true);
+        let _ = election.await;
+    }
+
+    /// Records the configured tasks for the admin API.
+    async fn register_tasks(&self) {
+        for name in self.registry.names() {
+            if let Some(config) = self.config.tasks.get(name)
+                && let Err(e) = self.tasks.register(name, config).await
+            {
+                tracing::warn!("Failed to register task {}: {}", name, e);
+            }
+        }
+    }
+
+    async fn schedule(
+        self: Arc<Self>,
+        name: &'static str,
+        mut shutdown: watch::Receiver<bool>,
+        mut triggers: broadcast::Receiver<Option<String>>,
+    ) {
+        let config = &self.config.tasks[name];
+        let mut leader = self.election.subscribe();
+        let mut runs = JoinSet::new();
+        // Occurrences waiting for the running ones to finish.
+        let mut queued = VecDeque::new();
+        let mut caught_up = false;
+        let mut after = Utc::now();
+
+        'schedule: while !*shutdown.borrow() {
+            let Some(next) = config.next_after(after) else {
+                tracing::info!("Task {} has no further occurrences", name);
+                break;
+            };
+            tracing::debug!("Task {} next runs at {}", name, next);
+
+            let due = tokio::time::sleep((next - Utc::now()).to_std().unwrap_or_default());
+            tokio::pin!(due);
+            loop {
+                if *leader.borrow_and_update() {
+                    if !caught_up {
+                        self.catch_up(name, config, &mut queued).await;
+                        self.take_trigger(name, config, &mut runs, &mut queued)
+                            .await;
+                        caught_up = true;
+                    }
+                } else {
+                    // The next leader catches up from the run history.
+                    caught_up = false;
+                    queued.clear();
+                }
+                if runs.is_empty()
+                    && let Some((scheduled_at, trigger)) = queued.pop_front()
+                {
+                    runs.spawn(self.clone().fire(name, scheduled_at, trigger));
+                }
+
+                let mut triggered = false;
+                tokio::select! {
+                    _ = shutdown.changed() => break 'schedule,
+                    _ = &mut due => break,
+                    Some(_) = runs.join_next() => {}
+                    Ok(()) = leader.changed() => {}
+                    Ok(task) = triggers.recv() => {
+                        triggered = task.is_none_or(|task| task == name);
+                    }
+                }
+                if triggered && self.election.is_leader() {
+                    self.take_trigger(name, config, &mut runs, &mut queued)
+                        .await;
+                }
+            }
+
+            after = next;
+            if !self.election.is_leader() {
+                tracing::debug!("Task {} at {} left to the leader", name, next);
+                continue;
+            }
+            self.dispatch(
+                name,
+                config,
+                next,
+                RunTrigger::Schedule,
+                &mut runs,
+                &mut queued,
+            )
+            .await;
+        }
+
+        // Queued occurrences are dropped; running ones get to finish.
+        while runs.join_next().await.is_some() {}
+    }
+
+    /// Starts a run, unless the task's overlap policy holds it back.
+    async fn dispatch(
+        self: &Arc<Self>,
+        name: &'static str,
+        config: &TaskConfig,
+        scheduled_at: DateTime<Utc>,
+        trigger: RunTrigger,
+        runs: &mut JoinSet<()>,
+        queued: &mut VecDeque<(DateTime<Utc>, RunTrigger)>,
+    ) {
+        if runs.is_empty() || config.overlap == OverlapPolicy::Concurrent {
+            runs.spawn(self.clone().fire(name, scheduled_at, trigger));
+        } else if config.overlap == OverlapPolicy::Queue {
+            queued.push_back((scheduled_at, trigger));
+        } else {
+            tracing::warn!(
+                "Task {} at {} skipped, the previous run is still going",
+                name,
+                scheduled_at
+            );
+            self.skip(name, scheduled_at, trigger, "previous run still going")
+                .await;
+        }
+    }
+
+    /// Dispatches the manual run asked for through the admin API, if any.
+    async fn take_trigger(
+        self: &Arc<Self>,
+        name: &'static str,
+        config: &TaskConfig,
+        runs: &mut JoinSet<()>,
+        queued: &mut VecDeque<(DateTime<Utc>, RunTrigger)>,
+    ) {
+        match self.tasks.take_trigger(name).await {
+            Ok(Some(requested_at)) => {
+                tracing::info!("Task {} triggered manually at {}", name, requested_at);
+                self.dispatch(name, config, requested_at, RunTrigger::Manual, runs, queued)
+                    .await;
+            }
+            Ok(None) => {}
+            Err(e) => tracing::warn!("Failed to take the manual run of task {}: {}", name, e),
+        }
+    }
+
+    /// Fails the runs a previous leader left running, then queues the
+    /// occurrences missed since the last one fired, as far as the task's
+    /// misfire policy asks for them.
+    async fn catch_up(
+        &self,
+        name: &'static str,
+        config: &TaskConfig,
+        queued: &mut VecDeque<(DateTime<Utc>, RunTrigger)>,
+    ) {
+        match self.history.fail_stale(name, config.timeout).await {
+            Ok(0) => {}
+            Ok(stale) => tracing::warn!(
+                "Task {} had {} runs left running by a stopped scheduler, marked as failed",
+                name,
+                stale
+            ),
+            Err(e) => tracing::warn!("Failed to fail the stale runs of task {}: {}", name, e),
+        }
+
+        let last = match self.history.last_scheduled_at(name).await {
+            Ok(Some(last)) => last,
+            // Never fired, so nothing was missed.
+            Ok(None) => return,
+            Err(e) => {
+                tracing::warn!("Failed to look up the last run of task {}: {}", name, e);
+                return;
+            }
+        };
+
+        let missed = missed_occurrences(config, last, Utc::now());
+        if missed.is_empty() {
+            return;
+        }
+
+        match config.misfire {
+            MisfirePolicy::Skip => tracing::info!(
+                "Task {} skips {} occurrences missed since {}",
+                name,
+                missed.len(),
+                last
+            ),
+            MisfirePolicy::RunOnce => tracing::info!(
+                "Task {} runs the latest of {} occurrences missed since {}",
+                name,
+                missed.len(),
+                last
+            ),
+            MisfirePolicy::RunAll => tracing::info!(
+                "Task {} runs {} occurrences missed since {}",
+                name,
+                missed.len(),
+                last
+            ),
+        }
+        queued.extend(
+            misfire_runs(config.misfire, missed)
+                .into_iter()
+                .map(|at| (at, RunTrigger::Schedule)),
+        );
+    }
+
+    async fn skip(
+        &self,
+        name: &'static str,
+        scheduled_at: DateTime<Utc>,
+        trigger: RunTrigger,
+        reason: &str,
+    ) {
+        if let Err(e) = self
+            .history
+            .skip(
+                name,
+                scheduled_at,
+                trigger,
+                self.election.instance(),
+                reason,
+            )
+            .await
+        {
+            tracing::warn!("Failed to record the skipped run of task {}: {}", name, e);
+        }
+    }
+
+    async fn fire(
+        self: Arc<Self>,
+        name: &'static str,
+        scheduled_at: DateTime<Utc>,
+        trigger: RunTrigger,
+    ) {
+        if trigger == RunTrigger::Schedule {
+            match self.tasks.is_paused(name).await {
+                Ok(true) => {
+                    tracing::debug!(
+                        "Task {} at {} skipped, the task is paused",
+                        name,
+                        scheduled_at
+                    );
+                    self.skip(name, scheduled_at, trigger, "task paused").await;
+                    return;
+                }
+                Ok(false) => {}
+                Err(e) => tracing::warn!("Failed to check whether task {} is paused: {}", name, e),
+            }
+        }
+
+        let timeout = self.config.tasks[name].timeout;
+        let run_id = match self
+            .history
+            .start(name, scheduled_at, trigger, self.election.instance())
+            .await
+        {
+            Ok(Some(id)) => Some(id),
+            Ok(None) => {
+                tracing::debug!("Task {} at {} already ran", name, scheduled_at);
+                return;
+            }
+            // The leader lock already keeps other replicas from firing.
+            Err(e) => {
+                tracing::warn!("Failed to record the run of task {}: {}", name, e);
+                None
+            }
+        };
+
+        let ctx = TaskContext {
+            task: name,
+            scheduled_at,
+            trigger,
+            state: self.state.clone(),
+        };
+        let Some(run) = self.registry.run(name, ctx) else {
+            return;
+        };
+
+        let started = Instant::now();
+        let result = match tokio::spawn(tokio::time::timeout(timeout, run)).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err(TaskError::new(format!("timed out after {:?}", timeout))),
+            Err(e) if e.is_panic() => Err(TaskError::new(format!(
+                "panicked: {}",
+                panic_message(e.into_panic())
+            ))),
+            Err(e) => Err(TaskError::new(e.to_string())),
+        };
+
+        let (outcome, error, job_id) = match &result {
+            Ok(job_id) => (RunOutcome::Succeeded, None, *job_id),
+            Err(e) => (RunOutcome::Failed, Some(e.message.as_str()), None),
+        };
+        metrics::histogram!("scheduled_task_duration_seconds", "task" => name, "outcome" => outcome.as_str())
+            .record(started.elapsed().as_secs_f64());
+        match &result {
+            Ok(Some(job_id)) => tracing::info!("Task {} enqueued job {}", name, job_id),
+            Ok(None) => tracing::info!("Task {} succeeded in {:?}", name, started.elapsed()),
+            Err(e) => tracing::error!("Task {} failed after {:?}: {}", name, started.elapsed(), e),
+        }
+
+        if let Some(id) = run_id
+            && let Err(e) = self.history.finish(id, outcome, error, job_id).await
+        {
+            tracing::warn!("Failed to record the outcome of task {}: {}", name, e);
+        }
+    }
+}
+
+/// The occurrences after `last` that were due by `now`, oldest first.
+fn missed_occurrences(
+    config: &TaskConfig,
+    last: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Vec<DateTime<Utc>> {
+    iter::successors(config.next_after(last), |at| config.next_after(*at))
+        .take_while(|at| *at <= now)
+        .collect()
+}
+
+/// The missed occurrences `policy` runs, oldest first.
+fn misfire_runs(policy: MisfirePolicy, mut missed: Vec<DateTime<Utc>>) -> Vec<DateTime<Utc>> {
+    match policy {
+        MisfirePolicy::Skip => Vec::new(),
+        MisfirePolicy::RunOnce => missed.pop().into_iter().collect(),
+        MisfirePolicy::RunAll => missed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::infrastructure::scheduler::config::TaskTimeZone;
+    use chrono::TimeZone;
+    use cron::Schedule;
+    use std::{str::FromStr, time::Duration};
+
+    fn hourly() -> TaskConfig {
+        TaskConfig {
+            schedule: Schedule::from_str("0 0 * * * *").unwrap(),
+            time_zone: TaskTimeZone::Utc,
+            enabled: true,
+            timeout: Duration::from_secs(60),
+            misfire: MisfirePolicy::Skip,
+            overlap: OverlapPolicy::Skip,
+        }
+    }
+
+    fn at(hour: u32, min: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 1, 1, hour, min, 0).unwrap()
+    }
+
+    #[test]
+    fn collects_occurrences_due_since_the_last_run() {
+        let missed = missed_occurrences(&hourly(), at(1, 0), at(4, 0));
+        assert_eq!(missed, vec![at(2, 0), at(3, 0), at(4, 0)]);
+    }
+
+    #[test]
+    fn nothing_is_missed_before_the_next_occurrence() {
+        assert!(missed_occurrences(&hourly(), at(1, 0), at(1, 59)).is_empty());
+    }
+
+    #[test]
+    fn misfire_policy_picks_the_runs() {
+        let missed = vec![at(2, 0), at(3, 0), at(4, 0)];
+        assert!(misfire_runs(MisfirePolicy::Skip, missed.clone()).is_empty());
+        assert_eq!(
+            misfire_runs(MisfirePolicy::RunOnce, missed.clone()),
+            vec![at(4, 0)]
+        );
+        assert_eq!(misfire_runs(MisfirePolicy::RunAll, missed.clone()), missed);
+    }
+}
@@ -0,0 +1,138 @@
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use sqlx::PgPool;
+use std::{collections::HashMap, fmt, future::Future, sync::Arc};
+use uuid::Uuid;
+
+use crate::common::infrastructure::{
+    queue::{backend::JobQueue, job::Job},
+    redis::{RedisCache, RedisConnection},
+    scheduler::history::RunTrigger,
+};
+
+/// A task run on a cron schedule by the `scheduler` binary. Its schedule,
+/// time zone, timeout and whether it runs at all come from the scheduler
+/// config, under the task's `NAME`.
+///
+/// The task runs inside the scheduler, once per occurrence. Work that takes
+/// long or should be retried belongs in a job instead, see
+/// [`TaskRegistry::register_job`].
+pub trait ScheduledTask: Send + Sync + 'static {
+    const NAME: &'static str;
+
+    fn run(&self, ctx: &TaskContext) -> impl Future<Output = Result<(), TaskError>> + Send;
+}
+
+#[derive(Debug, Clone)]
+pub struct TaskError {
+    pub message: String,
+}
+
+impl TaskError {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+        }
+    }
+}
+
+impl<E: std::error::Error> From<E> for TaskError {
+    fn from(err: E) -> Self {
+        Self::new(err.to_string())
+    }
+}
+
+impl fmt::Display for TaskError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Shared resources every task can use.
+pub struct TaskState {
+    pub db: PgPool,
+    pub redis: RedisConnection,
+    pub cache: Arc<RedisCache>,
+    pub jobs: Arc<JobQueue>,
+}
+
+/// What a task knows about the run it is part of.
+#[derive(Clone)]
+pub struct TaskContext {
+    pub task: &'static str,
+    /// The occurrence being run, which may lie slightly in the past, or when
+    /// a manual run was asked for.
+    pub scheduled_at: DateTime<Utc>,
+    pub trigger: RunTrigger,
+    pub state: Arc<TaskState>,
+}
+
+/// Resolves to the id of the job the run enqueued, if it enqueued one.
+type TaskHandler =
+    Box<dyn Fn(TaskContext) -> BoxFuture<'static, Result<Option<Uuid>, TaskError>> + Send + Sync>;
+
+/// Maps task names to the registered tasks.
+#[derive(Default)]
+pub struct TaskRegistry {
+    tasks: HashMap<&'static str, TaskHandler>,
+}
+
+impl TaskRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<T: ScheduledTask>(self, task: T) -> Self {
+        let task = Arc::new(task);
+        let handler: TaskHandler = Box::new(move |ctx| {
+            let task = task.clone();
+            Box::pin(async move { task.run(&ctx).await.map(|()| None) })
+        });
+        self.insert(T::NAME, handler)
+    }
+
+    /// Registers a task that only decides when: each run enqueues the job
+    /// `make` builds, and the worker does the work with the job's retries,
+    /// timeout and dead-lettering. The task is scheduled under `J::NAME`,
+    /// and the worker must register `J` too.
+    pub fn register_job<J, F>(self, make: F) -> Self
+    where
+        J: Job,
+        F: Fn(&TaskContext) -> J + Send + Sync + 'static,
+    {
+        let make = Arc::new(make);
+        let handler: TaskHandler = Box::new(move |ctx| {
+            let make = make.clone();
+            Box::pin(async move {
+                let job = make(&ctx);
+                let enqueued = ctx.state.jobs.enqueue(&job).await?;
+                Ok(Some(enqueued.id()))
+            })
+        });
+        self.insert(J::NAME, handler)
+    }
+
+    fn insert(mut self, name: &'static str, handler: TaskHandler) -> Self {
+        if self.tasks.insert(name, handler).is_some() {
+            panic!("Scheduled task {} is registered twice", name);
+        }
+        self
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.tasks.contains_key(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.tasks.keys().copied()
+    }
+
+    /// Starts the task `name`, or returns `None` if none is registered.
+    pub(crate) fn run(
+        &self,
+        name: &str,
+        ctx: TaskContext,
+    ) -> Option<BoxFuture<'static, Result<Option<Uuid>, TaskError>>> {
+        self.tasks.get(name).map(|handler| handler(ctx))
+    }
+}
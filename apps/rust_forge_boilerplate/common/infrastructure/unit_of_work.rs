@@ -0,0 +1,130 @@
+use sqlx::{PgConnection, PgPool};
+use std::{future::Future, pin::Pin, time::Duration};
+
+pub type TxFuture<'c, T> = Pin<Box<dyn Future<Output = Result<T, sqlx::Error>> + Send + 'c>>;
+
+const SERIALIZATION_FAILURE: &str = "40001";
+const DEADLOCK_DETECTED: &str = "40P01";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IsolationLevel {
+    ReadCommitted,
+    RepeatableRead,
+    Serializable,
+}
+
+impl IsolationLevel {
+    fn as_sql(&self) -> &'static str {
+        match self {
+            IsolationLevel::ReadCommitted => "SET TRANSACTION ISOLATION LEVEL READ COMMITTED",
+            IsolationLevel::RepeatableRead => "SET TRANSACTION ISOLATION LEVEL REPEATABLE READ",
+            IsolationLevel::Serializable => "SET TRANSACTION ISOLATION LEVEL SERIALIZABLE",
+        }
+    }
+}
+
+/// Runs a closure inside a single transaction, committing when it returns
+/// `Ok` and rolling back otherwise. Serialization failures and deadlocks
+/// restart the whole closure on a fresh transaction, so it must be safe to
+/// run more than once.
+///
+/// ```ignore
+/// let uow = UnitOfWork::new(db.writer().clone());
+/// uow.run(|conn| {
+///     let repo = repo.clone();
+///     Box::pin(async move { repo.create(conn.into()).await })
+/// })
+/// .await?;
+/// ```
+#[derive(Clone)]
+pub struct UnitOfWork {
+    pool: PgPool,
+    isolation: Option<IsolationLevel>,
+    max_retries: u32,
+    retry_delay: Duration,
+}
+
+impl UnitOfWork {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            isolation: None,
+            max_retries: 3,
+            retry_delay: Duration::from_millis(20),
+        }
+    }
+
+    pub fn isolation(mut self, isolation: IsolationLevel) -> Self {
+        self.isolation = Some(isolation);
+        self
+    }
+
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    pub fn retry_delay(mut self, retry_delay: Duration) -> Self {
+        self.retry_delay = retry_delay;
+        self
+    }
+
+    pub async fn run<T, F>(&self, mut work: F) -> Result<T, sqlx::Error>
+    where
+        F: for<'c> FnMut(&'c mut PgConnection) -> TxFuture<'c, T>,
+    {
+        let mut attempt = 0;
+        loop {
+            match self.run_once(&mut work).await {
+                Err(err) if is_retryable(&err) && attempt < self.max_retries => {
+                    attempt += 1;
+                    tracing::warn!(
+                        "Transaction aborted ({}), retrying attempt {}/{}",
+                        err,
+                        attempt,
+                        self.max_retries
+                    );
+                    let backoff = self
+                        .retry_delay
+                        .saturating_mul(2u32.pow((attempt - 1).min(16)));
+                    tokio::time::sleep(backoff).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn run_once<T, F>(&self, work: &mut F) -> Result<T, sqlx::Error>
+    where
+        F: for<'c> FnMut(&'c mut PgConnection) -> TxFuture<'c, T>,
+    {
+        let mut tx = self.pool.begin().await?;
+
+        if let Some(isolation) = self.isolation {
+            sqlx::query(isolation.as_sql()).execute(&mut *tx).await?;
+        }
+
+        match work(&mut tx).await {
+            Ok(value) => {
+                tx.commit().await?;
+                Ok(value)
+            }
+            Err(err) => {
+                if let Err(rollback_err) = tx.rollback().await {
+                    tracing::error!("Failed to roll back transaction: {}", rollback_err);
+                }
+                Err(err)
+            }
+        }
+    }
+}
+
+pub fn is_retryable(err: &sqlx::Error) -> bool {
+    match err {
+        sqlx::Error::Database(db_err) => matches!(
+            db_err.code().as_deref(),
+            Some(SERIALIZATION_FAILURE) | Some(DEADLOCK_DETECTED)
+        ),
+        _ => false,
+    }
+}
@@ -0,0 +1,76 @@
+use actix_web::{
+    FromRequest, HttpRequest,
+    dev::Payload,
+    http::{StatusCode, header},
+    web,
+};
+use sha2::{Digest, Sha256};
+use std::{
+    env,
+    future::{Ready, ready},
+};
+
+use crate::common::utils::error::AppError;
+
+#[derive(Debug, Clone, Default)]
+pub struct AdminAuthConfig {
+    /// Bearer token admin endpoints require. Admin endpoints are disabled
+    /// when unset.
+    pub token: Option<String>,
+}
+
+impl AdminAuthConfig {
+    pub fn from_env() -> Self {
+        Self {
+            token: env::var("ADMIN_API_TOKEN")
+                .ok()
+                .filter(|token| !token.is_empty()),
+        }
+    }
+}
+
+/// Extractor that only succeeds for requests carrying
+/// `Authorization: Bearer <ADMIN_API_TOKEN>`. Add it as a handler argument
+/// to protect an admin endpoint.
+pub struct AdminAccess;
+
+impl FromRequest for AdminAccess {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let Some(config) = req.app_data::<web::Data<AdminAuthConfig>>() else {
+            tracing::error!("AdminAccess extractor used without AdminAuthConfig app data");
+            return ready(Err(reject(AppError::new(1100, None))));
+        };
+        let Some(expected) = config.token.as_deref() else {
+            return ready(Err(reject(AppError::new(
+                1107,
+                Some(StatusCode::FORBIDDEN),
+            ))));
+        };
+
+        let provided = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .unwrap_or_default();
+
+        // Comparing digests keeps the comparison time independent of where
+        // the tokens differ.
+        if Sha256::digest(provided.as_bytes()) == Sha256::digest(expected.as_bytes()) {
+            ready(Ok(AdminAccess))
+        } else {
+            ready(Err(reject(AppError::new(
+                1106,
+                Some(StatusCode::UNAUTHORIZED),
+            ))))
+        }
+    }
+}
+
+fn reject(error: AppError) -> actix_web::Error {
+    actix_web::error::InternalError::from_response(error.to_string(), error.http_response_builder())
+        .into()
+}
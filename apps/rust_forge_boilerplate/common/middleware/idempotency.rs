@@ -0,0 +1,691 @@
+use actix_web::{
+    Error, HttpResponse,
+    body::{self, EitherBody, MessageBody},
+    dev::{Payload, Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+    http::{
+        Method, StatusCode,
+        header::{self, HeaderName},
+    },
+    web::Bytes,
+};
+use futures::future::LocalBoxFuture;
+use redis::{RedisError, Script};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::{
+    env,
+    error::Error as StdError,
+    fmt,
+    future::{Future, Ready, ready},
+    rc::Rc,
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::common::{
+    infrastructure::{database::DbExecutor, redis::RedisConnection},
+    utils::error::AppError,
+};
+
+const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+const REPLAYED_HEADER: &str = "idempotent-replayed";
+const MAX_KEY_LENGTH: usize = 255;
+
+#[derive(Debug, Clone)]
+pub struct IdempotencyConfig {
+    /// How long a completed response is replayed.
+    pub ttl: Duration,
+    /// How long a key stays locked by a request that never completes, e.g.
+    /// because the replica handling it crashed.
+    pub lock_timeout: Duration,
+}
+
+impl IdempotencyConfig {
+    /// Returns `None` unless `IDEMPOTENCY_ENABLED=true`.
+    pub fn from_env() -> Option<Self> {
+        let enabled: bool = env::var("IDEMPOTENCY_ENABLED")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .expect("Invalid IDEMPOTENCY_ENABLED");
+        if !enabled {
+            return None;
+        }
+
+        let ttl_secs: u64 = env::var("IDEMPOTENCY_TTL_SECS")
+            .unwrap_or_else(|_| "86400".to_string())
+            .parse()
+            .expect("Invalid IDEMPOTENCY_TTL_SECS");
+        let lock_timeout_secs: u64 = env::var("IDEMPOTENCY_LOCK_TIMEOUT_SECS")
+            .unwrap_or_else(|_| "60".to_string())
+            .parse()
+            .expect("Invalid IDEMPOTENCY_LOCK_TIMEOUT_SECS");
+
+        Some(Self {
+            ttl: Duration::from_secs(ttl_secs),
+            lock_timeout: Duration::from_secs(lock_timeout_secs),
+        })
+    }
+}
+
+impl Default for IdempotencyConfig {
+    fn default() -> Self {
+        Self {
+            ttl: Duration::from_secs(86400),
+            lock_timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl StoredResponse {
+    fn to_http_response(&self) -> HttpResponse {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::OK);
+        let mut builder = HttpResponse::build(status);
+        for (name, value) in &self.headers {
+            builder.append_header((name.as_str(), value.as_str()));
+        }
+        builder.insert_header((REPLAYED_HEADER, "true"));
+        builder.body(self.body.clone())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IdempotencyRecord {
+    fingerprint: String,
+    response: Option<StoredResponse>,
+}
+
+/// State of a key when a request tries to claim it.
+#[derive(Debug)]
+pub enum IdempotencyOutcome {
+    /// The key was free and is now locked by this request.
+    Started,
+    InFlight {
+        fingerprint: String,
+    },
+    Completed {
+        fingerprint: String,
+        response: StoredResponse,
+    },
+}
+
+#[derive(Debug)]
+pub enum IdempotencyError {
+    Redis(RedisError),
+    Database(sqlx::Error),
+    Codec(String),
+}
+
+impl fmt::Display for IdempotencyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IdempotencyError::Redis(e) => write!(f, "idempotency backend error: {}", e),
+            IdempotencyError::Database(e) => write!(f, "idempotency backend error: {}", e),
+            IdempotencyError::Codec(e) => write!(f, "idempotency codec error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for IdempotencyError {}
+
+impl From<RedisError> for IdempotencyError {
+    fn from(err: RedisError) -> Self {
+        IdempotencyError::Redis(err)
+    }
+}
+
+impl From<sqlx::Error> for IdempotencyError {
+    fn from(err: sqlx::Error) -> Self {
+        IdempotencyError::Database(err)
+    }
+}
+
+pub trait IdempotencyStore: Send + Sync + 'static {
+    /// Atomically locks `key` for `lock_timeout` unless it is already taken.
+    fn begin(
+        &self,
+        key: &str,
+        fingerprint: &str,
+        lock_timeout: Duration,
+    ) -> impl Future<Output = Result<IdempotencyOutcome, IdempotencyError>>;
+
+    /// Stores the response to replay for `ttl`, as long as `key` is still
+    /// locked by the request with this `fingerprint`.
+    fn complete(
+        &self,
+        key: &str,
+        fingerprint: &str,
+        response: &StoredResponse,
+        ttl: Duration,
+    ) -> impl Future<Output = Result<(), IdempotencyError>>;
+
+    /// Frees `key` without a response, so the client may retry. Leaves it
+    /// alone once it holds a response or was taken over by a request with
+    /// another `fingerprint` after the lock expired.
+    fn abandon(
+        &self,
+        key: &str,
+        fingerprint: &str,
+    ) -> impl Future<Output = Result<(), IdempotencyError>>;
+}
+
+const BEGIN_SCRIPT: &str = r#"
+if redis.call('SET', KEYS[1], ARGV[1], 'NX', 'PX', ARGV[2]) then
+    return false
+end
+return redis.call('GET', KEYS[1])
+"#;
+
+const COMPLETE_SCRIPT: &str = r#"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    redis.call('SET', KEYS[1], ARGV[2], 'PX', ARGV[3])
+    return 1
+end
+return 0
+"#;
+
+const ABANDON_SCRIPT: &str = r#"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('DEL', KEYS[1])
+end
+return 0
+"#;
+
+pub struct RedisIdempotencyStore {
+    conn: RedisConnection,
+    namespace: String,
+}
+
+impl RedisIdempotencyStore {
+    pub fn new(conn: RedisConnection, namespace: impl Into<String>) -> Self {
+        Self {
+            conn,
+            namespace: namespace.into(),
+        }
+    }
+
+    fn key(&self, key: &str) -> String {
+        format!("{}:idempotency:{}", self.namespace, key)
+    }
+}
+
+fn encode_record(record: &IdempotencyRecord) -> Result<Vec<u8>, IdempotencyError> {
+    rmp_serde::to_vec_named(record).map_err(|e| IdempotencyError::Codec(e.to_string()))
+}
+
+fn decode_record(bytes: &[u8]) -> Result<IdempotencyRecord, IdempotencyError> {
+    rmp_serde::from_slice(bytes).map_err(|e| IdempotencyError::Codec(e.to_string()))
+}
+
+impl IdempotencyStore for RedisIdempotencyStore {
+    async fn begin(
+        &self,
+        key: &str,
+        fingerprint: &str,
+        lock_timeout: Duration,
+    ) -> Result<IdempotencyOutcome, IdempotencyError> {
+        let pending = encode_record(&IdempotencyRecord {
+            fingerprint: fingerprint.to_string(),
+            response: None,
+        })?;
+        let mut conn = self.conn.clone();
+
+        let existing: Option<Vec<u8>> = Script::new(BEGIN_SCRIPT)
+            .key(self.key(key))
+            .arg(pending)
+            .arg(lock_timeout.as_millis() as u64)
+            .invoke_async(&mut conn)
+            .await?;
+
+        let Some(existing) = existing else {
+            return Ok(IdempotencyOutcome::Started);
+        };
+        let record = decode_record(&existing)?;
+        Ok(match record.response {
+            Some(response) => IdempotencyOutcome::Completed {
+                fingerprint: record.fingerprint,
+                response,
+            },
+            None => IdempotencyOutcome::InFlight {
+                fingerprint: record.fingerprint,
+            },
+        })
+    }
+
+    async fn complete(
+        &self,
+        key: &str,
+        fingerprint: &str,
+        response: &StoredResponse,
+        ttl: Duration,
+    ) -> Result<(), IdempotencyError> {
+        // Only over the pending record `begin` wrote for this fingerprint:
+        // once the lock expired the key may belong to another request.
+        let pending = encode_record(&IdempotencyRecord {
+            fingerprint: fingerprint.to_string(),
+            response: None,
+        })?;
+        let record = encode_record(&IdempotencyRecord {
+            fingerprint: fingerprint.to_string(),
+            response: Some(response.clone()),
+        })?;
+        let mut conn = self.conn.clone();
+        Script::new(COMPLETE_SCRIPT)
+            .key(self.key(key))
+            .arg(pending)
+            .arg(record)
+            .arg(ttl.as_millis() as u64)
+            .invoke_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn abandon(&self, key: &str, fingerprint: &str) -> Result<(), IdempotencyError> {
+        // Only the pending record `begin` wrote for this fingerprint.
+        let pending = encode_record(&IdempotencyRecord {
+            fingerprint: fingerprint.to_string(),
+            response: None,
+        })?;
+        let mut conn = self.conn.clone();
+        Script::new(ABANDON_SCRIPT)
+            .key(self.key(key))
+            .arg(pending)
+            .invoke_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+}
+
+/// Stores keys in the `idempotency_keys` table. Expired rows are reclaimed
+/// when their key is reused; `purge_expired`, run by the
+/// `purge_job_records` task, removes the rest.
+pub struct PostgresIdempotencyStore {
+    pool: PgPool,
+}
+
+impl PostgresIdempotencyStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn purge_expired(&self) -> Result<u64, sqlx::Error> {
+        let result = DbExecutor::from(&self.pool)
+            .execute(sqlx::query(
+                "DELETE FROM idempotency_keys WHERE expires_at < NOW()",
+            ))
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct IdempotencyRow {
+    fingerprint: String,
+    status_code: Option<i32>,
+    headers: Option<sqlx::types::Json<Vec<(String, String)>>>,
+    body: Option<Vec<u8>>,
+}
+
+impl IdempotencyStore for PostgresIdempotencyStore {
+    async fn begin(
+        &self,
+        key: &str,
+        fingerprint: &str,
+        lock_timeout: Duration,
+    ) -> Result<IdempotencyOutcome, IdempotencyError> {
+        let lock_timeout_ms = lock_timeout.as_millis() as i64;
+
+        let claimed = DbExecutor::from(&self.pool)
+            .fetch_optional(
+                sqlx::query_as::<_, (String,)>(
+                    "INSERT INTO idempotency_keys (key, fingerprint, expires_at) \
+                     VALUES ($1, $2, NOW() + $3 * INTERVAL '1 millisecond') \
+                     ON CONFLICT (key) DO UPDATE \
+                         SET fingerprint = EXCLUDED.fingerprint, status_code = NULL, \
+                             headers = NULL, body = NULL, created_at = NOW(), \
+                             expires_at = EXCLUDED.expires_at \
+                         WHERE idempotency_keys.expires_at < NOW() \
+                     RETURNING key",
+                )
+                .bind(key)
+                .bind(fingerprint)
+                .bind(lock_timeout_ms),
+            )
+            .await?;
+        if claimed.is_some() {
+            return Ok(IdempotencyOutcome::Started);
+        }
+
+        let row = DbExecutor::from(&self.pool)
+            .fetch_optional(
+                sqlx::query_as::<_, IdempotencyRow>(
+                    "SELECT fingerprint, status_code, headers, body \
+                     FROM idempotency_keys WHERE key = $1",
+                )
+                .bind(key),
+            )
+            .await?;
+
+        // The row expired and was purged in between: the key is free again.
+        let Some(row) = row else {
+            return Box::pin(self.begin(key, fingerprint, lock_timeout)).await;
+        };
+
+        Ok(match row.status_code {
+            Some(status) => IdempotencyOutcome::Completed {
+                fingerprint: row.fingerprint,
+                response: StoredResponse {
+                    status: status as u16,
+                    headers: row.headers.map(|headers| headers.0).unwrap_or_default(),
+                    body: row.body.unwrap_or_default(),
+                },
+            },
+            None => IdempotencyOutcome::InFlight {
+                fingerprint: row.fingerprint,
+            },
+        })
+    }
+
+    async fn complete(
+        &self,
+        key: &str,
+        fingerprint: &str,
+        response: &StoredResponse,
+        ttl: Duration,
+    ) -> Result<(), IdempotencyError> {
+        DbExecutor::from(&self.pool)
+            .execute(
+                sqlx::query(
+                    "UPDATE idempotency_keys \
+                     SET status_code = $3, headers = $4, body = $5, \
+                         expires_at = NOW() + $6 * INTERVAL '1 millisecond' \
+                     WHERE key = $1 AND fingerprint = $2",
+                )
+                .bind(key)
+                .bind(fingerprint)
+                .bind(response.status as i32)
+                .bind(sqlx::types::Json(&response.headers))
+                .bind(&response.body)
+                .bind(ttl.as_millis() as i64),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn abandon(&self, key: &str, fingerprint: &str) -> Result<(), IdempotencyError> {
+        DbExecutor::from(&self.pool)
+            .execute(
+                sqlx::query(
+                    "DELETE FROM idempotency_keys \
+                     WHERE key = $1 AND fingerprint = $2 AND status_code IS NULL",
+                )
+                .bind(key)
+                .bind(fingerprint),
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+/// Either store, so the backend can be picked at startup with
+/// `IDEMPOTENCY_BACKEND`.
+pub enum IdempotencyBackend {
+    Redis(RedisIdempotencyStore),
+    Postgres(PostgresIdempotencyStore),
+}
+
+impl IdempotencyBackend {
+    pub fn from_env(conn: RedisConnection, pool: PgPool, namespace: &str) -> Self {
+        let backend = env::var("IDEMPOTENCY_BACKEND").unwrap_or_else(|_| "redis".to_string());
+        match backend.to_lowercase().as_str() {
+            "redis" => IdempotencyBackend::Redis(RedisIdempotencyStore::new(conn, namespace)),
+            "postgres" => IdempotencyBackend::Postgres(PostgresIdempotencyStore::new(pool)),
+            other => panic!("Invalid IDEMPOTENCY_BACKEND: {}", other),
+        }
+    }
+}
+
+impl IdempotencyStore for IdempotencyBackend {
+    async fn begin(
+        &self,
+        key: &str,
+        fingerprint: &str,
+        lock_timeout: Duration,
+    ) -> Result<IdempotencyOutcome, IdempotencyError> {
+        match self {
+            IdempotencyBackend::Redis(store) => store.begin(key, fingerprint, lock_timeout).await,
+            IdempotencyBackend::Postgres(store) => {
+                store.begin(key, fingerprint, lock_timeout).await
+            }
+        }
+    }
+
+    async fn complete(
+        &self,
+        key: &str,
+        fingerprint: &str,
+        response: &StoredResponse,
+        ttl: Duration,
+    ) -> Result<(), IdempotencyError> {
+        match self {
+            IdempotencyBackend::Redis(store) => {
+                store.complete(key, fingerprint, response, ttl).await
+            }
+            IdempotencyBackend::Postgres(store) => {
+                store.complete(key, fingerprint, response, ttl).await
+            }
+        }
+    }
+
+    async fn abandon(&self, key: &str, fingerprint: &str) -> Result<(), IdempotencyError> {
+        match self {
+            IdempotencyBackend::Redis(store) => store.abandon(key, fingerprint).await,
+            IdempotencyBackend::Postgres(store) => store.abandon(key, fingerprint).await,
+        }
+    }
+}
+
+/// Middleware honoring the `Idempotency-Key` header on POST, PUT and PATCH.
+///
+/// The first request with a key runs and its response is stored; repeats
+/// with the same method, path and body get that response back with an
+/// `Idempotent-Replayed: true` header. A repeat while the first request is
+/// still running gets a 409, and a key reused for a different request a 422.
+/// Server errors are not stored, so the client may retry them.
+///
+/// Keys are global to the app; clients should use random keys such as UUIDs.
+/// If the store is unavailable requests with a key are rejected with a 503
+/// rather than risk running twice.
+pub struct Idempotency<St: IdempotencyStore> {
+    store: Arc<St>,
+    config: Rc<IdempotencyConfig>,
+}
+
+impl<St: IdempotencyStore> Idempotency<St> {
+    pub fn new(store: Arc<St>, config: IdempotencyConfig) -> Self {
+        Self {
+            store,
+            config: Rc::new(config),
+        }
+    }
+}
+
+impl<S, B, St> Transform<S, ServiceRequest> for Idempotency<St>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+    B::Error: Into<Box<dyn StdError>>,
+    St: IdempotencyStore,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = IdempotencyMiddleware<S, St>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(IdempotencyMiddleware {
+            service: Rc::new(service),
+            store: self.store.clone(),
+            config: self.config.clone(),
+        }))
+    }
+}
+
+pub struct IdempotencyMiddleware<S, St> {
+    service: Rc<S>,
+    store: Arc<St>,
+    config: Rc<IdempotencyConfig>,
+}
+
+fn error_response<B>(
+    req: ServiceRequest,
+    code: u16,
+    status: StatusCode,
+) -> ServiceResponse<EitherBody<B>> {
+    let response = AppError::new(code, Some(status)).http_response_builder();
+    req.into_response(response).map_into_right_body()
+}
+
+fn fingerprint(req: &ServiceRequest, body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(req.method().as_str());
+    hasher.update(b"\n");
+    hasher.update(req.uri().to_string());
+    hasher.update(b"\n");
+    hasher.update(body);
+    hex::encode(hasher.finalize())
+}
+
+/// Headers that describe the connection or the original transfer rather
+/// than the response itself. Cookies are left out too, since a session
+/// cookie must never be handed to whoever replays the key.
+fn is_replayable_header(name: &HeaderName) -> bool {
+    !matches!(
+        *name,
+        header::CONNECTION
+            | header::CONTENT_LENGTH
+            | header::DATE
+            | header::TRANSFER_ENCODING
+            | header::SET_COOKIE
+    )
+}
+
+impl<S, B, St> Service<ServiceRequest> for IdempotencyMiddleware<S, St>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+    B::Error: Into<Box<dyn StdError>>,
+    St: IdempotencyStore,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let store = self.store.clone();
+        let config = self.config.clone();
+
+        Box::pin(async move {
+            let unsafe_method = matches!(*req.method(), Method::POST | Method::PUT | Method::PATCH);
+            let key = match req.headers().get(IDEMPOTENCY_KEY_HEADER) {
+                Some(key) if unsafe_method => key.to_str().ok().map(str::to_string),
+                _ => return Ok(service.call(req).await?.map_into_left_body()),
+            };
+            let Some(key) = key.filter(|key| !key.is_empty() && key.len() <= MAX_KEY_LENGTH) else {
+                return Ok(error_response(req, 3014, StatusCode::BAD_REQUEST));
+            };
+
+            let body = req.extract::<Bytes>().await?;
+            let fingerprint = fingerprint(&req, &body);
+            req.set_payload(Payload::from(body.clone()));
+
+            let outcome = match store.begin(&key, &fingerprint, config.lock_timeout).await {
+                Ok(outcome) => outcome,
+                Err(e) => {
+                    tracing::error!("Idempotency check for key {} failed: {}", key, e);
+                    return Ok(error_response(req, 1102, StatusCode::SERVICE_UNAVAILABLE));
+                }
+            };
+
+            match outcome {
+                IdempotencyOutcome::Started => {}
+                IdempotencyOutcome::InFlight {
+                    fingerprint: stored,
+                }
+                | IdempotencyOutcome::Completed {
+                    fingerprint: stored,
+                    ..
+                } if stored != fingerprint => {
+                    return Ok(error_response(req, 3013, StatusCode::UNPROCESSABLE_ENTITY));
+                }
+                IdempotencyOutcome::InFlight { .. } => {
+                    return Ok(error_response(req, 3012, StatusCode::CONFLICT));
+                }
+                IdempotencyOutcome::Completed { response, .. } => {
+                    let response = response.to_http_response();
+                    return Ok(req.into_response(response).map_into_right_body());
+                }
+            }
+
+            let res = match service.call(req).await {
+                Ok(res) => res,
+                Err(e) => {
+                    if let Err(e) = store.abandon(&key, &fingerprint).await {
+                        tracing::warn!("Failed to release idempotency key {}: {}", key, e);
+                    }
+                    return Err(e);
+                }
+            };
+
+            if res.status().is_server_error() {
+                if let Err(e) = store.abandon(&key, &fingerprint).await {
+                    tracing::warn!("Failed to release idempotency key {}: {}", key, e);
+                }
+                return Ok(res.map_into_left_body());
+            }
+
+            let (req, res) = res.into_parts();
+            let (head, response_body) = res.into_parts();
+            let response_body = body::to_bytes(response_body)
+                .await
+                .map_err(|e| actix_web::error::ErrorInternalServerError(e.into()))?;
+
+            let stored = StoredResponse {
+                status: head.status().as_u16(),
+                headers: head
+                    .headers()
+                    .iter()
+                    .filter(|(name, _)| is_replayable_header(name))
+                    .filter_map(|(name, value)| {
+                        Some((name.to_string(), value.to_str().ok()?.to_string()))
+                    })
+                    .collect(),
+                body: response_body.to_vec(),
+            };
+            if let Err(e) = store
+                .complete(&key, &fingerprint, &stored, config.ttl)
+                .await
+            {
+                tracing::warn!(
+                    "Failed to store response for idempotency key {}: {}",
+                    key,
+                    e
+                );
+            }
+
+            let res = head.set_body(response_body).map_into_boxed_body();
+            Ok(ServiceResponse::new(req, res).map_into_right_body())
+        })
+    }
+}
@@ -0,0 +1,4 @@
+pub mod admin;
+pub mod idempotency;
+pub mod rate_limit;
+pub mod session;
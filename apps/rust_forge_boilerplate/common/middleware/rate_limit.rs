@@ -0,0 +1,738 @@
+use actix_web::{
+    Error, HttpMessage,
+    body::EitherBody,
+    dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+    http::{
+        StatusCode,
+        header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER},
+    },
+};
+use futures::future::LocalBoxFuture;
+use redis::{RedisError, Script};
+use std::{
+    collections::HashMap,
+    env, fmt,
+    future::{Future, Ready, ready},
+    rc::Rc,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+use crate::common::{infrastructure::redis::RedisConnection, utils::error::AppError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitAlgorithm {
+    /// Allows bursts of up to `limit` requests, refilled evenly over `period`.
+    TokenBucket,
+    /// At most `limit` requests in any `period`-long window.
+    SlidingWindow,
+}
+
+impl FromStr for RateLimitAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "token_bucket" => Ok(RateLimitAlgorithm::TokenBucket),
+            "sliding_window" => Ok(RateLimitAlgorithm::SlidingWindow),
+            other => Err(format!("unknown rate limit algorithm: {}", other)),
+        }
+    }
+}
+
+/// Whose budget a request is counted against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitKey {
+    Ip,
+    /// The `RateLimitApiKey` request extension, falling back to the IP when
+    /// absent. API key authentication wrapped outside the limiter sets it
+    /// once the key is validated; without that middleware every request is
+    /// limited by IP. The raw `X-API-Key` header is never used, as callers
+    /// could send a new made-up key for a fresh budget.
+    ApiKey,
+    /// The `RateLimitUser` request extension, falling back to the IP when
+    /// absent. Authentication middleware wrapped outside the limiter sets it.
+    User,
+    /// One budget shared by every caller.
+    Global,
+}
+
+impl FromStr for RateLimitKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ip" => Ok(RateLimitKey::Ip),
+            "api_key" => Ok(RateLimitKey::ApiKey),
+            "user" => Ok(RateLimitKey::User),
+            "global" => Ok(RateLimitKey::Global),
+            other => Err(format!("unknown rate limit key: {}", other)),
+        }
+    }
+}
+
+/// Identity of the authenticated caller, used by `RateLimitKey::User`.
+#[derive(Debug, Clone)]
+pub struct RateLimitUser(pub String);
+
+/// Id of the validated API key the request came with, used by
+/// `RateLimitKey::ApiKey`.
+#[derive(Debug, Clone)]
+pub struct RateLimitApiKey(pub String);
+
+#[derive(Debug, Clone)]
+pub struct RateLimitPolicy {
+    /// Part of the storage key, so policies never share a budget.
+    pub name: String,
+    pub algorithm: RateLimitAlgorithm,
+    pub limit: u64,
+    pub period: Duration,
+    pub key: RateLimitKey,
+    /// Gives every route pattern under the wrapped scope its own budget.
+    pub per_route: bool,
+    /// Reads the client IP from `Forwarded`/`X-Forwarded-For`. Only enable
+    /// behind a proxy that overwrites those headers.
+    pub trust_proxy: bool,
+    /// Catalog code of the 429 response.
+    pub error_code: u16,
+}
+
+impl Default for RateLimitPolicy {
+    fn default() -> Self {
+        Self::new(
+            "default",
+            RateLimitAlgorithm::TokenBucket,
+            100,
+            Duration::from_secs(60),
+        )
+    }
+}
+
+impl RateLimitPolicy {
+    /// Panics unless `limit` and `period` are positive; a zero rate has no
+    /// refill time.
+    pub fn new(
+        name: impl Into<String>,
+        algorithm: RateLimitAlgorithm,
+        limit: u64,
+        period: Duration,
+    ) -> Self {
+        assert!(
+            limit > 0 && !period.is_zero(),
+            "A rate limit policy needs a positive limit and period"
+        );
+        Self {
+            name: name.into(),
+            algorithm,
+            limit,
+            period,
+            key: RateLimitKey::Ip,
+            per_route: false,
+            trust_proxy: false,
+            error_code: 1111,
+        }
+    }
+
+    pub fn key(mut self, key: RateLimitKey) -> Self {
+        self.key = key;
+        self
+    }
+
+    pub fn per_route(mut self) -> Self {
+        self.per_route = true;
+        self
+    }
+
+    pub fn trust_proxy(mut self, trust_proxy: bool) -> Self {
+        self.trust_proxy = trust_proxy;
+        self
+    }
+
+    pub fn error_code(mut self, error_code: u16) -> Self {
+        self.error_code = error_code;
+        self
+    }
+
+    /// The app-wide policy, or `None` unless `RATE_LIMIT_ENABLED=true`.
+    pub fn from_env() -> Option<Self> {
+        let enabled: bool = env::var("RATE_LIMIT_ENABLED")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .expect("Invalid RATE_LIMIT_ENABLED");
+        if !enabled {
+            return None;
+        }
+
+        let algorithm: RateLimitAlgorithm = env::var("RATE_LIMIT_ALGORITHM")
+            .unwrap_or_else(|_| "token_bucket".to_string())
+            .parse()
+            .expect("Invalid RATE_LIMIT_ALGORITHM");
+        let limit: u64 = env::var("RATE_LIMIT_LIMIT")
+            .unwrap_or_else(|_| "100".to_string())
+            .parse()
+            .ok()
+            .filter(|limit| *limit > 0)
+            .expect("Invalid RATE_LIMIT_LIMIT");
+        let period_secs: u64 = env::var("RATE_LIMIT_PERIOD_SECS")
+            .unwrap_or_else(|_| "60".to_string())
+            .parse()
+            .ok()
+            .filter(|period_secs| *period_secs > 0)
+            .expect("Invalid RATE_LIMIT_PERIOD_SECS");
+        let key: RateLimitKey = env::var("RATE_LIMIT_KEY")
+            .unwrap_or_else(|_| "ip".to_string())
+            .parse()
+            .expect("Invalid RATE_LIMIT_KEY");
+        let trust_proxy: bool = env::var("RATE_LIMIT_TRUST_PROXY")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .expect("Invalid RATE_LIMIT_TRUST_PROXY");
+
+        Some(
+            Self::new(
+                "default",
+                algorithm,
+                limit,
+                Duration::from_secs(period_secs),
+            )
+            .key(key)
+            .trust_proxy(trust_proxy),
+        )
+    }
+
+    fn client_ip(&self, req: &ServiceRequest) -> String {
+        if self.trust_proxy
+            && let Some(ip) = req.connection_info().realip_remote_addr()
+        {
+            return ip.to_string();
+        }
+        req.peer_addr()
+            .map(|addr| addr.ip().to_string())
+            .unwrap_or_else(|| "unknown".to_string())
+    }
+
+    fn bucket_key(&self, req: &ServiceRequest) -> String {
+        let subject = match self.key {
+            RateLimitKey::Ip => format!("ip:{}", self.client_ip(req)),
+            RateLimitKey::ApiKey => match req.extensions().get::<RateLimitApiKey>() {
+                Some(api_key) => format!("key:{}", api_key.0),
+                None => format!("ip:{}", self.client_ip(req)),
+            },
+            RateLimitKey::User => match req.extensions().get::<RateLimitUser>() {
+                Some(user) => format!("user:{}", user.0),
+                None => format!("ip:{}", self.client_ip(req)),
+            },
+            RateLimitKey::Global => "global".to_string(),
+        };
+
+        if self.per_route {
+            // Unmatched paths share one budget instead of one per raw path.
+            let route = req
+                .match_pattern()
+                .unwrap_or_else(|| "unmatched".to_string());
+            format!("{}:{}:{}", self.name, route, subject)
+        } else {
+            format!("{}:{}", self.name, subject)
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RateLimitDecision {
+    pub allowed: bool,
+    pub limit: u64,
+    pub remaining: u64,
+    /// Until the budget is full again.
+    pub reset_after: Duration,
+    /// Until the next request would be allowed; zero when allowed.
+    pub retry_after: Duration,
+}
+
+impl RateLimitDecision {
+    fn write_headers(&self, policy: &RateLimitPolicy, headers: &mut HeaderMap) {
+        let values = [
+            ("ratelimit-limit", self.limit.to_string()),
+            ("ratelimit-remaining", self.remaining.to_string()),
+            ("ratelimit-reset", ceil_secs(self.reset_after).to_string()),
+            (
+                "ratelimit-policy",
+                format!("{};w={}", policy.limit, policy.period.as_secs()),
+            ),
+        ];
+        for (name, value) in values {
+            if let Ok(value) = HeaderValue::from_str(&value) {
+                headers.insert(HeaderName::from_static(name), value);
+            }
+        }
+
+        if !self.allowed {
+            headers.insert(RETRY_AFTER, HeaderValue::from(ceil_secs(self.retry_after)));
+        }
+    }
+}
+
+fn ceil_secs(duration: Duration) -> u64 {
+    duration.as_millis().div_ceil(1000) as u64
+}
+
+#[derive(Debug)]
+pub enum RateLimitError {
+    Redis(RedisError),
+}
+
+impl fmt::Display for RateLimitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RateLimitError::Redis(e) => write!(f, "rate limit backend error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for RateLimitError {}
+
+impl From<RedisError> for RateLimitError {
+    fn from(err: RedisError) -> Self {
+        RateLimitError::Redis(err)
+    }
+}
+
+/// Counts a request against `key` and says whether it is allowed.
+pub trait RateLimitStore: Send + Sync + 'static {
+    fn check(
+        &self,
+        key: &str,
+        policy: &RateLimitPolicy,
+    ) -> impl Future<Output = Result<RateLimitDecision, RateLimitError>>;
+}
+
+const TOKEN_BUCKET_SCRIPT: &str = r#"
+local capacity = tonumber(ARGV[1])
+local period_ms = tonumber(ARGV[2])
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+
+local state = redis.call('HMGET', KEYS[1], 'tokens', 'ts')
+local tokens = tonumber(state[1]) or capacity
+local ts = tonumber(state[2]) or now
+local rate = capacity / period_ms
+
+tokens = math.min(capacity, tokens + math.max(0, now - ts) * rate)
+local allowed = 0
+local retry_after = 0
+if tokens >= 1 then
+    tokens = tokens - 1
+    allowed = 1
+else
+    retry_after = math.ceil((1 - tokens) / rate)
+end
+
+redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'ts', now)
+redis.call('PEXPIRE', KEYS[1], period_ms)
+
+return {allowed, math.floor(tokens), math.ceil((capacity - tokens) / rate), retry_after}
+"#;
+
+const SLIDING_WINDOW_SCRIPT: &str = r#"
+local limit = tonumber(ARGV[1])
+local window_ms = tonumber(ARGV[2])
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+
+redis.call('ZREMRANGEBYSCORE', KEYS[1], '-inf', now - window_ms)
+local count = redis.call('ZCARD', KEYS[1])
+local allowed = 0
+if count < limit then
+    redis.call('ZADD', KEYS[1], now, ARGV[3])
+    redis.call('PEXPIRE', KEYS[1], window_ms)
+    count = count + 1
+    allowed = 1
+end
+
+local oldest = redis.call('ZRANGE', KEYS[1], 0, 0, 'WITHSCORES')
+local reset_after = 0
+if oldest[2] then
+    reset_after = tonumber(oldest[2]) + window_ms - now
+end
+local retry_after = 0
+if allowed == 0 then
+    retry_after = reset_after
+end
+
+return {allowed, limit - count, reset_after, retry_after}
+"#;
+
+/// Shared limits across replicas. Each check is one Lua script, so
+/// concurrent requests never both take the last token.
+pub struct RedisRateLimitStore {
+    conn: RedisConnection,
+    namespace: String,
+}
+
+impl RedisRateLimitStore {
+    pub fn new(conn: RedisConnection, namespace: impl Into<String>) -> Self {
+        Self {
+            conn,
+            namespace: namespace.into(),
+        }
+    }
+}
+
+impl RateLimitStore for RedisRateLimitStore {
+    async fn check(
+        &self,
+        key: &str,
+        policy: &RateLimitPolicy,
+    ) -> Result<RateLimitDecision, RateLimitError> {
+        let key = format!("{}:ratelimit:{}", self.namespace, key);
+        let period_ms = policy.period.as_millis() as u64;
+
+        let script = match policy.algorithm {
+            RateLimitAlgorithm::TokenBucket => Script::new(TOKEN_BUCKET_SCRIPT),
+            RateLimitAlgorithm::SlidingWindow => Script::new(SLIDING_WINDOW_SCRIPT),
+        };
+        let mut invocation = script.key(key);
+        invocation.arg(policy.limit).arg(period_ms);
+        if policy.algorithm == RateLimitAlgorithm::SlidingWindow {
+            invocation.arg(Uuid::new_v4().to_string());
+        }
+
+        let mut conn = self.conn.clone();
+        let (allowed, remaining, reset_after_ms, retry_after_ms): (i64, i64, i64, i64) =
+            invocation.invoke_async(&mut conn).await?;
+
+        Ok(RateLimitDecision {
+            allowed: allowed == 1,
+            limit: policy.limit,
+            remaining: remaining.max(0) as u64,
+            reset_after: Duration::from_millis(reset_after_ms.max(0) as u64),
+            retry_after: Duration::from_millis(retry_after_ms.max(0) as u64),
+        })
+    }
+}
+
+enum BucketState {
+    Tokens { tokens: f64, updated_at: Instant },
+    Window { hits: Vec<Instant> },
+}
+
+struct InMemoryBucket {
+    state: BucketState,
+    expires_at: Instant,
+}
+
+/// Process-local `RateLimitStore` with the same algorithms as
+/// `RedisRateLimitStore`, for single-node setups and tests.
+#[derive(Default)]
+pub struct InMemoryRateLimitStore {
+    buckets: Mutex<HashMap<String, InMemoryBucket>>,
+}
+
+impl InMemoryRateLimitStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn check_now(&self, key: &str, policy: &RateLimitPolicy, now: Instant) -> RateLimitDecision {
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        if buckets.len() >= 10_000 {
+            buckets.retain(|_, bucket| bucket.expires_at > now);
+        }
+
+        let limit = policy.limit;
+        let period = policy.period;
+        let bucket = buckets
+            .entry(key.to_string())
+            .or_insert_with(|| InMemoryBucket {
+                state: match policy.algorithm {
+                    RateLimitAlgorithm::TokenBucket => BucketState::Tokens {
+                        tokens: limit as f64,
+                        updated_at: now,
+                    },
+                    RateLimitAlgorithm::SlidingWindow => BucketState::Window { hits: Vec::new() },
+                },
+                expires_at: now + period,
+            });
+        bucket.expires_at = now + period;
+
+        match &mut bucket.state {
+            BucketState::Tokens { tokens, updated_at } => {
+                let rate = limit as f64 / period.as_secs_f64();
+                *tokens = (*tokens + now.duration_since(*updated_at).as_secs_f64() * rate)
+                    .min(limit as f64);
+                *updated_at = now;
+
+                let allowed = *tokens >= 1.0;
+                let retry_after = if allowed {
+                    *tokens -= 1.0;
+                    Duration::ZERO
+                } else {
+                    Duration::from_secs_f64((1.0 - *tokens) / rate)
+                };
+
+                RateLimitDecision {
+                    allowed,
+                    limit,
+                    remaining: tokens.floor() as u64,
+                    reset_after: Duration::from_secs_f64((limit as f64 - *tokens) / rate),
+                    retry_after,
+                }
+            }
+            BucketState::Window { hits } => {
+                hits.retain(|hit| now.duration_since(*hit) < period);
+                let allowed = (hits.len() as u64) < limit;
+                if allowed {
+                    hits.push(now);
+                }
+
+                let reset_after = hits
+                    .first()
+                    .map(|oldest| period.saturating_sub(now.duration_since(*oldest)))
+                    .unwrap_or_default();
+
+                RateLimitDecision {
+                    allowed,
+                    limit,
+                    remaining: limit.saturating_sub(hits.len() as u64),
+                    reset_after,
+                    retry_after: if allowed { Duration::ZERO } else { reset_after },
+                }
+            }
+        }
+    }
+}
+
+impl RateLimitStore for InMemoryRateLimitStore {
+    async fn check(
+        &self,
+        key: &str,
+        policy: &RateLimitPolicy,
+    ) -> Result<RateLimitDecision, RateLimitError> {
+        Ok(self.check_now(key, policy, Instant::now()))
+    }
+}
+
+/// Either store, so the backend can be picked at startup with
+/// `RATE_LIMIT_BACKEND`.
+pub enum RateLimitBackend {
+    Redis(RedisRateLimitStore),
+    InMemory(InMemoryRateLimitStore),
+}
+
+impl RateLimitBackend {
+    pub fn from_env(conn: RedisConnection, namespace: &str) -> Self {
+        let backend = env::var("RATE_LIMIT_BACKEND").unwrap_or_else(|_| "redis".to_string());
+        match backend.to_lowercase().as_str() {
+            "redis" => RateLimitBackend::Redis(RedisRateLimitStore::new(conn, namespace)),
+            "memory" => RateLimitBackend::InMemory(InMemoryRateLimitStore::new()),
+            other => panic!("Invalid RATE_LIMIT_BACKEND: {}", other),
+        }
+    }
+}
+
+impl RateLimitStore for RateLimitBackend {
+    async fn check(
+        &self,
+        key: &str,
+        policy: &RateLimitPolicy,
+    ) -> Result<RateLimitDecision, RateLimitError> {
+        match self {
+            RateLimitBackend::Redis(store) => store.check(key, policy).await,
+            RateLimitBackend::InMemory(store) => store.check(key, policy).await,
+        }
+    }
+}
+
+/// Middleware enforcing one `RateLimitPolicy`. Wrap the whole app for a
+/// global limit, or individual scopes and resources for per-route limits.
+///
+/// If the store fails the request is let through: an unavailable Redis
+/// should not take the API down with it.
+pub struct RateLimiter<St: RateLimitStore> {
+    store: Arc<St>,
+    policy: Rc<RateLimitPolicy>,
+}
+
+impl<St: RateLimitStore> RateLimiter<St> {
+    pub fn new(store: Arc<St>, policy: RateLimitPolicy) -> Self {
+        Self {
+            store,
+            policy: Rc::new(policy),
+        }
+    }
+}
+
+impl<S, B, St> Transform<S, ServiceRequest> for RateLimiter<St>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+    St: RateLimitStore,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RateLimiterMiddleware<S, St>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimiterMiddleware {
+            service: Rc::new(service),
+            store: self.store.clone(),
+            policy: self.policy.clone(),
+        }))
+    }
+}
+
+pub struct RateLimiterMiddleware<S, St> {
+    service: Rc<S>,
+    store: Arc<St>,
+    policy: Rc<RateLimitPolicy>,
+}
+
+impl<S, B, St> Service<ServiceRequest> for RateLimiterMiddleware<S, St>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+    St: RateLimitStore,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let store = self.store.clone();
+        let policy = self.policy.clone();
+
+        Box::pin(async move {
+            let key = policy.bucket_key(&req);
+            let decision = match store.check(&key, &policy).await {
+                Ok(decision) => Some(decision),
+                Err(e) => {
+                    tracing::warn!("Rate limit check for {} failed, allowing: {}", key, e);
+                    None
+                }
+            };
+
+            if let Some(decision) = decision.as_ref().filter(|decision| !decision.allowed) {
+                let mut response =
+                    AppError::new(policy.error_code, Some(StatusCode::TOO_MANY_REQUESTS))
+                        .http_response_builder();
+                decision.write_headers(&policy, response.headers_mut());
+                return Ok(req.into_response(response).map_into_right_body());
+            }
+
+            let mut res = service.call(req).await?;
+            if let Some(decision) = decision {
+                decision.write_headers(&policy, res.headers_mut());
+            }
+            Ok(res.map_into_left_body())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(algorithm: RateLimitAlgorithm) -> RateLimitPolicy {
+        RateLimitPolicy::new("test", algorithm, 2, Duration::from_secs(10))
+    }
+
+    #[test]
+    fn token_bucket_refills_at_limit_per_period() {
+        let store = InMemoryRateLimitStore::new();
+        let policy = policy(RateLimitAlgorithm::TokenBucket);
+        let start = Instant::now();
+
+        assert!(store.check_now("k", &policy, start).allowed);
+        let second = store.check_now("k", &policy, start);
+        assert!(second.allowed);
+        assert_eq!(second.remaining, 0);
+        assert_eq!(second.reset_after, Duration::from_secs(10));
+
+        let denied = store.check_now("k", &policy, start);
+        assert!(!denied.allowed);
+        assert_eq!(denied.retry_after, Duration::from_secs(5));
+
+        // One token refills every five seconds.
+        assert!(
+            store
+                .check_now("k", &policy, start + Duration::from_secs(5))
+                .allowed
+        );
+        assert!(
+            !store
+                .check_now("k", &policy, start + Duration::from_secs(5))
+                .allowed
+        );
+    }
+
+    #[test]
+    fn sliding_window_frees_hits_after_the_period() {
+        let store = InMemoryRateLimitStore::new();
+        let policy = policy(RateLimitAlgorithm::SlidingWindow);
+        let start = Instant::now();
+
+        assert!(store.check_now("k", &policy, start).allowed);
+        assert!(
+            store
+                .check_now("k", &policy, start + Duration::from_secs(4))
+                .allowed
+        );
+
+        let denied = store.check_now("k", &policy, start + Duration::from_secs(6));
+        assert!(!denied.allowed);
+        assert_eq!(denied.remaining, 0);
+        assert_eq!(denied.retry_after, Duration::from_secs(4));
+
+        let freed = store.check_now("k", &policy, start + Duration::from_secs(10));
+        assert!(freed.allowed);
+        assert_eq!(freed.remaining, 0);
+    }
+
+    #[test]
+    fn keys_are_limited_separately() {
+        let store = InMemoryRateLimitStore::new();
+        let policy = policy(RateLimitAlgorithm::SlidingWindow);
+        let now = Instant::now();
+
+        store.check_now("a", &policy, now);
+        store.check_now("a", &policy, now);
+        assert!(!store.check_now("a", &policy, now).allowed);
+        assert_eq!(store.check_now("b", &policy, now).remaining, 1);
+    }
+
+    #[test]
+    fn api_keys_count_only_once_validated() {
+        let policy = policy(RateLimitAlgorithm::TokenBucket).key(RateLimitKey::ApiKey);
+
+        let unvalidated = actix_web::test::TestRequest::default()
+            .insert_header(("x-api-key", "made-up"))
+            .peer_addr("10.0.0.1:4000".parse().unwrap())
+            .to_srv_request();
+        assert_eq!(policy.bucket_key(&unvalidated), "test:ip:10.0.0.1");
+
+        let validated = actix_web::test::TestRequest::default()
+            .peer_addr("10.0.0.1:4000".parse().unwrap())
+            .to_srv_request();
+        validated
+            .extensions_mut()
+            .insert(RateLimitApiKey("key-1".to_string()));
+        assert_eq!(policy.bucket_key(&validated), "test:key:key-1");
+    }
+
+    #[test]
+    #[should_panic(expected = "positive limit and period")]
+    fn zero_limit_is_rejected() {
+        RateLimitPolicy::new(
+            "test",
+            RateLimitAlgorithm::TokenBucket,
+            0,
+            Duration::from_secs(10),
+        );
+    }
+}
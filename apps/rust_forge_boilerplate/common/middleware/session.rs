@@ -0,0 +1,618 @@
+use actix_web::{
+    Error, FromRequest, HttpMessage, HttpRequest,
+    cookie::{Cookie, CookieJar, Key, SameSite, time::Duration as CookieDuration},
+    dev::{Payload, Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+};
+use chrono::{DateTime, Utc};
+use futures::future::LocalBoxFuture;
+use redis::{AsyncCommands, RedisError};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use sha2::{Digest, Sha256};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    env, fmt,
+    future::{Ready, ready},
+    rc::Rc,
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::common::{infrastructure::redis::RedisConnection, utils::error::AppError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CookieMode {
+    /// Readable by the client but tamper-proof.
+    Signed,
+    /// Encrypted and authenticated.
+    Private,
+}
+
+impl FromStr for CookieMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "signed" => Ok(CookieMode::Signed),
+            "private" => Ok(CookieMode::Private),
+            other => Err(format!("unknown session cookie mode: {}", other)),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct SessionConfig {
+    pub cookie_name: String,
+    pub cookie_mode: CookieMode,
+    pub cookie_secure: bool,
+    pub cookie_same_site: SameSite,
+    pub key: Key,
+    pub ttl: Duration,
+    /// Pushes the expiry back on every request instead of only on writes.
+    pub rolling: bool,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            cookie_name: "session".to_string(),
+            cookie_mode: CookieMode::Private,
+            cookie_secure: true,
+            cookie_same_site: SameSite::Lax,
+            key: Key::generate(),
+            ttl: Duration::from_secs(86400),
+            rolling: true,
+        }
+    }
+}
+
+impl SessionConfig {
+    /// Returns `None` unless `SESSION_ENABLED=true`.
+    ///
+    /// `SESSION_SECRET` must be at least 32 bytes and shared by all replicas;
+    /// without it a random key is used and sessions end on restart.
+    pub fn from_env() -> Option<Self> {
+        let enabled: bool = env::var("SESSION_ENABLED")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .expect("Invalid SESSION_ENABLED");
+        if !enabled {
+            return None;
+        }
+
+        let key = match env::var("SESSION_SECRET") {
+            Ok(secret) => {
+                assert!(
+                    secret.len() >= 32,
+                    "SESSION_SECRET must be at least 32 bytes"
+                );
+                Key::derive_from(secret.as_bytes())
+            }
+            Err(_) => {
+                tracing::warn!("SESSION_SECRET is not set, sessions will not survive a restart");
+                Key::generate()
+            }
+        };
+        let cookie_mode: CookieMode = env::var("SESSION_COOKIE_MODE")
+            .unwrap_or_else(|_| "private".to_string())
+            .parse()
+            .expect("Invalid SESSION_COOKIE_MODE");
+        let cookie_secure: bool = env::var("SESSION_COOKIE_SECURE")
+            .unwrap_or_else(|_| "true".to_string())
+            .parse()
+            .expect("Invalid SESSION_COOKIE_SECURE");
+        let cookie_same_site = match env::var("SESSION_COOKIE_SAME_SITE")
+            .unwrap_or_else(|_| "lax".to_string())
+            .to_lowercase()
+            .as_str()
+        {
+            "strict" => SameSite::Strict,
+            "lax" => SameSite::Lax,
+            "none" => SameSite::None,
+            other => panic!("Invalid SESSION_COOKIE_SAME_SITE: {}", other),
+        };
+        let ttl_secs: u64 = env::var("SESSION_TTL_SECS")
+            .unwrap_or_else(|_| "86400".to_string())
+            .parse()
+            .expect("Invalid SESSION_TTL_SECS");
+        let rolling: bool = env::var("SESSION_ROLLING")
+            .unwrap_or_else(|_| "true".to_string())
+            .parse()
+            .expect("Invalid SESSION_ROLLING");
+
+        Some(Self {
+            cookie_name: env::var("SESSION_COOKIE_NAME").unwrap_or_else(|_| "session".to_string()),
+            cookie_mode,
+            cookie_secure,
+            cookie_same_site,
+            key,
+            ttl: Duration::from_secs(ttl_secs),
+            rolling,
+        })
+    }
+
+    fn read_cookie(&self, req: &ServiceRequest) -> Option<String> {
+        let cookie = req.cookie(&self.cookie_name)?;
+        let mut jar = CookieJar::new();
+        jar.add_original(cookie);
+
+        let cookie = match self.cookie_mode {
+            CookieMode::Signed => jar.signed(&self.key).get(&self.cookie_name),
+            CookieMode::Private => jar.private(&self.key).get(&self.cookie_name),
+        };
+        cookie.map(|cookie| cookie.value().to_string())
+    }
+
+    fn write_cookie<B>(&self, res: &mut ServiceResponse<B>, session_id: Option<&str>) {
+        let mut cookie = Cookie::build(
+            self.cookie_name.clone(),
+            session_id.unwrap_or_default().to_string(),
+        )
+        .path("/")
+        .http_only(true)
+        .secure(self.cookie_secure)
+        .same_site(self.cookie_same_site)
+        .finish();
+
+        if session_id.is_some() {
+            cookie.set_max_age(CookieDuration::seconds(self.ttl.as_secs() as i64));
+        } else {
+            cookie.make_removal();
+        }
+
+        let mut jar = CookieJar::new();
+        match self.cookie_mode {
+            CookieMode::Signed => jar.signed_mut(&self.key).add(cookie),
+            CookieMode::Private => jar.private_mut(&self.key).add(cookie),
+        }
+        for cookie in jar.delta() {
+            if let Err(e) = res.response_mut().add_cookie(cookie) {
+                tracing::warn!("Failed to set session cookie: {}", e);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionRecord {
+    pub user_id: Option<String>,
+    pub data: HashMap<String, serde_json::Value>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+}
+
+impl SessionRecord {
+    fn new(req: &ServiceRequest) -> Self {
+        let now = Utc::now();
+        Self {
+            user_id: None,
+            data: HashMap::new(),
+            ip: req.peer_addr().map(|addr| addr.ip().to_string()),
+            user_agent: req
+                .headers()
+                .get("user-agent")
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string),
+            created_at: now,
+            last_seen_at: now,
+        }
+    }
+}
+
+/// One of a user's sessions, as listed by `RedisSessionStore`.
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionInfo {
+    /// Names the session for `revoke_session`. The session id itself is the
+    /// cookie's credential and is never listed.
+    pub handle: String,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+pub enum SessionError {
+    Redis(RedisError),
+    Codec(String),
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SessionError::Redis(e) => write!(f, "session store error: {}", e),
+            SessionError::Codec(e) => write!(f, "session codec error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SessionError {}
+
+impl From<RedisError> for SessionError {
+    fn from(err: RedisError) -> Self {
+        SessionError::Redis(err)
+    }
+}
+
+/// Sessions in Redis, one key per session plus one set of session ids per
+/// user so all of a user's sessions can be listed and revoked.
+pub struct RedisSessionStore {
+    conn: RedisConnection,
+    namespace: String,
+}
+
+impl RedisSessionStore {
+    pub fn new(conn: RedisConnection, namespace: impl Into<String>) -> Self {
+        Self {
+            conn,
+            namespace: namespace.into(),
+        }
+    }
+
+    fn session_key(&self, session_id: &str) -> String {
+        format!("{}:session:{}", self.namespace, session_id)
+    }
+
+    fn user_key(&self, user_id: &str) -> String {
+        format!("{}:user-sessions:{}", self.namespace, user_id)
+    }
+
+    pub async fn load(&self, session_id: &str) -> Result<Option<SessionRecord>, SessionError> {
+        let mut conn = self.conn.clone();
+        let json: Option<String> = conn.get(self.session_key(session_id)).await?;
+        json.map(|json| serde_json::from_str(&json).map_err(|e| SessionError::Codec(e.to_string())))
+            .transpose()
+    }
+
+    pub async fn save(
+        &self,
+        session_id: &str,
+        record: &SessionRecord,
+        ttl: Duration,
+    ) -> Result<(), SessionError> {
+        let json = serde_json::to_string(record).map_err(|e| SessionError::Codec(e.to_string()))?;
+        let ttl_ms = ttl.as_millis() as u64;
+        let mut conn = self.conn.clone();
+
+        conn.pset_ex::<_, _, ()>(self.session_key(session_id), json, ttl_ms)
+            .await?;
+
+        // Commands are sent one by one so the session and user keys may live
+        // in different cluster slots.
+        if let Some(user_id) = &record.user_id {
+            let user_key = self.user_key(user_id);
+            conn.sadd::<_, _, ()>(&user_key, session_id).await?;
+            let remaining: i64 = conn.pttl(&user_key).await?;
+            if remaining < ttl_ms as i64 {
+                conn.pexpire::<_, ()>(&user_key, ttl_ms as i64).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn delete(
+        &self,
+        session_id: &str,
+        user_id: Option<&str>,
+    ) -> Result<(), SessionError> {
+        let mut conn = self.conn.clone();
+        conn.del::<_, ()>(self.session_key(session_id)).await?;
+        if let Some(user_id) = user_id {
+            conn.srem::<_, _, ()>(self.user_key(user_id), session_id)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Lists the live sessions of `user_id`, dropping ids that expired.
+    pub async fn list_user_sessions(
+        &self,
+        user_id: &str,
+    ) -> Result<Vec<SessionInfo>, SessionError> {
+        let user_key = self.user_key(user_id);
+        let mut conn = self.conn.clone();
+        let session_ids: Vec<String> = conn.smembers(&user_key).await?;
+
+        let mut sessions = Vec::with_capacity(session_ids.len());
+        for session_id in session_ids {
+            match self.load(&session_id).await? {
+                Some(record) if record.user_id.as_deref() == Some(user_id) => {
+                    sessions.push(SessionInfo {
+                        handle: session_handle(&session_id),
+                        ip: record.ip,
+                        user_agent: record.user_agent,
+                        created_at: record.created_at,
+                        last_seen_at: record.last_seen_at,
+                    });
+                }
+                _ => conn.srem::<_, _, ()>(&user_key, &session_id).await?,
+            }
+        }
+
+        sessions.sort_by_key(|session| std::cmp::Reverse(session.last_seen_at));
+        Ok(sessions)
+    }
+
+    /// Ends the session of `user_id` with the listed `handle`; handles of
+    /// other users' sessions are ignored.
+    pub async fn revoke_session(&self, user_id: &str, handle: &str) -> Result<bool, SessionError> {
+        let mut conn = self.conn.clone();
+        let session_ids: Vec<String> = conn.smembers(self.user_key(user_id)).await?;
+        let Some(session_id) = session_ids
+            .into_iter()
+            .find(|session_id| session_handle(session_id) == handle)
+        else {
+            return Ok(false);
+        };
+        self.delete(&session_id, Some(user_id)).await?;
+        Ok(true)
+    }
+
+    /// Ends every session of `user_id` and returns how many there were.
+    pub async fn revoke_user_sessions(&self, user_id: &str) -> Result<u64, SessionError> {
+        let user_key = self.user_key(user_id);
+        let mut conn = self.conn.clone();
+        let session_ids: Vec<String> = conn.smembers(&user_key).await?;
+
+        let mut revoked = 0;
+        for session_id in &session_ids {
+            revoked += conn.del::<_, u64>(self.session_key(session_id)).await?;
+        }
+        conn.del::<_, ()>(&user_key).await?;
+
+        Ok(revoked)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SessionStatus {
+    Unchanged,
+    Changed,
+    /// Needs a new id, keeping its data.
+    Renewed,
+    Purged,
+}
+
+struct SessionState {
+    id: Option<String>,
+    /// Owner when loaded, to unlink the old id after login or logout.
+    loaded_user_id: Option<String>,
+    record: SessionRecord,
+    status: SessionStatus,
+}
+
+/// The current request's session, loaded by `SessionMiddleware`.
+///
+/// Changes are saved once the handler has returned.
+#[derive(Clone)]
+pub struct Session(Rc<RefCell<SessionState>>);
+
+impl Session {
+    pub fn user_id(&self) -> Option<String> {
+        self.0.borrow().record.user_id.clone()
+    }
+
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, serde_json::Error> {
+        self.0
+            .borrow()
+            .record
+            .data
+            .get(key)
+            .map(|value| serde_json::from_value(value.clone()))
+            .transpose()
+    }
+
+    pub fn insert<T: Serialize>(&self, key: &str, value: T) -> Result<(), serde_json::Error> {
+        let value = serde_json::to_value(value)?;
+        let mut state = self.0.borrow_mut();
+        state.record.data.insert(key.to_string(), value);
+        state.mark(SessionStatus::Changed);
+        Ok(())
+    }
+
+    pub fn remove(&self, key: &str) {
+        let mut state = self.0.borrow_mut();
+        if state.record.data.remove(key).is_some() {
+            state.mark(SessionStatus::Changed);
+        }
+    }
+
+    /// Attaches the session to `user_id` under a fresh id, so an id planted
+    /// before login cannot be used to ride the authenticated session.
+    pub fn login(&self, user_id: impl Into<String>) {
+        let mut state = self.0.borrow_mut();
+        state.record.user_id = Some(user_id.into());
+        state.mark(SessionStatus::Renewed);
+    }
+
+    /// Moves the session to a fresh id, e.g. after a privilege change.
+    pub fn renew(&self) {
+        self.0.borrow_mut().mark(SessionStatus::Renewed);
+    }
+
+    /// Deletes the session and clears the cookie.
+    pub fn purge(&self) {
+        let mut state = self.0.borrow_mut();
+        state.record.data.clear();
+        state.record.user_id = None;
+        state.status = SessionStatus::Purged;
+    }
+}
+
+impl SessionState {
+    fn mark(&mut self, status: SessionStatus) {
+        self.status = match (self.status, status) {
+            (SessionStatus::Purged, _) => SessionStatus::Purged,
+            (SessionStatus::Renewed, _) | (_, SessionStatus::Renewed) => SessionStatus::Renewed,
+            _ => status,
+        };
+    }
+}
+
+impl FromRequest for Session {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        if let Some(session) = req.extensions().get::<Session>() {
+            return ready(Ok(session.clone()));
+        }
+
+        tracing::error!("Session extractor used without SessionMiddleware");
+        let error = AppError::new(1100, None);
+        ready(Err(actix_web::error::InternalError::from_response(
+            error.to_string(),
+            error.http_response_builder(),
+        )
+        .into()))
+    }
+}
+
+fn new_session_id() -> String {
+    hex::encode(rand::random::<[u8; 32]>())
+}
+
+/// Stable, non-reversible name for a session that is safe to show to
+/// scripts.
+fn session_handle(session_id: &str) -> String {
+    hex::encode(Sha256::digest(session_id.as_bytes()))
+}
+
+/// Loads the session named by the session cookie before the handler runs
+/// and persists it afterwards.
+///
+/// A session is only written to Redis, and the cookie only set, once it
+/// holds data, so anonymous traffic does not create sessions.
+pub struct SessionMiddleware {
+    store: Arc<RedisSessionStore>,
+    config: Rc<SessionConfig>,
+}
+
+impl SessionMiddleware {
+    pub fn new(store: Arc<RedisSessionStore>, config: SessionConfig) -> Self {
+        Self {
+            store,
+            config: Rc::new(config),
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for SessionMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = SessionMiddlewareService<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(SessionMiddlewareService {
+            service: Rc::new(service),
+            store: self.store.clone(),
+            config: self.config.clone(),
+        }))
+    }
+}
+
+pub struct SessionMiddlewareService<S> {
+    service: Rc<S>,
+    store: Arc<RedisSessionStore>,
+    config: Rc<SessionConfig>,
+}
+
+impl<S, B> Service<ServiceRequest> for SessionMiddlewareService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let store = self.store.clone();
+        let config = self.config.clone();
+
+        Box::pin(async move {
+            let mut state = SessionState {
+                id: None,
+                loaded_user_id: None,
+                record: SessionRecord::new(&req),
+                status: SessionStatus::Unchanged,
+            };
+            if let Some(session_id) = config.read_cookie(&req) {
+                match store.load(&session_id).await {
+                    Ok(Some(record)) => {
+                        state.id = Some(session_id);
+                        state.loaded_user_id = record.user_id.clone();
+                        state.record = record;
+                    }
+                    Ok(None) => {}
+                    Err(e) => tracing::warn!("Failed to load session: {}", e),
+                }
+            }
+
+            let session = Session(Rc::new(RefCell::new(state)));
+            req.extensions_mut().insert(session.clone());
+
+            let mut res = service.call(req).await?;
+
+            let (previous_id, loaded_user_id, status) = {
+                let state = session.0.borrow();
+                (state.id.clone(), state.loaded_user_id.clone(), state.status)
+            };
+
+            let persisted: Result<(), SessionError> = async {
+                match status {
+                    SessionStatus::Purged => {
+                        if let Some(previous_id) = &previous_id {
+                            store.delete(previous_id, loaded_user_id.as_deref()).await?;
+                            config.write_cookie(&mut res, None);
+                        }
+                    }
+                    SessionStatus::Unchanged if previous_id.is_none() || !config.rolling => {}
+                    status => {
+                        let session_id = match (&previous_id, status) {
+                            (
+                                Some(previous_id),
+                                SessionStatus::Unchanged | SessionStatus::Changed,
+                            ) => previous_id.clone(),
+                            _ => new_session_id(),
+                        };
+                        let mut record = session.0.borrow().record.clone();
+                        record.last_seen_at = Utc::now();
+
+                        store.save(&session_id, &record, config.ttl).await?;
+                        if let Some(previous_id) =
+                            previous_id.as_ref().filter(|id| **id != session_id)
+                        {
+                            store.delete(previous_id, loaded_user_id.as_deref()).await?;
+                        }
+                        config.write_cookie(&mut res, Some(&session_id));
+                    }
+                }
+                Ok(())
+            }
+            .await;
+
+            // The handler's side effects are already committed, so its
+            // response stands; the session change is lost and the client
+            // keeps its previous cookie.
+            if let Err(e) = persisted {
+                tracing::error!("Failed to save session: {}", e);
+            }
+
+            Ok(res)
+        })
+    }
+}
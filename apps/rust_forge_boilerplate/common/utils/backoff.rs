@@ -0,0 +1,232 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::{env, fmt::Display, future::Future, time::Duration};
+use tokio::time::Instant;
+
+/// Exponential backoff with full jitter: the delay before retry `n` is a
+/// random duration between zero and `initial_delay * multiplier^n`, capped at
+/// `max_delay`. Retrying stops once `max_elapsed` has passed, if set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackoffPolicy {
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub multiplier: f64,
+    pub max_elapsed: Option<Duration>,
+}
+
+impl Default for BackoffPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            multiplier: 2.0,
+            max_elapsed: Some(Duration::from_secs(120)),
+        }
+    }
+}
+
+impl BackoffPolicy {
+    /// Startup retry policy; `STARTUP_RETRY_MAX_ELAPSED_SECS=0` retries forever.
+    pub fn from_env() -> Self {
+        let initial_delay_ms: u64 = env::var("STARTUP_RETRY_INITIAL_DELAY_MS")
+            .unwrap_or_else(|_| "500".to_string())
+            .parse()
+            .expect("Invalid STARTUP_RETRY_INITIAL_DELAY_MS");
+        let max_delay_ms: u64 = env::var("STARTUP_RETRY_MAX_DELAY_MS")
+            .unwrap_or_else(|_| "30000".to_string())
+            .parse()
+            .expect("Invalid STARTUP_RETRY_MAX_DELAY_MS");
+        let max_elapsed_secs: u64 = env::var("STARTUP_RETRY_MAX_ELAPSED_SECS")
+            .unwrap_or_else(|_| "120".to_string())
+            .parse()
+            .expect("Invalid STARTUP_RETRY_MAX_ELAPSED_SECS");
+
+        Self {
+            initial_delay: Duration::from_millis(initial_delay_ms),
+            max_delay: Duration::from_millis(max_delay_ms),
+            multiplier: 2.0,
+            max_elapsed: (max_elapsed_secs > 0).then(|| Duration::from_secs(max_elapsed_secs)),
+        }
+    }
+
+    /// Rejects policies whose delays never grow or that cannot reach
+    /// `initial_delay`.
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.multiplier.is_finite() && self.multiplier >= 1.0) {
+            return Err(format!(
+                "backoff multiplier must be at least 1, got {}",
+                self.multiplier
+            ));
+        }
+        if self.initial_delay > self.max_delay {
+            return Err(format!(
+                "backoff initial delay {:?} exceeds the max delay {:?}",
+                self.initial_delay, self.max_delay
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn unbounded(mut self) -> Self {
+        self.max_elapsed = None;
+        self
+    }
+
+    /// Upper bound of the delay before retry number `attempt` (starting at 0).
+    pub fn max_delay_for(&self, attempt: u32) -> Duration {
+        // Capped in f64 first: the uncapped delay can overflow a `Duration`.
+        let factor = self.multiplier.powi(attempt.min(64) as i32);
+        let delay = (self.initial_delay.as_secs_f64() * factor).min(self.max_delay.as_secs_f64());
+        Duration::try_from_secs_f64(delay).unwrap_or(self.max_delay)
+    }
+
+    pub fn delay_for(&self, attempt: u32) -> Duration {
+        let ceiling = self.max_delay_for(attempt);
+        ceiling.mul_f64(rand::thread_rng().gen_range(0.0..=1.0))
+    }
+}
+
+/// Runs `operation` until it succeeds or the policy's `max_elapsed` is used
+/// up, logging every failed attempt under `name`.
+pub async fn retry_with_backoff<T, E, F, Fut>(
+    name: &str,
+    policy: &BackoffPolicy,
+    mut operation: F,
+) -> Result<T, E>
+where
+    E: Display,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let started_at = Instant::now();
+    let mut attempt = 0;
+
+    loop {
+        match operation().await {
+            Ok(value) => {
+                if attempt > 0 {
+                    tracing::info!("{} connected after {} retries", name, attempt);
+                }
+                return Ok(value);
+            }
+            Err(err) => {
+                let delay = policy.delay_for(attempt);
+                let exhausted = policy
+                    .max_elapsed
+                    .is_some_and(|max_elapsed| started_at.elapsed() + delay > max_elapsed);
+
+                if exhausted {
+                    tracing::error!(
+                        "{} still unavailable after {} attempts, giving up: {}",
+                        name,
+                        attempt + 1,
+                        err
+                    );
+                    return Err(err);
+                }
+
+                tracing::warn!(
+                    "{} unavailable (attempt {}), retrying in {:?}: {}",
+                    name,
+                    attempt + 1,
+                    delay,
+                    err
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(max_elapsed: Option<Duration>) -> BackoffPolicy {
+        BackoffPolicy {
+            initial_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(5),
+            multiplier: 2.0,
+            max_elapsed,
+        }
+    }
+
+    #[test]
+    fn max_delay_grows_exponentially_up_to_the_cap() {
+        let policy = policy(None);
+        assert_eq!(policy.max_delay_for(0), Duration::from_millis(1));
+        assert_eq!(policy.max_delay_for(1), Duration::from_millis(2));
+        assert_eq!(policy.max_delay_for(2), Duration::from_millis(4));
+        assert_eq!(policy.max_delay_for(3), Duration::from_millis(5));
+        assert_eq!(policy.max_delay_for(u32::MAX), Duration::from_millis(5));
+    }
+
+    #[test]
+    fn max_delay_does_not_overflow_for_long_delays() {
+        let policy = BackoffPolicy {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+            ..policy(None)
+        };
+        assert_eq!(policy.max_delay_for(64), Duration::from_secs(30));
+        assert_eq!(policy.max_delay_for(u32::MAX), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn rejects_policies_that_cannot_back_off() {
+        assert!(policy(None).validate().is_ok());
+        for multiplier in [0.5, f64::NAN, f64::INFINITY] {
+            let policy = BackoffPolicy {
+                multiplier,
+                ..policy(None)
+            };
+            assert!(policy.validate().is_err());
+        }
+        let policy = BackoffPolicy {
+            initial_delay: Duration::from_secs(10),
+            ..policy(None)
+        };
+        assert!(policy.validate().is_err());
+    }
+
+    #[test]
+    fn delay_stays_within_the_max_delay() {
+        let policy = policy(None);
+        for attempt in 0..10 {
+            assert!(policy.delay_for(attempt) <= policy.max_delay_for(attempt));
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_until_the_operation_succeeds() {
+        let mut calls = 0;
+        let result: Result<u32, String> = retry_with_backoff("test", &policy(None), || {
+            calls += 1;
+            let result = if calls < 3 {
+                Err("down".to_string())
+            } else {
+                Ok(calls)
+            };
+            async move { result }
+        })
+        .await;
+        assert_eq!(result, Ok(3));
+    }
+
+    #[tokio::test]
+    async fn gives_up_once_max_elapsed_is_used_up() {
+        let policy = BackoffPolicy {
+            initial_delay: Duration::from_secs(1),
+            ..policy(Some(Duration::ZERO))
+        };
+        let mut calls = 0;
+        let result: Result<(), String> = retry_with_backoff("test", &policy, || {
+            calls += 1;
+            async { Err("down".to_string()) }
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+}
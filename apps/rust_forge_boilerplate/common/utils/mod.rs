@@ -1,2 +1,10 @@
 // Utility functions can be added here
-pub mod error;
\ No newline at end of file
+pub mod backoff;
+// Upstream code, kept as written.
+#[allow(
+    clippy::manual_range_contains,
+    clippy::needless_late_init,
+    clippy::needless_return,
+    clippy::redundant_field_names
+)]
+pub mod error;
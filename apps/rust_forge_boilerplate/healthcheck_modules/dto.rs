@@ -1,5 +1,8 @@
+use chrono::{DateTime, Utc};
 use serde::Serialize;
 
+use crate::common::infrastructure::scheduler::leader::SchedulerLeader;
+
 #[derive(Debug, Serialize)]
 pub struct HealthResponse {
     pub status: String,
@@ -13,3 +16,30 @@ pub struct ReadinessResponse {
     pub database: bool,
     pub redis: bool,
 }
+
+#[derive(Debug, Serialize)]
+pub struct SchedulerLeaderResponse {
+    pub instance: String,
+    pub fencing_token: i64,
+    pub elected_at: DateTime<Utc>,
+    pub renewed_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl From<SchedulerLeader> for SchedulerLeaderResponse {
+    fn from(leader: SchedulerLeader) -> Self {
+        Self {
+            instance: leader.instance,
+            fencing_token: leader.fencing_token,
+            elected_at: leader.elected_at,
+            renewed_at: leader.renewed_at,
+            expires_at: leader.expires_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SchedulerHealthResponse {
+    /// `None` while no scheduler holds a live lease.
+    pub leader: Option<SchedulerLeaderResponse>,
+}
@@ -1,15 +1,17 @@
 use std::sync::Arc;
 
 use crate::{
-    common::utils::error::AppError,
+    common::{
+        infrastructure::{database::DatabaseCluster, redis::RedisConnection},
+        utils::error::AppError,
+    },
     healthcheck_modules::{
-        dto::{HealthResponse, ReadinessResponse},
+        dto::{HealthResponse, ReadinessResponse, SchedulerHealthResponse},
         service::{HealthCheckService, HealthCheckServicesTrait},
     },
 };
 use actix_web::{HttpResponse, web};
-use redis::aio::ConnectionManager;
-use sqlx::PgPool;
+use metrics_exporter_prometheus::PrometheusHandle;
 
 pub async fn health_check() -> HttpResponse {
     let response = HealthResponse {
@@ -23,18 +25,18 @@ pub async fn health_check() -> HttpResponse {
 
 pub async fn readiness_check(
     service: web::Data<Arc<HealthCheckService>>,
-    db_pool: web::Data<PgPool>,
-    redis_conn: web::Data<ConnectionManager>,
+    db: web::Data<DatabaseCluster>,
+    redis_conn: web::Data<RedisConnection>,
 ) -> HttpResponse {
     let mut redis_clone = redis_conn.as_ref().clone();
 
-    let db_fut = async { service.ping_db(db_pool.get_ref()).await };
+    let db_fut = async { service.ping_db(db.get_ref()).await };
 
     let redis_fut = async { service.ping_redis(&mut redis_clone).await };
 
     let (db_ready, redis_ready) = tokio::join!(db_fut, redis_fut);
 
-    let ready = false;
+    let ready = db_ready && redis_ready;
     let response = ReadinessResponse {
         ready,
         database: db_ready,
@@ -48,3 +50,32 @@ pub async fn readiness_check(
         error.http_response_builder()
     }
 }
+
+pub async fn scheduler_health(
+    service: web::Data<Arc<HealthCheckService>>,
+    db: web::Data<DatabaseCluster>,
+) -> HttpResponse {
+    match service.scheduler_leader(db.get_ref()).await {
+        Ok(leader) => HttpResponse::Ok().json(SchedulerHealthResponse {
+            leader: leader.map(Into::into),
+        }),
+        Err(e) => AppError::map_db_error(e).http_response_builder(),
+    }
+}
+
+pub async fn metrics(
+    service: web::Data<Arc<HealthCheckService>>,
+    db: web::Data<DatabaseCluster>,
+    handle: web::Data<PrometheusHandle>,
+) -> HttpResponse {
+    // The leader lives in the scheduler process, so it is looked up on each
+    // scrape. Without the database the gauges keep their last values.
+    match service.scheduler_leader(db.get_ref()).await {
+        Ok(leader) => service.report_scheduler_leader(leader.as_ref()),
+        Err(e) => tracing::warn!("Failed to look up the scheduler leader: {}", e),
+    }
+
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(handle.render())
+}
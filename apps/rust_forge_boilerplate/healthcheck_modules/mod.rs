@@ -8,6 +8,11 @@ pub fn configure_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/api")
             .route("/health", web::get().to(handler::health_check))
-            .route("/ready", web::get().to(handler::readiness_check)),
+            .route(
+                "/health/scheduler",
+                web::get().to(handler::scheduler_health),
+            )
+            .route("/ready", web::get().to(handler::readiness_check))
+            .route("/metrics", web::get().to(handler::metrics)),
     );
 }
@@ -1,15 +1,15 @@
-use sqlx::{Pool, Postgres};
+use crate::common::infrastructure::database::DbExecutor;
 
 #[async_trait::async_trait]
 pub trait HealthCheckRepoTrait: Send + Sync {
-    async fn ping(&self, pg_pool: &Pool<Postgres>) -> Result<(), sqlx::Error>;
+    async fn ping(&self, db: DbExecutor<'_>) -> Result<(), sqlx::Error>;
 }
 
 pub struct HealthCheckRepo;
 
 #[async_trait::async_trait]
 impl HealthCheckRepoTrait for HealthCheckRepo {
-    async fn ping(&self, pg_pool: &Pool<Postgres>) -> Result<(), sqlx::Error> {
-        sqlx::query("SELECT 1").execute(pg_pool).await.map(|_| ())
+    async fn ping(&self, mut db: DbExecutor<'_>) -> Result<(), sqlx::Error> {
+        db.execute(sqlx::query("SELECT 1")).await.map(|_| ())
     }
 }
@@ -1,29 +1,72 @@
-use redis::aio::ConnectionManager;
-use sqlx::{Pool, Postgres};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
-use crate::healthcheck_modules::repo::HealthCheckRepoTrait;
+use crate::{
+    common::infrastructure::{
+        database::DatabaseCluster,
+        redis::RedisConnection,
+        scheduler::leader::{SchedulerLeader, SchedulerLeaderStore},
+    },
+    healthcheck_modules::repo::HealthCheckRepoTrait,
+};
 
 #[async_trait::async_trait]
 pub trait HealthCheckServicesTrait: Send + Sync {
-    async fn ping_db(&self, pg_pool: &Pool<Postgres>) -> bool;
-    async fn ping_redis(&self, redis_conn: &mut ConnectionManager) -> bool;
+    async fn ping_db(&self, db: &DatabaseCluster) -> bool;
+    async fn ping_redis(&self, redis_conn: &mut RedisConnection) -> bool;
+    async fn scheduler_leader(
+        &self,
+        db: &DatabaseCluster,
+    ) -> Result<Option<SchedulerLeader>, sqlx::Error>;
 }
 
 pub struct HealthCheckService {
     pub repo: Arc<dyn HealthCheckRepoTrait>,
+    /// Instance last reported by the `scheduler_leader` gauge.
+    pub reported_scheduler_leader: Mutex<Option<String>>,
+}
+
+impl HealthCheckService {
+    /// Sets the `scheduler_leader` gauge to 1 for the leading instance and
+    /// back to 0 for the one reported before it.
+    pub fn report_scheduler_leader(&self, leader: Option<&SchedulerLeader>) {
+        let instance = leader.map(|leader| leader.instance.clone());
+        let mut reported = self
+            .reported_scheduler_leader
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        if let Some(previous) = reported.take()
+            && instance.as_ref() != Some(&previous)
+        {
+            metrics::gauge!("scheduler_leader", "instance" => previous).set(0.0);
+        }
+        if let Some(leader) = leader {
+            metrics::gauge!("scheduler_leader", "instance" => leader.instance.clone()).set(1.0);
+            metrics::gauge!("scheduler_leader_fencing_token").set(leader.fencing_token as f64);
+        }
+        *reported = instance;
+    }
 }
 
 #[async_trait::async_trait]
 impl HealthCheckServicesTrait for HealthCheckService {
-    async fn ping_db(&self, pg_pool: &Pool<Postgres>) -> bool {
-        self.repo.ping(pg_pool).await.is_ok()
+    async fn ping_db(&self, db: &DatabaseCluster) -> bool {
+        self.repo.ping(db.writer().into()).await.is_ok()
     }
 
-    async fn ping_redis(&self, redis_conn: &mut ConnectionManager) -> bool {
+    async fn ping_redis(&self, redis_conn: &mut RedisConnection) -> bool {
         redis::cmd("PING")
             .query_async::<_, String>(redis_conn)
             .await
             .is_ok()
     }
+
+    async fn scheduler_leader(
+        &self,
+        db: &DatabaseCluster,
+    ) -> Result<Option<SchedulerLeader>, sqlx::Error> {
+        SchedulerLeaderStore::new(db.writer().clone())
+            .current()
+            .await
+    }
 }
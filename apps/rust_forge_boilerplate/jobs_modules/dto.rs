@@ -0,0 +1,164 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::common::infrastructure::queue::{
+    dead_letter::DeadLetter,
+    job::JobAttempt,
+    status::{JobState, JobStatus},
+    workflow::BatchProgress,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct DeadLetterListQuery {
+    pub name: Option<String>,
+    pub failed_before: Option<DateTime<Utc>>,
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeadLetterPurgeQuery {
+    pub name: Option<String>,
+    pub failed_before: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeadLetterSummary {
+    pub id: Uuid,
+    pub name: String,
+    pub attempts: i32,
+    pub last_error: String,
+    pub enqueued_at: DateTime<Utc>,
+    pub failed_at: DateTime<Utc>,
+}
+
+impl From<DeadLetter> for DeadLetterSummary {
+    fn from(dead_letter: DeadLetter) -> Self {
+        Self {
+            id: dead_letter.id,
+            name: dead_letter.name,
+            attempts: dead_letter.attempts,
+            last_error: dead_letter.last_error,
+            enqueued_at: dead_letter.envelope.enqueued_at,
+            failed_at: dead_letter.failed_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeadLetterListResponse {
+    pub items: Vec<DeadLetterSummary>,
+    pub page: i64,
+    pub per_page: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeadLetterDetail {
+    pub id: Uuid,
+    pub name: String,
+    pub payload: serde_json::Value,
+    pub attempts: i32,
+    pub max_attempts: u32,
+    pub last_error: String,
+    pub stack: Option<String>,
+    pub history: Vec<JobAttempt>,
+    pub enqueued_at: DateTime<Utc>,
+    pub failed_at: DateTime<Utc>,
+}
+
+impl From<DeadLetter> for DeadLetterDetail {
+    fn from(dead_letter: DeadLetter) -> Self {
+        let envelope = dead_letter.envelope.0;
+        Self {
+            id: dead_letter.id,
+            name: dead_letter.name,
+            payload: envelope.payload,
+            attempts: dead_letter.attempts,
+            max_attempts: envelope.max_attempts,
+            last_error: dead_letter.last_error,
+            stack: dead_letter.stack,
+            history: envelope.history,
+            enqueued_at: envelope.enqueued_at,
+            failed_at: dead_letter.failed_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReplayResponse {
+    pub job_id: Uuid,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PurgeResponse {
+    pub purged: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatchProgressResponse {
+    pub id: Uuid,
+    pub description: Option<String>,
+    pub total: i32,
+    pub succeeded: i32,
+    pub failed: i32,
+    pub pending: i32,
+    pub finished: bool,
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+impl From<BatchProgress> for BatchProgressResponse {
+    fn from(progress: BatchProgress) -> Self {
+        Self {
+            pending: progress.pending(),
+            finished: progress.is_finished(),
+            id: progress.id,
+            description: progress.description,
+            total: progress.total,
+            succeeded: progress.succeeded,
+            failed: progress.failed,
+            created_at: progress.created_at,
+            finished_at: progress.finished_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JobStatusResponse {
+    pub id: Uuid,
+    pub name: String,
+    pub status: JobState,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    pub last_error: Option<String>,
+    pub progress: Option<i16>,
+    pub progress_message: Option<String>,
+    pub batch_id: Option<Uuid>,
+    pub run_at: DateTime<Utc>,
+    pub enqueued_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<JobStatus> for JobStatusResponse {
+    fn from(status: JobStatus) -> Self {
+        Self {
+            id: status.id,
+            name: status.name,
+            status: status.status,
+            attempts: status.attempts,
+            max_attempts: status.max_attempts,
+            last_error: status.last_error,
+            progress: status.progress,
+            progress_message: status.progress_message,
+            batch_id: status.batch_id,
+            run_at: status.run_at,
+            enqueued_at: status.enqueued_at,
+            started_at: status.started_at,
+            finished_at: status.finished_at,
+            updated_at: status.updated_at,
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use actix_web::{HttpResponse, http::header, web};
+use futures::{StreamExt, stream};
+use std::{sync::Arc, time::Duration};
+use tokio::sync::broadcast::error::RecvError;
+use uuid::Uuid;
+
+use crate::{
+    common::{infrastructure::queue::status::JobStatusWatch, middleware::admin::AdminAccess},
+    jobs_modules::{
+        dto::{DeadLetterListQuery, DeadLetterPurgeQuery, JobStatusResponse},
+        service::{JobsService, JobsServiceTrait},
+    },
+};
+
+/// Sent while a job's status stays unchanged, so proxies keep the stream
+/// open.
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+pub async fn list_dead_letters(
+    _admin: AdminAccess,
+    service: web::Data<Arc<JobsService>>,
+    query: web::Query<DeadLetterListQuery>,
+) -> HttpResponse {
+    match service.list_dead_letters(query.into_inner()).await {
+        Ok(response) => HttpResponse::Ok().json(response),
+        Err(error) => error.http_response_builder(),
+    }
+}
+
+pub async fn get_dead_letter(
+    _admin: AdminAccess,
+    service: web::Data<Arc<JobsService>>,
+    id: web::Path<Uuid>,
+) -> HttpResponse {
+    match service.get_dead_letter(id.into_inner()).await {
+        Ok(response) => HttpResponse::Ok().json(response),
+        Err(error) => error.http_response_builder(),
+    }
+}
+
+pub async fn replay_dead_letter(
+    _admin: AdminAccess,
+    service: web::Data<Arc<JobsService>>,
+    id: web::Path<Uuid>,
+) -> HttpResponse {
+    match service.replay_dead_letter(id.into_inner()).await {
+        Ok(response) => HttpResponse::Accepted().json(response),
+        Err(error) => error.http_response_builder(),
+    }
+}
+
+pub async fn delete_dead_letter(
+    _admin: AdminAccess,
+    service: web::Data<Arc<JobsService>>,
+    id: web::Path<Uuid>,
+) -> HttpResponse {
+    match service.delete_dead_letter(id.into_inner()).await {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(error) => error.http_response_builder(),
+    }
+}
+
+pub async fn purge_dead_letters(
+    _admin: AdminAccess,
+    service: web::Data<Arc<JobsService>>,
+    query: web::Query<DeadLetterPurgeQuery>,
+) -> HttpResponse {
+    match service.purge_dead_letters(query.into_inner()).await {
+        Ok(response) => HttpResponse::Ok().json(response),
+        Err(error) => error.http_response_builder(),
+    }
+}
+
+pub async fn get_batch_progress(
+    _admin: AdminAccess,
+    service: web::Data<Arc<JobsService>>,
+    id: web::Path<Uuid>,
+) -> HttpResponse {
+    match service.get_batch_progress(id.into_inner()).await {
+        Ok(response) => HttpResponse::Ok().json(response),
+        Err(error) => error.http_response_builder(),
+    }
+}
+
+pub async fn get_job_status(
+    _admin: AdminAccess,
+    service: web::Data<Arc<JobsService>>,
+    id: web::Path<Uuid>,
+) -> HttpResponse {
+    match service.get_job_status(id.into_inner()).await {
+        Ok(response) => HttpResponse::Ok().json(response),
+        Err(error) => error.http_response_builder(),
+    }
+}
+
+struct StatusStream {
+    service: web::Data<Arc<JobsService>>,
+    id: Uuid,
+    watch: JobStatusWatch,
+    keep_alive: tokio::time::Interval,
+}
+
+/// Server-sent events with the job's status, first the current one and then
+/// each change, until the job finishes.
+pub async fn stream_job_status(
+    _admin: AdminAccess,
+    service: web::Data<Arc<JobsService>>,
+    id: web::Path<Uuid>,
+) -> HttpResponse {
+    let id = id.into_inner();
+    let (current, watch) = match service.watch_job_status(id).await {
+        Ok(watch) => watch,
+        Err(error) => return error.http_response_builder(),
+    };
+
+    let mut keep_alive = tokio::time::interval(KEEP_ALIVE_INTERVAL);
+    keep_alive.reset();
+    let state = (!current.status.is_finished()).then_some(StatusStream {
+        service,
+        id,
+        watch,
+        keep_alive,
+    });
+    let changes = stream::unfold(state, |state| async move {
+        let mut state = state?;
+        loop {
+            tokio::select! {
+                received = state.watch.recv() => {
+                    let status: JobStatusResponse = match received {
+                        Ok(status) => status.into(),
+                        // Missed some changes: the current status is all
+                        // that matters.
+                        Err(RecvError::Lagged(_)) => {
+                            match state.service.get_job_status(state.id).await {
+                                Ok(status) => status,
+                                Err(_) => continue,
+                            }
+                        }
+                        Err(RecvError::Closed) => return None,
+                    };
+                    let finished = status.status.is_finished();
+                    return Some((status_event(&status), (!finished).then_some(state)));
+                }
+                _ = state.keep_alive.tick() => {
+                    return Some((web::Bytes::from_static(b": keep-alive\n\n"), Some(state)));
+                }
+            }
+        }
+    });
+
+    let events = stream::once(async move { status_event(&current) })
+        .chain(changes)
+        .map(Ok::<_, actix_web::Error>);
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .streaming(events)
+}
+
+fn status_event(status: &JobStatusResponse) -> web::Bytes {
+    let data = serde_json::to_string(status).unwrap_or_default();
+    web::Bytes::from(format!("event: status\ndata: {}\n\n", data))
+}
@@ -0,0 +1,34 @@
+pub mod dto;
+pub mod handler;
+use actix_web::web;
+pub mod service;
+pub mod tasks;
+
+pub fn configure_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/api/admin/jobs")
+            .route("/dead-letters", web::get().to(handler::list_dead_letters))
+            .route(
+                "/dead-letters",
+                web::delete().to(handler::purge_dead_letters),
+            )
+            .route(
+                "/dead-letters/{id}",
+                web::get().to(handler::get_dead_letter),
+            )
+            .route(
+                "/dead-letters/{id}",
+                web::delete().to(handler::delete_dead_letter),
+            )
+            .route(
+                "/dead-letters/{id}/replay",
+                web::post().to(handler::replay_dead_letter),
+            ),
+    );
+    cfg.service(
+        web::scope("/api/jobs")
+            .route("/batches/{id}", web::get().to(handler::get_batch_progress))
+            .route("/{id}", web::get().to(handler::get_job_status))
+            .route("/{id}/events", web::get().to(handler::stream_job_status)),
+    );
+}
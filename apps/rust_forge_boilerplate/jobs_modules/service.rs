@@ -0,0 +1,176 @@
+use actix_web::http::StatusCode;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::{
+    common::{
+        infrastructure::queue::{
+            backend::{JobQueue, QueueError},
+            dead_letter::{DeadLetterFilter, DeadLetterStore},
+            status::{JobStatusFeed, JobStatusWatch},
+            workflow::BatchStore,
+        },
+        utils::error::{AppError, AppResult},
+    },
+    jobs_modules::dto::{
+        BatchProgressResponse, DeadLetterDetail, DeadLetterListQuery, DeadLetterListResponse,
+        DeadLetterPurgeQuery, JobStatusResponse, PurgeResponse, ReplayResponse,
+    },
+};
+
+const DEFAULT_PER_PAGE: i64 = 20;
+const MAX_PER_PAGE: i64 = 100;
+
+#[async_trait::async_trait]
+pub trait JobsServiceTrait: Send + Sync {
+    async fn list_dead_letters(
+        &self,
+        query: DeadLetterListQuery,
+    ) -> AppResult<DeadLetterListResponse>;
+    async fn get_dead_letter(&self, id: Uuid) -> AppResult<DeadLetterDetail>;
+    async fn replay_dead_letter(&self, id: Uuid) -> AppResult<ReplayResponse>;
+    async fn delete_dead_letter(&self, id: Uuid) -> AppResult<()>;
+    async fn purge_dead_letters(&self, query: DeadLetterPurgeQuery) -> AppResult<PurgeResponse>;
+    async fn get_batch_progress(&self, id: Uuid) -> AppResult<BatchProgressResponse>;
+    async fn get_job_status(&self, id: Uuid) -> AppResult<JobStatusResponse>;
+    /// The job's current status, and a receiver of every status change from
+    /// then on, of all jobs.
+    async fn watch_job_status(&self, id: Uuid) -> AppResult<(JobStatusResponse, JobStatusWatch)>;
+}
+
+pub struct JobsService {
+    pub dead_letters: DeadLetterStore,
+    pub batches: BatchStore,
+    pub queue: Arc<JobQueue>,
+    pub status_feed: JobStatusFeed,
+}
+
+fn map_queue_error(err: QueueError) -> AppError {
+    match err {
+        QueueError::Database(e) => AppError::map_db_error(e),
+        QueueError::Redis(e) => {
+            tracing::error!("Job queue unavailable: {}", e);
+            AppError::new(1101, Some(StatusCode::SERVICE_UNAVAILABLE))
+        }
+        QueueError::InvalidJob(e) => {
+            tracing::error!("Invalid job: {}", e);
+            AppError::new(1000, None)
+        }
+        QueueError::Codec(e) => {
+            tracing::error!("Job queue codec error: {}", e);
+            AppError::new(1000, None)
+        }
+        QueueError::LeaseLost => {
+            tracing::error!("Job queue lease lost");
+            AppError::new(1000, None)
+        }
+    }
+}
+
+fn not_found() -> AppError {
+    AppError::new(3002, Some(StatusCode::NOT_FOUND))
+}
+
+#[async_trait::async_trait]
+impl JobsServiceTrait for JobsService {
+    async fn list_dead_letters(
+        &self,
+        query: DeadLetterListQuery,
+    ) -> AppResult<DeadLetterListResponse> {
+        let page = query.page.unwrap_or(1).max(1);
+        let per_page = query
+            .per_page
+            .unwrap_or(DEFAULT_PER_PAGE)
+            .clamp(1, MAX_PER_PAGE);
+        let filter = DeadLetterFilter {
+            name: query.name,
+            failed_before: query.failed_before,
+        };
+
+        let items = self
+            .dead_letters
+            .list(&filter, per_page, (page - 1) * per_page)
+            .await
+            .map_err(map_queue_error)?;
+
+        Ok(DeadLetterListResponse {
+            items: items.into_iter().map(Into::into).collect(),
+            page,
+            per_page,
+        })
+    }
+
+    async fn get_dead_letter(&self, id: Uuid) -> AppResult<DeadLetterDetail> {
+        self.dead_letters
+            .get(id)
+            .await
+            .map_err(map_queue_error)?
+            .map(Into::into)
+            .ok_or_else(not_found)
+    }
+
+    async fn replay_dead_letter(&self, id: Uuid) -> AppResult<ReplayResponse> {
+        let replayed = self
+            .dead_letters
+            .replay(id, &self.queue)
+            .await
+            .map_err(map_queue_error)?;
+        if !replayed {
+            return Err(not_found());
+        }
+
+        tracing::info!("Replayed dead-lettered job {}", id);
+        Ok(ReplayResponse { job_id: id })
+    }
+
+    async fn delete_dead_letter(&self, id: Uuid) -> AppResult<()> {
+        let deleted = self
+            .dead_letters
+            .delete(id)
+            .await
+            .map_err(map_queue_error)?;
+        if deleted { Ok(()) } else { Err(not_found()) }
+    }
+
+    async fn purge_dead_letters(&self, query: DeadLetterPurgeQuery) -> AppResult<PurgeResponse> {
+        let filter = DeadLetterFilter {
+            name: query.name,
+            failed_before: query.failed_before,
+        };
+        let purged = self
+            .dead_letters
+            .purge(&filter)
+            .await
+            .map_err(map_queue_error)?;
+
+        tracing::info!("Purged {} dead-lettered jobs", purged);
+        Ok(PurgeResponse { purged })
+    }
+
+    async fn get_batch_progress(&self, id: Uuid) -> AppResult<BatchProgressResponse> {
+        self.batches
+            .progress(id)
+            .await
+            .map_err(map_queue_error)?
+            .map(Into::into)
+            .ok_or_else(not_found)
+    }
+
+    async fn get_job_status(&self, id: Uuid) -> AppResult<JobStatusResponse> {
+        self.queue
+            .statuses()
+            .get(id)
+            .await
+            .map_err(map_queue_error)?
+            .map(Into::into)
+            .ok_or_else(not_found)
+    }
+
+    async fn watch_job_status(&self, id: Uuid) -> AppResult<(JobStatusResponse, JobStatusWatch)> {
+        // Watch first, so no change between the read and the watch is
+        // missed.
+        let watch = self.status_feed.watch(id);
+        let current = self.get_job_status(id).await?;
+        Ok((current, watch))
+    }
+}
@@ -0,0 +1,71 @@
+use chrono::Utc;
+use std::{env, time::Duration};
+
+use crate::common::{
+    infrastructure::{
+        database::DbExecutor,
+        outbox::Outbox,
+        queue::{status::JobStatusStore, workflow::BatchStore},
+        scheduler::{
+            history::TaskRunStore,
+            task::{ScheduledTask, TaskContext, TaskError},
+        },
+    },
+    middleware::idempotency::PostgresIdempotencyStore,
+};
+
+/// Deletes the statuses and batches of jobs, the scheduled task runs and the
+/// sent or dead outbox events that finished longer than
+/// `JOB_RECORD_RETENTION_DAYS` ago, along with expired job unique keys and
+/// idempotency keys.
+pub struct PurgeJobRecords {
+    pub retention: Duration,
+}
+
+impl PurgeJobRecords {
+    pub fn from_env() -> Self {
+        let retention_days: u64 = env::var("JOB_RECORD_RETENTION_DAYS")
+            .unwrap_or_else(|_| "7".to_string())
+            .parse()
+            .expect("Invalid JOB_RECORD_RETENTION_DAYS");
+
+        Self {
+            retention: Duration::from_secs(retention_days * 24 * 60 * 60),
+        }
+    }
+}
+
+impl ScheduledTask for PurgeJobRecords {
+    const NAME: &'static str = "purge_job_records";
+
+    async fn run(&self, ctx: &TaskContext) -> Result<(), TaskError> {
+        let before = Utc::now() - self.retention;
+        let statuses = JobStatusStore::new(ctx.state.db.clone())
+            .purge_finished(before)
+            .await?;
+        let batches = BatchStore::new(ctx.state.db.clone())
+            .purge_finished(before)
+            .await?;
+        let runs = TaskRunStore::new(ctx.state.db.clone())
+            .purge_finished(before)
+            .await?;
+        let outbox_events = Outbox::purge_finished(DbExecutor::from(&ctx.state.db), before).await?;
+        let unique_keys = ctx.state.jobs.purge_expired_unique_keys().await?;
+        let idempotency_keys = PostgresIdempotencyStore::new(ctx.state.db.clone())
+            .purge_expired()
+            .await?;
+
+        tracing::info!(
+            "Purged {} job statuses, {} batches, {} task runs and {} outbox events \
+             finished before {}, {} expired job unique keys and {} expired idempotency keys",
+            statuses,
+            batches,
+            runs,
+            outbox_events,
+            before,
+            unique_keys,
+            idempotency_keys
+        );
+        Ok(())
+    }
+}
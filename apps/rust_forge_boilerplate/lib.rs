@@ -1,2 +1,4 @@
 pub mod common;
 pub mod healthcheck_modules;
+pub mod jobs_modules;
+pub mod scheduler_modules;
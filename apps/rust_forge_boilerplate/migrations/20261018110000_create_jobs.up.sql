@@ -0,0 +1,33 @@
+CREATE TABLE IF NOT EXISTS jobs (
+    id UUID PRIMARY KEY,
+    name TEXT NOT NULL,
+    payload JSONB NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    max_attempts INTEGER NOT NULL,
+    timeout_ms BIGINT NOT NULL,
+    run_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    locked_by TEXT,
+    lease_id UUID,
+    heartbeat_at TIMESTAMPTZ,
+    enqueued_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS jobs_ready_idx
+    ON jobs (run_at)
+    WHERE locked_by IS NULL;
+
+CREATE INDEX IF NOT EXISTS jobs_locked_idx
+    ON jobs (heartbeat_at)
+    WHERE locked_by IS NOT NULL;
+
+CREATE OR REPLACE FUNCTION notify_job() RETURNS TRIGGER AS $$
+BEGIN
+    PERFORM pg_notify('jobs', NEW.id::TEXT);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER jobs_notify
+    AFTER INSERT OR UPDATE OF run_at ON jobs
+    FOR EACH ROW EXECUTE FUNCTION notify_job();
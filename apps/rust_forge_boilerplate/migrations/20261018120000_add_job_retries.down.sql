@@ -0,0 +1,5 @@
+DROP TABLE IF EXISTS dead_letter_jobs;
+
+ALTER TABLE jobs
+    DROP COLUMN IF EXISTS history,
+    DROP COLUMN IF EXISTS backoff;
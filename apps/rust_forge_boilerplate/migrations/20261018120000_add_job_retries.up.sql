@@ -0,0 +1,19 @@
+ALTER TABLE jobs
+    ADD COLUMN IF NOT EXISTS backoff JSONB,
+    ADD COLUMN IF NOT EXISTS history JSONB NOT NULL DEFAULT '[]';
+
+CREATE TABLE IF NOT EXISTS dead_letter_jobs (
+    id UUID PRIMARY KEY,
+    name TEXT NOT NULL,
+    envelope JSONB NOT NULL,
+    attempts INTEGER NOT NULL,
+    last_error TEXT NOT NULL,
+    stack TEXT,
+    failed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS dead_letter_jobs_failed_at_idx
+    ON dead_letter_jobs (failed_at);
+
+CREATE INDEX IF NOT EXISTS dead_letter_jobs_name_idx
+    ON dead_letter_jobs (name);
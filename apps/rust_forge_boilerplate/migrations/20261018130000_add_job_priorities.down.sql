@@ -0,0 +1,11 @@
+DROP TABLE IF EXISTS job_unique_keys;
+
+DROP INDEX IF EXISTS jobs_ready_idx;
+
+CREATE INDEX IF NOT EXISTS jobs_ready_idx
+    ON jobs (run_at)
+    WHERE locked_by IS NULL;
+
+ALTER TABLE jobs
+    DROP COLUMN IF EXISTS sort_at,
+    DROP COLUMN IF EXISTS priority;
@@ -0,0 +1,22 @@
+ALTER TABLE jobs
+    ADD COLUMN IF NOT EXISTS priority SMALLINT NOT NULL DEFAULT 1,
+    ADD COLUMN IF NOT EXISTS sort_at TIMESTAMPTZ;
+
+UPDATE jobs SET sort_at = run_at WHERE sort_at IS NULL;
+
+ALTER TABLE jobs ALTER COLUMN sort_at SET NOT NULL;
+
+DROP INDEX IF EXISTS jobs_ready_idx;
+
+CREATE INDEX IF NOT EXISTS jobs_ready_idx
+    ON jobs (sort_at)
+    WHERE locked_by IS NULL;
+
+CREATE TABLE IF NOT EXISTS job_unique_keys (
+    key TEXT PRIMARY KEY,
+    job_id UUID NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS job_unique_keys_expires_at_idx
+    ON job_unique_keys (expires_at);
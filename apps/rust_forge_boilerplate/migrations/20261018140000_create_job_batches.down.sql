@@ -0,0 +1,5 @@
+DROP TABLE IF EXISTS job_batch_members;
+DROP TABLE IF EXISTS job_batches;
+
+ALTER TABLE jobs
+    DROP COLUMN IF EXISTS workflow;
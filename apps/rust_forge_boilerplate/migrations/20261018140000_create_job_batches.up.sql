@@ -0,0 +1,27 @@
+ALTER TABLE jobs
+    ADD COLUMN IF NOT EXISTS workflow JSONB;
+
+CREATE TABLE IF NOT EXISTS job_batches (
+    id UUID PRIMARY KEY,
+    description TEXT,
+    total INTEGER NOT NULL,
+    succeeded INTEGER NOT NULL DEFAULT 0,
+    failed INTEGER NOT NULL DEFAULT 0,
+    callback JSONB,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    finished_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS job_batches_finished_at_idx
+    ON job_batches (finished_at);
+
+CREATE TABLE IF NOT EXISTS job_batch_members (
+    batch_id UUID NOT NULL REFERENCES job_batches (id) ON DELETE CASCADE,
+    job_id UUID NOT NULL,
+    position INTEGER NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending',
+    result JSONB,
+    error TEXT,
+    finished_at TIMESTAMPTZ,
+    PRIMARY KEY (batch_id, job_id)
+);
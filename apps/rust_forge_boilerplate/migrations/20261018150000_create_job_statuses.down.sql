@@ -0,0 +1,3 @@
+DROP TRIGGER IF EXISTS job_statuses_notify ON job_statuses;
+DROP FUNCTION IF EXISTS notify_job_status();
+DROP TABLE IF EXISTS job_statuses;
@@ -0,0 +1,30 @@
+CREATE TABLE IF NOT EXISTS job_statuses (
+    id UUID PRIMARY KEY,
+    name TEXT NOT NULL,
+    status TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    max_attempts INTEGER NOT NULL,
+    last_error TEXT,
+    progress SMALLINT,
+    progress_message TEXT,
+    batch_id UUID,
+    run_at TIMESTAMPTZ NOT NULL,
+    enqueued_at TIMESTAMPTZ NOT NULL,
+    started_at TIMESTAMPTZ,
+    finished_at TIMESTAMPTZ,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS job_statuses_finished_at_idx
+    ON job_statuses (finished_at);
+
+CREATE OR REPLACE FUNCTION notify_job_status() RETURNS TRIGGER AS $$
+BEGIN
+    PERFORM pg_notify('job_status', NEW.id::TEXT);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER job_statuses_notify
+    AFTER INSERT OR UPDATE ON job_statuses
+    FOR EACH ROW EXECUTE FUNCTION notify_job_status();
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS scheduler_leaders (
+    name TEXT PRIMARY KEY,
+    instance TEXT NOT NULL,
+    fencing_token BIGINT NOT NULL,
+    elected_at TIMESTAMPTZ NOT NULL,
+    renewed_at TIMESTAMPTZ NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS scheduled_task_runs (
+    id UUID PRIMARY KEY,
+    task TEXT NOT NULL,
+    scheduled_at TIMESTAMPTZ NOT NULL,
+    instance TEXT NOT NULL,
+    outcome TEXT NOT NULL,
+    error TEXT,
+    started_at TIMESTAMPTZ,
+    finished_at TIMESTAMPTZ,
+    -- Claims each occurrence once, even if two replicas briefly both lead.
+    UNIQUE (task, scheduled_at)
+);
+
+CREATE INDEX IF NOT EXISTS idx_scheduled_task_runs_task_scheduled_at
+    ON scheduled_task_runs (task, scheduled_at DESC);
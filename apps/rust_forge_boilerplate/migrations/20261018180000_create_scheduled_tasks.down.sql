@@ -0,0 +1,4 @@
+ALTER TABLE scheduled_task_runs DROP COLUMN IF EXISTS trigger;
+DROP TRIGGER IF EXISTS scheduled_tasks_trigger_notify ON scheduled_tasks;
+DROP FUNCTION IF EXISTS notify_scheduled_task_trigger();
+DROP TABLE IF EXISTS scheduled_tasks;
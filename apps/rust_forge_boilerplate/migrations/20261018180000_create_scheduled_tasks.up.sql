@@ -0,0 +1,28 @@
+CREATE TABLE IF NOT EXISTS scheduled_tasks (
+    name TEXT PRIMARY KEY,
+    schedule TEXT NOT NULL,
+    time_zone TEXT NOT NULL,
+    enabled BOOLEAN NOT NULL,
+    misfire TEXT NOT NULL,
+    overlap TEXT NOT NULL,
+    timeout_secs BIGINT NOT NULL,
+    paused BOOLEAN NOT NULL DEFAULT FALSE,
+    paused_at TIMESTAMPTZ,
+    trigger_requested_at TIMESTAMPTZ,
+    registered_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE OR REPLACE FUNCTION notify_scheduled_task_trigger() RETURNS TRIGGER AS $$
+BEGIN
+    PERFORM pg_notify('scheduled_task_trigger', NEW.name);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER scheduled_tasks_trigger_notify
+    AFTER UPDATE OF trigger_requested_at ON scheduled_tasks
+    FOR EACH ROW WHEN (NEW.trigger_requested_at IS NOT NULL)
+    EXECUTE FUNCTION notify_scheduled_task_trigger();
+
+ALTER TABLE scheduled_task_runs
+    ADD COLUMN IF NOT EXISTS trigger TEXT NOT NULL DEFAULT 'schedule';
@@ -0,0 +1 @@
+ALTER TABLE scheduled_task_runs DROP COLUMN IF EXISTS job_id;
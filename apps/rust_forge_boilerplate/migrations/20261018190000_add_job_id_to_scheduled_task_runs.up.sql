@@ -0,0 +1,2 @@
+ALTER TABLE scheduled_task_runs
+    ADD COLUMN IF NOT EXISTS job_id UUID;
@@ -0,0 +1 @@
+CREATE SEQUENCE IF NOT EXISTS lock_fencing_tokens;
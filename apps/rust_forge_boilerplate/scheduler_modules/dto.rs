@@ -0,0 +1,81 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::common::infrastructure::scheduler::{
+    config::{MisfirePolicy, OverlapPolicy},
+    control::RegisteredTask,
+    history::{RunOutcome, RunTrigger, TaskRun},
+};
+
+#[derive(Debug, Deserialize)]
+pub struct TaskRunListQuery {
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TaskRunResponse {
+    pub id: Uuid,
+    pub scheduled_at: DateTime<Utc>,
+    pub trigger: RunTrigger,
+    pub instance: String,
+    pub outcome: RunOutcome,
+    pub error: Option<String>,
+    pub job_id: Option<Uuid>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+impl From<TaskRun> for TaskRunResponse {
+    fn from(run: TaskRun) -> Self {
+        Self {
+            id: run.id,
+            scheduled_at: run.scheduled_at,
+            trigger: run.trigger,
+            instance: run.instance,
+            outcome: run.outcome,
+            error: run.error,
+            job_id: run.job_id,
+            started_at: run.started_at,
+            finished_at: run.finished_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScheduledTaskResponse {
+    pub name: String,
+    pub schedule: String,
+    pub time_zone: String,
+    pub enabled: bool,
+    pub paused: bool,
+    pub paused_at: Option<DateTime<Utc>>,
+    pub misfire: MisfirePolicy,
+    pub overlap: OverlapPolicy,
+    pub timeout_secs: i64,
+    /// `None` while the task is paused or disabled.
+    pub next_run_at: Option<DateTime<Utc>>,
+    pub trigger_requested_at: Option<DateTime<Utc>>,
+    pub last_run: Option<TaskRunResponse>,
+    pub registered_at: DateTime<Utc>,
+}
+
+impl ScheduledTaskResponse {
+    pub fn new(task: RegisteredTask, last_run: Option<TaskRun>) -> Self {
+        Self {
+            next_run_at: task.next_run_after(Utc::now()),
+            last_run: last_run.map(Into::into),
+            name: task.name,
+            schedule: task.schedule,
+            time_zone: task.time_zone,
+            enabled: task.enabled,
+            paused: task.paused,
+            paused_at: task.paused_at,
+            misfire: task.misfire,
+            overlap: task.overlap,
+            timeout_secs: task.timeout_secs,
+            trigger_requested_at: task.trigger_requested_at,
+            registered_at: task.registered_at,
+        }
+    }
+}
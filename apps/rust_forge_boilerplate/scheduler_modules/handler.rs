@@ -0,0 +1,77 @@
+use actix_web::{HttpResponse, web};
+use std::sync::Arc;
+
+use crate::{
+    common::middleware::admin::AdminAccess,
+    scheduler_modules::{
+        dto::TaskRunListQuery,
+        service::{SchedulerService, SchedulerServiceTrait},
+    },
+};
+
+pub async fn list_tasks(
+    _admin: AdminAccess,
+    service: web::Data<Arc<SchedulerService>>,
+) -> HttpResponse {
+    match service.list_tasks().await {
+        Ok(response) => HttpResponse::Ok().json(response),
+        Err(error) => error.http_response_builder(),
+    }
+}
+
+pub async fn get_task(
+    _admin: AdminAccess,
+    service: web::Data<Arc<SchedulerService>>,
+    name: web::Path<String>,
+) -> HttpResponse {
+    match service.get_task(&name).await {
+        Ok(response) => HttpResponse::Ok().json(response),
+        Err(error) => error.http_response_builder(),
+    }
+}
+
+pub async fn list_task_runs(
+    _admin: AdminAccess,
+    service: web::Data<Arc<SchedulerService>>,
+    name: web::Path<String>,
+    query: web::Query<TaskRunListQuery>,
+) -> HttpResponse {
+    match service.list_task_runs(&name, query.into_inner()).await {
+        Ok(response) => HttpResponse::Ok().json(response),
+        Err(error) => error.http_response_builder(),
+    }
+}
+
+pub async fn pause_task(
+    _admin: AdminAccess,
+    service: web::Data<Arc<SchedulerService>>,
+    name: web::Path<String>,
+) -> HttpResponse {
+    match service.set_task_paused(&name, true).await {
+        Ok(response) => HttpResponse::Ok().json(response),
+        Err(error) => error.http_response_builder(),
+    }
+}
+
+pub async fn resume_task(
+    _admin: AdminAccess,
+    service: web::Data<Arc<SchedulerService>>,
+    name: web::Path<String>,
+) -> HttpResponse {
+    match service.set_task_paused(&name, false).await {
+        Ok(response) => HttpResponse::Ok().json(response),
+        Err(error) => error.http_response_builder(),
+    }
+}
+
+/// Accepted rather than done: the leading scheduler starts the run.
+pub async fn trigger_task(
+    _admin: AdminAccess,
+    service: web::Data<Arc<SchedulerService>>,
+    name: web::Path<String>,
+) -> HttpResponse {
+    match service.trigger_task(&name).await {
+        Ok(response) => HttpResponse::Accepted().json(response),
+        Err(error) => error.http_response_builder(),
+    }
+}
@@ -0,0 +1,19 @@
+pub mod dto;
+pub mod handler;
+use actix_web::web;
+pub mod service;
+
+pub fn configure_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/api/admin/scheduler")
+            .route("/tasks", web::get().to(handler::list_tasks))
+            .route("/tasks/{name}", web::get().to(handler::get_task))
+            .route("/tasks/{name}/runs", web::get().to(handler::list_task_runs))
+            .route("/tasks/{name}/pause", web::post().to(handler::pause_task))
+            .route("/tasks/{name}/resume", web::post().to(handler::resume_task))
+            .route(
+                "/tasks/{name}/trigger",
+                web::post().to(handler::trigger_task),
+            ),
+    );
+}
@@ -0,0 +1,130 @@
+use actix_web::http::StatusCode;
+
+use crate::{
+    common::{
+        infrastructure::scheduler::{
+            control::{RegisteredTask, RegisteredTaskStore},
+            history::TaskRunStore,
+        },
+        utils::error::{AppError, AppResult},
+    },
+    scheduler_modules::dto::{ScheduledTaskResponse, TaskRunListQuery, TaskRunResponse},
+};
+
+const DEFAULT_RUN_LIMIT: i64 = 20;
+const MAX_RUN_LIMIT: i64 = 100;
+
+#[async_trait::async_trait]
+pub trait SchedulerServiceTrait: Send + Sync {
+    async fn list_tasks(&self) -> AppResult<Vec<ScheduledTaskResponse>>;
+    async fn get_task(&self, name: &str) -> AppResult<ScheduledTaskResponse>;
+    async fn list_task_runs(
+        &self,
+        name: &str,
+        query: TaskRunListQuery,
+    ) -> AppResult<Vec<TaskRunResponse>>;
+    async fn set_task_paused(&self, name: &str, paused: bool) -> AppResult<ScheduledTaskResponse>;
+    /// Asks the leading scheduler to run the task now.
+    async fn trigger_task(&self, name: &str) -> AppResult<ScheduledTaskResponse>;
+}
+
+pub struct SchedulerService {
+    pub tasks: RegisteredTaskStore,
+    pub runs: TaskRunStore,
+}
+
+fn not_found() -> AppError {
+    AppError::new(3002, Some(StatusCode::NOT_FOUND))
+}
+
+impl SchedulerService {
+    async fn with_last_run(&self, task: RegisteredTask) -> AppResult<ScheduledTaskResponse> {
+        let last_run = self
+            .runs
+            .list(&task.name, 1)
+            .await
+            .map_err(AppError::map_db_error)?
+            .pop();
+        Ok(ScheduledTaskResponse::new(task, last_run))
+    }
+}
+
+#[async_trait::async_trait]
+impl SchedulerServiceTrait for SchedulerService {
+    async fn list_tasks(&self) -> AppResult<Vec<ScheduledTaskResponse>> {
+        let tasks = self.tasks.list().await.map_err(AppError::map_db_error)?;
+
+        let mut responses = Vec::with_capacity(tasks.len());
+        for task in tasks {
+            responses.push(self.with_last_run(task).await?);
+        }
+        Ok(responses)
+    }
+
+    async fn get_task(&self, name: &str) -> AppResult<ScheduledTaskResponse> {
+        let task = self
+            .tasks
+            .get(name)
+            .await
+            .map_err(AppError::map_db_error)?
+            .ok_or_else(not_found)?;
+        self.with_last_run(task).await
+    }
+
+    async fn list_task_runs(
+        &self,
+        name: &str,
+        query: TaskRunListQuery,
+    ) -> AppResult<Vec<TaskRunResponse>> {
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_RUN_LIMIT)
+            .clamp(1, MAX_RUN_LIMIT);
+        let runs = self
+            .runs
+            .list(name, limit)
+            .await
+            .map_err(AppError::map_db_error)?;
+        Ok(runs.into_iter().map(Into::into).collect())
+    }
+
+    async fn set_task_paused(&self, name: &str, paused: bool) -> AppResult<ScheduledTaskResponse> {
+        let task = self
+            .tasks
+            .set_paused(name, paused)
+            .await
+            .map_err(AppError::map_db_error)?
+            .ok_or_else(not_found)?;
+
+        if paused {
+            tracing::info!("Paused scheduled task {}", name);
+        } else {
+            tracing::info!("Resumed scheduled task {}", name);
+        }
+        self.with_last_run(task).await
+    }
+
+    async fn trigger_task(&self, name: &str) -> AppResult<ScheduledTaskResponse> {
+        let task = self
+            .tasks
+            .get(name)
+            .await
+            .map_err(AppError::map_db_error)?
+            .ok_or_else(not_found)?;
+        // No scheduler follows a disabled task, so nothing would pick the
+        // request up.
+        if !task.enabled {
+            return Err(AppError::new(3015, Some(StatusCode::CONFLICT)));
+        }
+
+        let task = self
+            .tasks
+            .request_trigger(name)
+            .await
+            .map_err(AppError::map_db_error)?
+            .ok_or_else(not_found)?;
+
+        tracing::info!("Requested a manual run of scheduled task {}", name);
+        self.with_last_run(task).await
+    }
+}
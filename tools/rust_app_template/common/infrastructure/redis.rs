@@ -8,10 +8,8 @@ pub trait RedisClient {
 pub struct RedisClientImpl;
 
 impl RedisClient for RedisClientImpl {
-    fn create_connection(redis_url: &str) -> impl Future<Output = Result<ConnectionManager, redis::RedisError>> + Send {
-        async move {
-            let client = Client::open(redis_url)?;
-            ConnectionManager::new(client).await
-        }
+    async fn create_connection(redis_url: &str) -> Result<ConnectionManager, redis::RedisError> {
+        let client = Client::open(redis_url)?;
+        ConnectionManager::new(client).await
     }
 }
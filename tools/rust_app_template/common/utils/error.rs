@@ -55,7 +55,7 @@ fn load_service_error_messages() -> &'static HashMap<String, String> {
 }
 
 fn get_error_message(code: u16, fallback: &str) -> String {
-    let message = if code >= 1000 && code < 2000 {
+    let message = if (1000..2000).contains(&code) {
         let global_messages = load_global_error_messages();
         global_messages.get(&code.to_string()).cloned()
     } else {
@@ -77,12 +77,12 @@ impl AppError {
                 errors: None,
             };
         };
-        return Self {
+        Self {
             code,
             message,
             http_code: StatusCode::INTERNAL_SERVER_ERROR,
             errors: None,
-        };
+        }
     }
 
     pub fn map_db_error(err: sqlx::Error) -> Self {
@@ -91,31 +91,30 @@ impl AppError {
             let constraint = db_err.constraint().unwrap_or_default();
             if code == "23505" {
                 let parts: Vec<&str> = constraint.split('_').collect();
-                let field: String;
-                if parts.len() < 3 {
-                    field = constraint.to_string();
+                let field = if parts.len() < 3 {
+                    constraint.to_string()
                 } else {
                     let field_parts = &parts[1..parts.len() - 1];
 
-                    field = field_parts.join(" ");
-                }
+                    field_parts.join(" ")
+                };
 
                 let message = get_error_message(3011, "a database error occurred");
                 return Self {
                     code: 3005,
-                    message: message,
+                    message,
                     http_code: StatusCode::INTERNAL_SERVER_ERROR,
                     errors: Some(format!("{} already exists", field)),
                 };
             }
         }
         let message = get_error_message(3005, "a database error occurred");
-        return Self {
+        Self {
             code: 3005,
-            message: message,
+            message,
             http_code: StatusCode::INTERNAL_SERVER_ERROR,
             errors: None,
-        };
+        }
     }
 
     pub fn http_response_builder(&self) -> HttpResponse {